@@ -33,18 +33,33 @@ class DriveAPI {
     return this.request("/api/files/list");
   }
 
+  async getUsage() {
+    return this.request("/api/files/usage");
+  }
+
   async downloadFile(file) {
     return this.request(`/api/files/download/${file.id}`);
   }
-  getDownloadUrl(file) {
-    return `${this.baseUrl}/api/files/download/${file.id}`;
+  // URL firmada y temporal, usable en <video src>, <img src> o enlaces
+  async getDownloadUrl(file, { inline = true, expiresIn, bindIp = false } = {}) {
+    const data = await this.request(`/api/files/${file.id}/signed-url`, {
+      method: "POST",
+      headers: { "Content-Type": "application/json" },
+      body: JSON.stringify({
+        inline,
+        bind_ip: bindIp,
+        expires_in_seconds: expiresIn,
+      }),
+    });
+    return `${this.baseUrl}${data.url}`;
   }
   async uploadFile(file) {
     const mimeType = encodeURIComponent(
       file.type || "application/octet-stream",
     );
+    const filename = encodeURIComponent(file.name || "");
 
-    return this.request(`/api/files/upload?mime=${mimeType}`, {
+    return this.request(`/api/files/upload?mime=${mimeType}&filename=${filename}`, {
       method: "POST",
       headers: {
         "Content-Type": file.type || "application/octet-stream",
@@ -84,7 +99,7 @@ class DriveAPI {
   }
 
   static getFileName(file) {
-    return `${file.id.substring(0, 8)}...`;
+    return file.filename || `${file.id.substring(0, 8)}...`;
   }
 
   static formatFileSize(bytes) {
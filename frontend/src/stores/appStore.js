@@ -22,6 +22,7 @@ export const useAppStore = defineStore("app", () => {
   const uploading = ref(false);
   const uploadMessage = ref("");
   const uploadSuccess = ref(false);
+  const usage = ref(null);
 
   // Getters computados
   const fileStats = computed(() => ({
@@ -89,6 +90,7 @@ export const useAppStore = defineStore("app", () => {
   const logout = () => {
     isAuthenticated.value = false;
     files.value = [];
+    usage.value = null;
     token.value = "";
     error.value = "";
     currentView.value = "files";
@@ -102,6 +104,7 @@ export const useAppStore = defineStore("app", () => {
     try {
       const data = await driveAPI.listFiles();
       files.value = data.files || [];
+      await fetchUsage();
     } catch (err) {
       error.value = err.message;
       if (!isAuthenticated.value) throw err;
@@ -110,6 +113,15 @@ export const useAppStore = defineStore("app", () => {
     }
   };
 
+  const fetchUsage = async () => {
+    try {
+      usage.value = await driveAPI.getUsage();
+    } catch (err) {
+      console.error("Error fetching usage:", err);
+      usage.value = null;
+    }
+  };
+
   const uploadFile = async (file) => {
     uploading.value = true;
     uploadMessage.value = "";
@@ -171,6 +183,7 @@ export const useAppStore = defineStore("app", () => {
     uploading,
     uploadMessage,
     uploadSuccess,
+    usage,
 
     // Getters
     fileStats,
@@ -180,6 +193,7 @@ export const useAppStore = defineStore("app", () => {
     login,
     logout,
     fetchFiles,
+    fetchUsage,
     uploadFile,
     setSelectedFile,
     clearSelectedFile,
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE blobs (
+    hash TEXT PRIMARY KEY NOT NULL,
+    size BIGINT NOT NULL,
+    refcount INTEGER NOT NULL
+);
+
+-- Registrar los blobs existentes; el tamaño se completa al migrarlos en disco
+INSERT INTO blobs (hash, size, refcount)
+SELECT hash, 0, COUNT(*) FROM files GROUP BY hash;
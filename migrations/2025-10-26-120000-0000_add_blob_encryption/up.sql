@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Los blobs existentes quedan en texto plano hasta que se cifran al arrancar
+ALTER TABLE blobs ADD COLUMN encrypted BOOLEAN NOT NULL DEFAULT 0;
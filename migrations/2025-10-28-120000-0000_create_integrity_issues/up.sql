@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE integrity_issues (
+    hash TEXT PRIMARY KEY NOT NULL,
+    status TEXT NOT NULL,
+    detail TEXT NOT NULL,
+    detected_at BIGINT NOT NULL
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE usuarios DROP COLUMN quota_bytes;
+ALTER TABLE usuarios DROP COLUMN used_bytes;
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+-- Bytes ocupados por los archivos de cada usuario y cuota propia (NULL = la
+-- cuota por defecto de la configuración)
+ALTER TABLE usuarios ADD COLUMN used_bytes BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE usuarios ADD COLUMN quota_bytes BIGINT;
+
+UPDATE usuarios SET used_bytes = COALESCE((
+    SELECT SUM(blobs.size)
+    FROM files
+    JOIN blobs ON blobs.hash = files.hash
+    WHERE files.owner_id = usuarios.id
+), 0);
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE tus_uploads DROP COLUMN filename;
+ALTER TABLE files DROP COLUMN last_accessed_at;
+ALTER TABLE files DROP COLUMN updated_at;
+ALTER TABLE files DROP COLUMN created_at;
+ALTER TABLE files DROP COLUMN size;
+ALTER TABLE files DROP COLUMN filename;
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+ALTER TABLE files ADD COLUMN filename TEXT NOT NULL DEFAULT '';
+ALTER TABLE files ADD COLUMN size BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE files ADD COLUMN created_at BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE files ADD COLUMN updated_at BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE files ADD COLUMN last_accessed_at BIGINT;
+
+-- Los archivos existentes no tienen nombre original: se usa su ID
+UPDATE files SET
+    filename = id,
+    size = COALESCE((SELECT size FROM blobs WHERE blobs.hash = files.hash), 0),
+    created_at = CAST(strftime('%s', 'now') AS INTEGER),
+    updated_at = CAST(strftime('%s', 'now') AS INTEGER);
+
+ALTER TABLE tus_uploads ADD COLUMN filename TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX idx_files_folder;
+ALTER TABLE files DROP COLUMN folder_id;
+DROP INDEX idx_folders_owner_parent;
+DROP TABLE folders;
@@ -0,0 +1,17 @@
+-- Your SQL goes here
+CREATE TABLE folders (
+    id TEXT PRIMARY KEY NOT NULL,
+    owner_id TEXT NOT NULL,
+    -- NULL = carpeta en la raíz del usuario
+    parent_id TEXT REFERENCES folders(id),
+    name TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
+
+CREATE INDEX idx_folders_owner_parent ON folders(owner_id, parent_id);
+
+-- NULL = archivo en la raíz del usuario
+ALTER TABLE files ADD COLUMN folder_id TEXT;
+
+CREATE INDEX idx_files_folder ON files(folder_id);
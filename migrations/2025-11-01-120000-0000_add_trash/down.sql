@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX idx_files_deleted;
+ALTER TABLE files DROP COLUMN deleted_at;
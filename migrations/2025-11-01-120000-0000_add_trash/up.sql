@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- NULL = archivo activo; con fecha = archivo en la papelera desde ese momento
+ALTER TABLE files ADD COLUMN deleted_at BIGINT;
+
+CREATE INDEX idx_files_deleted ON files(owner_id, deleted_at);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX idx_file_versions_hash;
+DROP TABLE file_versions;
+ALTER TABLE files DROP COLUMN version_created_at;
+ALTER TABLE files DROP COLUMN version;
@@ -0,0 +1,18 @@
+-- Your SQL goes here
+-- Número de la versión actual del contenido y cuándo se subió
+ALTER TABLE files ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE files ADD COLUMN version_created_at BIGINT NOT NULL DEFAULT 0;
+UPDATE files SET version_created_at = created_at;
+
+-- Versiones anteriores; cada una mantiene su propia referencia al blob
+CREATE TABLE file_versions (
+    file_id TEXT NOT NULL REFERENCES files(id),
+    version INTEGER NOT NULL,
+    hash TEXT NOT NULL,
+    mime TEXT NOT NULL,
+    size BIGINT NOT NULL,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (file_id, version)
+);
+
+CREATE INDEX idx_file_versions_hash ON file_versions(hash);
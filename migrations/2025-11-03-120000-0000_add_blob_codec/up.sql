@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Compresión aplicada al contenido antes de cifrarlo ("none" o "zstd")
+ALTER TABLE blobs ADD COLUMN codec TEXT NOT NULL DEFAULT 'none';
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- SHA-256 del contenido original para `Content-Digest` (RFC 9530)
+ALTER TABLE blobs ADD COLUMN sha256 TEXT;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+-- Etiquetas libres de cada archivo, normalizadas en minúsculas
+CREATE TABLE file_tags (
+    file_id TEXT NOT NULL REFERENCES files(id),
+    tag TEXT NOT NULL,
+    PRIMARY KEY (file_id, tag)
+);
+
+CREATE INDEX idx_file_tags_tag ON file_tags(tag);
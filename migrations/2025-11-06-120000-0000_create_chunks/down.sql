@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE blob_chunks;
+DROP TABLE chunks;
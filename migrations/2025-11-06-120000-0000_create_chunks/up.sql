@@ -0,0 +1,20 @@
+-- Your SQL goes here
+-- Trozos de contenido (FastCDC) compartidos entre blobs, indexados por su hash
+CREATE TABLE chunks (
+    hash TEXT PRIMARY KEY NOT NULL,
+    size BIGINT NOT NULL,
+    codec TEXT NOT NULL DEFAULT 'none',
+    refcount INTEGER NOT NULL DEFAULT 1
+);
+
+-- Manifiesto de los blobs guardados por trozos: qué trozo ocupa cada posición
+CREATE TABLE blob_chunks (
+    blob_hash TEXT NOT NULL,
+    seq INTEGER NOT NULL,
+    chunk_hash TEXT NOT NULL REFERENCES chunks(hash),
+    byte_offset BIGINT NOT NULL,
+    size BIGINT NOT NULL,
+    PRIMARY KEY (blob_hash, seq)
+);
+
+CREATE INDEX idx_blob_chunks_chunk_hash ON blob_chunks(chunk_hash);
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+-- Enlaces públicos para descargar un archivo sin cuenta
+CREATE TABLE share_links (
+    token TEXT PRIMARY KEY NOT NULL,
+    file_id TEXT NOT NULL REFERENCES files(id),
+    owner_id TEXT NOT NULL REFERENCES usuarios(id),
+    -- Hash Argon2 de la contraseña del enlace, si tiene
+    password_hash TEXT,
+    expires_at BIGINT,
+    max_downloads INTEGER,
+    download_count INTEGER NOT NULL DEFAULT 0,
+    created_at BIGINT NOT NULL
+);
+
+CREATE INDEX idx_share_links_file_id ON share_links(file_id);
+CREATE INDEX idx_share_links_owner_id ON share_links(owner_id);
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+-- Permisos para que otro usuario acceda a un archivo o a una carpeta (con
+-- todo su contenido) como lector ("viewer") o editor ("editor")
+CREATE TABLE grants (
+    id TEXT PRIMARY KEY NOT NULL,
+    owner_id TEXT NOT NULL REFERENCES usuarios(id),
+    grantee_id TEXT NOT NULL REFERENCES usuarios(id),
+    file_id TEXT REFERENCES files(id),
+    folder_id TEXT REFERENCES folders(id),
+    role TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    CHECK ((file_id IS NULL) <> (folder_id IS NULL))
+);
+
+CREATE UNIQUE INDEX idx_grants_grantee_file ON grants(grantee_id, file_id)
+    WHERE file_id IS NOT NULL;
+CREATE UNIQUE INDEX idx_grants_grantee_folder ON grants(grantee_id, folder_id)
+    WHERE folder_id IS NOT NULL;
+CREATE INDEX idx_grants_owner_id ON grants(owner_id);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE team_members;
+DROP TABLE teams;
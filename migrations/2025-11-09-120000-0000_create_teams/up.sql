@@ -0,0 +1,22 @@
+-- Your SQL goes here
+-- Equipos con un espacio común. Los archivos y carpetas de ese espacio usan
+-- el id del equipo como owner_id, así que su uso se cuenta en el equipo y no
+-- en quien los subió. quota_bytes NULL = la cuota por defecto.
+CREATE TABLE teams (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    used_bytes BIGINT NOT NULL DEFAULT 0,
+    quota_bytes BIGINT,
+    created_at BIGINT NOT NULL
+);
+
+-- Miembros de cada equipo con su rol: "owner", "admin", "member" o "viewer"
+CREATE TABLE team_members (
+    team_id TEXT NOT NULL REFERENCES teams(id),
+    user_id TEXT NOT NULL REFERENCES usuarios(id),
+    role TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    PRIMARY KEY (team_id, user_id)
+);
+
+CREATE INDEX idx_team_members_user_id ON team_members(user_id);
@@ -0,0 +1,30 @@
+-- Your SQL goes here
+-- Enlaces para que personas sin cuenta suban archivos a una carpeta
+CREATE TABLE upload_links (
+    token TEXT PRIMARY KEY NOT NULL,
+    folder_id TEXT NOT NULL REFERENCES folders(id),
+    owner_id TEXT NOT NULL REFERENCES usuarios(id),
+    -- Hash Argon2 de la contraseña del enlace, si tiene
+    password_hash TEXT,
+    expires_at BIGINT,
+    max_files INTEGER,
+    max_file_size BIGINT,
+    -- Tipos MIME aceptados separados por comas ("image/*,application/pdf");
+    -- NULL acepta cualquiera
+    allowed_mime_types TEXT,
+    upload_count INTEGER NOT NULL DEFAULT 0,
+    created_at BIGINT NOT NULL
+);
+
+CREATE INDEX idx_upload_links_folder_id ON upload_links(folder_id);
+CREATE INDEX idx_upload_links_owner_id ON upload_links(owner_id);
+
+-- Archivos recibidos por cada enlace y el nombre que dio quien los subió
+CREATE TABLE upload_link_files (
+    file_id TEXT PRIMARY KEY NOT NULL REFERENCES files(id),
+    token TEXT NOT NULL REFERENCES upload_links(token),
+    uploader_name TEXT,
+    created_at BIGINT NOT NULL
+);
+
+CREATE INDEX idx_upload_link_files_token ON upload_link_files(token);
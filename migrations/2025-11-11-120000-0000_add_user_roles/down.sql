@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX idx_usuarios_username;
+ALTER TABLE usuarios DROP COLUMN sessions_revoked_at;
+ALTER TABLE usuarios DROP COLUMN password_reset_required;
+ALTER TABLE usuarios DROP COLUMN disabled_at;
+ALTER TABLE usuarios DROP COLUMN role;
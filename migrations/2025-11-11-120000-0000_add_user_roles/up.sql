@@ -0,0 +1,12 @@
+-- Your SQL goes here
+-- Rol de la cuenta ('user' o 'admin') y estado administrado por los
+-- administradores
+ALTER TABLE usuarios ADD COLUMN role TEXT NOT NULL DEFAULT 'user';
+-- Momento en que se deshabilitó la cuenta; NULL = habilitada
+ALTER TABLE usuarios ADD COLUMN disabled_at BIGINT;
+-- La contraseña la fijó un administrador y el usuario debe cambiarla
+ALTER TABLE usuarios ADD COLUMN password_reset_required BOOLEAN NOT NULL DEFAULT 0;
+-- Los tokens emitidos antes de este momento dejan de valer
+ALTER TABLE usuarios ADD COLUMN sessions_revoked_at BIGINT;
+
+CREATE INDEX idx_usuarios_username ON usuarios(username);
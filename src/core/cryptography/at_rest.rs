@@ -0,0 +1,441 @@
+use aes_gcm::aead::consts::U12;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{Context, Result, anyhow, bail};
+use once_cell::sync::OnceCell;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use zeroize::Zeroizing;
+
+use crate::core::utils::master_key_path;
+
+// Formato de un blob cifrado:
+//
+//   "PFE1" | chunk_size (u32 LE) | nonce_envoltura (12) | clave_datos_envuelta (32 + 16)
+//   | prefijo_nonce (7) | bloque_0 | bloque_1 | ... | bloque_n
+//
+// Cada bloque es `chunk_size` bytes de texto plano (el último puede ser más
+// corto) cifrados con AES-256-GCM bajo la clave de datos del blob. El nonce de
+// cada bloque es prefijo || índice (u32 BE) || marca_de_último, y la cabecera
+// completa va como AAD, así que no se pueden reordenar, truncar ni mezclar
+// bloques de distintos blobs sin que falle la verificación.
+
+const MAGIC: &[u8; 4] = b"PFE1";
+const TAG_LEN: usize = 16;
+const NONCE_PREFIX_LEN: usize = 7;
+const WRAP_NONCE_LEN: usize = 12;
+const WRAPPED_KEY_LEN: usize = 32 + TAG_LEN;
+
+/// Tamaño del texto plano de cada bloque cifrado
+pub const CHUNK_SIZE: usize = 64 * 1024;
+/// Tamaño de la cabecera de un blob cifrado
+pub const HEADER_LEN: usize = 4 + 4 + WRAP_NONCE_LEN + WRAPPED_KEY_LEN + NONCE_PREFIX_LEN;
+
+/// Clave maestra del servidor; envuelve las claves de datos de cada blob
+pub struct MasterKey {
+    cipher: Aes256Gcm,
+}
+
+/// Cabecera ya validada de un blob cifrado, con su clave de datos desenvuelta
+pub struct BlobHeader {
+    cipher: Aes256Gcm,
+    raw: [u8; HEADER_LEN],
+    chunk_size: usize,
+}
+
+impl MasterKey {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+
+        let key_bytes = if path.exists() {
+            let key_bytes = Zeroizing::new(fs::read(path)?);
+            if key_bytes.len() != 32 {
+                return Err("Invalid key length: must be 32 bytes".into());
+            }
+            key_bytes
+        } else {
+            let mut key_bytes = Zeroizing::new(vec![0u8; 32]);
+            getrandom::fill(&mut key_bytes).map_err(|e| e.to_string())?;
+
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            // Se crea ya con permisos 0600: la clave nunca queda legible por
+            // otros usuarios, ni siquiera entre la creación y la escritura
+            let mut opciones = fs::OpenOptions::new();
+            opciones.write(true).create_new(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                opciones.mode(0o600);
+            }
+            let mut archivo = opciones.open(path)?;
+            archivo.write_all(&key_bytes)?;
+            archivo.sync_all()?;
+
+            key_bytes
+        };
+
+        let cipher = Aes256Gcm::new_from_slice(&key_bytes).map_err(|e| e.to_string())?;
+        Ok(Self { cipher })
+    }
+
+    /// Cifra todo `reader` en `writer` con una clave de datos nueva.
+    /// Devuelve la cantidad de bytes de texto plano procesados.
+    pub async fn encrypt_stream<R, W>(&self, reader: &mut R, writer: &mut W) -> Result<u64>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        let mut data_key = Zeroizing::new([0u8; 32]);
+        let mut wrap_nonce = [0u8; WRAP_NONCE_LEN];
+        let mut prefix = [0u8; NONCE_PREFIX_LEN];
+        getrandom::fill(data_key.as_mut())
+            .and_then(|_| getrandom::fill(&mut wrap_nonce))
+            .and_then(|_| getrandom::fill(&mut prefix))
+            .map_err(|e| anyhow!("Error al generar material aleatorio: {}", e))?;
+
+        let chunk_size = (CHUNK_SIZE as u32).to_le_bytes();
+        let wrapped = self
+            .cipher
+            .encrypt(
+                &Nonce::from(wrap_nonce),
+                Payload {
+                    msg: data_key.as_ref(),
+                    aad: &[&MAGIC[..], &chunk_size].concat(),
+                },
+            )
+            .map_err(|_| anyhow!("Error al envolver la clave de datos"))?;
+
+        let mut raw = [0u8; HEADER_LEN];
+        raw[..4].copy_from_slice(MAGIC);
+        raw[4..8].copy_from_slice(&chunk_size);
+        raw[8..20].copy_from_slice(&wrap_nonce);
+        raw[20..20 + WRAPPED_KEY_LEN].copy_from_slice(&wrapped);
+        raw[20 + WRAPPED_KEY_LEN..].copy_from_slice(&prefix);
+        writer.write_all(&raw).await?;
+
+        let cipher = Aes256Gcm::new_from_slice(data_key.as_ref())
+            .map_err(|_| anyhow!("Clave de datos inválida"))?;
+
+        // Se lee un bloque por adelantado para saber cuál es el último
+        let mut actual = vec![0u8; CHUNK_SIZE];
+        let mut siguiente = vec![0u8; CHUNK_SIZE];
+        let mut leidos = read_full(reader, &mut actual).await?;
+        let mut index: u32 = 0;
+        let mut total: u64 = 0;
+
+        loop {
+            let proximos = if leidos == CHUNK_SIZE {
+                read_full(reader, &mut siguiente).await?
+            } else {
+                0
+            };
+            let ultimo = proximos == 0;
+
+            let bloque = cipher
+                .encrypt(
+                    &chunk_nonce(&prefix, index, ultimo),
+                    Payload {
+                        msg: &actual[..leidos],
+                        aad: &raw,
+                    },
+                )
+                .map_err(|_| anyhow!("Error al cifrar bloque {}", index))?;
+            writer.write_all(&bloque).await?;
+            total += leidos as u64;
+
+            if ultimo {
+                break;
+            }
+            std::mem::swap(&mut actual, &mut siguiente);
+            leidos = proximos;
+            index = index
+                .checked_add(1)
+                .ok_or_else(|| anyhow!("Archivo demasiado grande para cifrar"))?;
+        }
+
+        writer.flush().await?;
+        Ok(total)
+    }
+
+    /// Lee y valida la cabecera de un blob cifrado, desenvolviendo su clave de datos
+    pub async fn read_header<R>(&self, reader: &mut R) -> Result<BlobHeader>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+    {
+        let mut raw = [0u8; HEADER_LEN];
+        if read_full(reader, &mut raw).await? != HEADER_LEN || &raw[..4] != MAGIC {
+            bail!("Cabecera de blob cifrado inválida");
+        }
+
+        let chunk_size = u32::from_le_bytes([raw[4], raw[5], raw[6], raw[7]]) as usize;
+        if chunk_size == 0 {
+            bail!("Cabecera de blob cifrado inválida");
+        }
+
+        let mut wrap_nonce = [0u8; WRAP_NONCE_LEN];
+        wrap_nonce.copy_from_slice(&raw[8..20]);
+        let data_key = Zeroizing::new(
+            self.cipher
+                .decrypt(
+                    &Nonce::from(wrap_nonce),
+                    Payload {
+                        msg: &raw[20..20 + WRAPPED_KEY_LEN],
+                        aad: &raw[..8],
+                    },
+                )
+                .map_err(|_| anyhow!("No se pudo desenvolver la clave de datos del blob"))?,
+        );
+        let cipher =
+            Aes256Gcm::new_from_slice(&data_key).map_err(|_| anyhow!("Clave de datos inválida"))?;
+
+        Ok(BlobHeader {
+            cipher,
+            raw,
+            chunk_size,
+        })
+    }
+}
+
+impl BlobHeader {
+    /// Tamaño de texto plano de cada bloque
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Posición en el blob cifrado donde empieza el bloque `index`
+    pub fn chunk_offset(&self, index: u64) -> u64 {
+        HEADER_LEN as u64 + index * (self.chunk_size + TAG_LEN) as u64
+    }
+
+    /// Descifra bloques desde `reader` (posicionado al inicio del bloque
+    /// `first_index`), descarta los primeros `skip` bytes de texto plano y
+    /// escribe como máximo `limit` bytes.
+    pub async fn decrypt_chunks<R, W>(
+        &self,
+        reader: &mut R,
+        writer: &mut W,
+        first_index: u32,
+        mut skip: u64,
+        limit: u64,
+    ) -> Result<u64>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        let prefix = &self.raw[HEADER_LEN - NONCE_PREFIX_LEN..];
+        let block = self.chunk_size + TAG_LEN;
+
+        let mut actual = vec![0u8; block];
+        let mut siguiente = vec![0u8; block];
+        let mut leidos = read_full(reader, &mut actual).await?;
+        let mut index = first_index;
+        let mut total: u64 = 0;
+
+        loop {
+            if leidos < TAG_LEN {
+                bail!("Blob cifrado truncado (bloque {})", index);
+            }
+
+            let proximos = if leidos == block {
+                read_full(reader, &mut siguiente).await?
+            } else {
+                0
+            };
+            let ultimo = proximos == 0;
+
+            let plano = self
+                .cipher
+                .decrypt(
+                    &chunk_nonce(prefix, index, ultimo),
+                    Payload {
+                        msg: &actual[..leidos],
+                        aad: &self.raw,
+                    },
+                )
+                .map_err(|_| anyhow!("Blob cifrado corrupto o manipulado (bloque {})", index))?;
+
+            let desde = skip.min(plano.len() as u64) as usize;
+            skip -= desde as u64;
+            let hasta = desde + (limit - total).min((plano.len() - desde) as u64) as usize;
+            writer.write_all(&plano[desde..hasta]).await?;
+            total += (hasta - desde) as u64;
+
+            if ultimo || total == limit {
+                break;
+            }
+            std::mem::swap(&mut actual, &mut siguiente);
+            leidos = proximos;
+            index = index
+                .checked_add(1)
+                .ok_or_else(|| anyhow!("Blob cifrado inválido"))?;
+        }
+
+        writer.flush().await?;
+        Ok(total)
+    }
+}
+
+fn chunk_nonce(prefix: &[u8], index: u32, ultimo: bool) -> Nonce<U12> {
+    let mut nonce = [0u8; 12];
+    nonce[..NONCE_PREFIX_LEN].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LEN..11].copy_from_slice(&index.to_be_bytes());
+    nonce[11] = ultimo as u8;
+    Nonce::from(nonce)
+}
+
+/// Lee hasta llenar `buf` o llegar a EOF. Devuelve los bytes leídos.
+async fn read_full<R>(reader: &mut R, buf: &mut [u8]) -> Result<usize>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let mut leidos = 0;
+    while leidos < buf.len() {
+        let n = reader
+            .read(&mut buf[leidos..])
+            .await
+            .context("Error al leer datos del blob")?;
+        if n == 0 {
+            break;
+        }
+        leidos += n;
+    }
+    Ok(leidos)
+}
+
+pub static MASTER_KEY: OnceCell<MasterKey> = OnceCell::new();
+
+/// Devuelve la clave maestra global, cargándola (o generándola) desde `master_key_path`.
+pub fn init_master_key() -> &'static MasterKey {
+    MASTER_KEY.get_or_init(|| {
+        MasterKey::from_file(master_key_path()).expect("No se pudo inicializar la clave maestra")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing::TempDir;
+
+    fn master_key(dir: &TempDir, nombre: &str) -> MasterKey {
+        MasterKey::from_file(dir.0.join(nombre)).unwrap()
+    }
+
+    /// Contenido de prueba que no se repite entre bloques
+    fn contenido(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    async fn cifrar(key: &MasterKey, data: &[u8]) -> Vec<u8> {
+        let mut blob = Vec::new();
+        let total = key.encrypt_stream(&mut &data[..], &mut blob).await.unwrap();
+        assert_eq!(total, data.len() as u64);
+        blob
+    }
+
+    /// Descifra `limit` bytes desde `start` leyendo solo desde el bloque
+    /// donde empieza el rango, como hace la lectura de rangos del storage
+    async fn descifrar(key: &MasterKey, blob: &[u8], start: u64, limit: u64) -> Result<Vec<u8>> {
+        let header = key.read_header(&mut &blob[..]).await?;
+        let chunk = header.chunk_size() as u64;
+        let index = start / chunk;
+        let desde = (header.chunk_offset(index) as usize).min(blob.len());
+        let mut plano = Vec::new();
+        header
+            .decrypt_chunks(
+                &mut &blob[desde..],
+                &mut plano,
+                index as u32,
+                start % chunk,
+                limit,
+            )
+            .await?;
+        Ok(plano)
+    }
+
+    #[tokio::test]
+    async fn ida_y_vuelta() {
+        let dir = TempDir::new();
+        let key = master_key(&dir, "master.key");
+        for len in [0, 1, CHUNK_SIZE, CHUNK_SIZE + 1] {
+            let data = contenido(len);
+            let blob = cifrar(&key, &data).await;
+            let bloques = len.div_ceil(CHUNK_SIZE).max(1);
+            assert_eq!(blob.len(), HEADER_LEN + len + bloques * TAG_LEN);
+            assert_eq!(descifrar(&key, &blob, 0, u64::MAX).await.unwrap(), data);
+        }
+    }
+
+    #[tokio::test]
+    async fn rangos_desde_un_bloque_intermedio() {
+        let dir = TempDir::new();
+        let key = master_key(&dir, "master.key");
+        let data = contenido(3 * CHUNK_SIZE + 100);
+        let blob = cifrar(&key, &data).await;
+
+        let casos = [
+            (0, 10),
+            (CHUNK_SIZE - 5, 10),
+            (CHUNK_SIZE, CHUNK_SIZE),
+            (CHUNK_SIZE + 7, 2 * CHUNK_SIZE),
+            (3 * CHUNK_SIZE + 50, 1000),
+        ];
+        for (inicio, largo) in casos {
+            let fin = (inicio + largo).min(data.len());
+            let plano = descifrar(&key, &blob, inicio as u64, largo as u64)
+                .await
+                .unwrap();
+            assert_eq!(plano, &data[inicio..fin], "rango {}+{}", inicio, largo);
+        }
+    }
+
+    #[tokio::test]
+    async fn detecta_manipulaciones() {
+        let dir = TempDir::new();
+        let key = master_key(&dir, "master.key");
+        let data = contenido(2 * CHUNK_SIZE + 5);
+        let blob = cifrar(&key, &data).await;
+        let header = key.read_header(&mut &blob[..]).await.unwrap();
+        let bloque = |i: u64| header.chunk_offset(i) as usize..header.chunk_offset(i + 1) as usize;
+
+        let mut alterado = blob.clone();
+        alterado[HEADER_LEN + 10] ^= 1;
+        assert!(descifrar(&key, &alterado, 0, u64::MAX).await.is_err());
+
+        // Sin el último bloque, el anterior no está marcado como último
+        let truncado = &blob[..bloque(2).start];
+        assert!(descifrar(&key, truncado, 0, u64::MAX).await.is_err());
+
+        let mut intercambiado = blob.clone();
+        let primero = blob[bloque(0)].to_vec();
+        intercambiado[bloque(0)].copy_from_slice(&blob[bloque(1)]);
+        intercambiado[bloque(1)].copy_from_slice(&primero);
+        assert!(descifrar(&key, &intercambiado, 0, u64::MAX).await.is_err());
+
+        let otra = master_key(&dir, "otra.key");
+        assert!(otra.read_header(&mut &blob[..]).await.is_err());
+        assert!(descifrar(&otra, &blob, 0, u64::MAX).await.is_err());
+    }
+
+    #[test]
+    fn from_file_reutiliza_la_clave_guardada() {
+        let dir = TempDir::new();
+        let path = dir.0.join("master.key");
+        MasterKey::from_file(&path).unwrap();
+        let guardada = fs::read(&path).unwrap();
+        assert_eq!(guardada.len(), 32);
+        MasterKey::from_file(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), guardada);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let modo = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(modo & 0o777, 0o600);
+        }
+    }
+}
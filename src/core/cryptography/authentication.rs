@@ -1,7 +1,9 @@
+use blake2::{Blake2b512, Digest};
 use chrono::{Duration, Utc};
 use rusty_paseto::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::io::Write;
 use std::path::Path;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -11,8 +13,33 @@ pub struct Claims {
     pub iat: i64,
 }
 
+/// Datos de una URL de descarga firmada
+#[derive(Debug)]
+pub struct DownloadClaims {
+    pub sub: String,
+    pub file_id: String,
+    /// IP a la que está atada la URL, si se pidió
+    pub ip: Option<String>,
+    pub iat: i64,
+}
+
 pub struct PasetoManager {
     key: PasetoSymmetricKey<V4, Local>,
+    /// Clave derivada de `key` para las URLs de descarga firmadas: un token
+    /// de descarga no sirve como token de sesión ni al revés
+    download_key: PasetoSymmetricKey<V4, Local>,
+}
+
+/// Deriva la clave de las URLs de descarga a partir de la clave de sesión
+fn derive_download_key(key: &[u8]) -> PasetoSymmetricKey<V4, Local> {
+    let mut hasher = Blake2b512::new();
+    hasher.update(b"privafile-signed-download-v1");
+    hasher.update(key);
+    let digest = hasher.finalize();
+
+    let mut key_array = [0u8; 32];
+    key_array.copy_from_slice(&digest[..32]);
+    PasetoSymmetricKey::<V4, Local>::from(Key::from(&key_array))
 }
 
 impl PasetoManager {
@@ -29,7 +56,10 @@ impl PasetoManager {
             key_array.copy_from_slice(&key_bytes);
             let key = PasetoSymmetricKey::<V4, Local>::from(Key::from(&key_array));
 
-            Ok(Self { key })
+            Ok(Self {
+                key,
+                download_key: derive_download_key(&key_array),
+            })
         } else {
             let key = Key::<32>::try_new_random()?;
 
@@ -37,15 +67,20 @@ impl PasetoManager {
                 fs::create_dir_all(parent)?;
             }
 
-            fs::write(path, key.as_ref())?;
-
+            // Igual que la clave maestra: se crea ya con permisos 0600
+            let mut opciones = fs::OpenOptions::new();
+            opciones.write(true).create_new(true);
             #[cfg(unix)]
             {
-                use std::os::unix::fs::PermissionsExt;
-                fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+                use std::os::unix::fs::OpenOptionsExt;
+                opciones.mode(0o600);
             }
+            let mut archivo = opciones.open(path)?;
+            archivo.write_all(key.as_ref())?;
+            archivo.sync_all()?;
 
             Ok(Self {
+                download_key: derive_download_key(key.as_ref()),
                 key: PasetoSymmetricKey::<V4, Local>::from(key),
             })
         }
@@ -98,4 +133,64 @@ impl PasetoManager {
 
         Ok(claims)
     }
+
+    /// Crea el token de una URL de descarga firmada para un solo archivo,
+    /// opcionalmente atada a la IP del cliente. Devuelve el token y su
+    /// expiración como timestamp.
+    pub fn create_download_token(
+        &self,
+        user_id: &str,
+        file_id: &str,
+        ip: Option<&str>,
+        expires_in_seconds: i64,
+    ) -> Result<(String, i64), Box<dyn std::error::Error>> {
+        let now = Utc::now();
+        let exp = now + Duration::seconds(expires_in_seconds);
+
+        let mut builder = PasetoBuilder::<V4, Local>::default();
+        builder
+            .set_claim(SubjectClaim::from(user_id))
+            .set_claim(ExpirationClaim::try_from(exp.to_rfc3339())?)
+            .set_claim(IssuedAtClaim::try_from(now.to_rfc3339())?)
+            .set_claim(CustomClaim::try_from(("fid", file_id))?);
+        if let Some(ip) = ip {
+            builder.set_claim(CustomClaim::try_from(("ip", ip))?);
+        }
+        let token = builder.build(&self.download_key)?;
+
+        Ok((token, exp.timestamp()))
+    }
+
+    pub fn verify_download_token(
+        &self,
+        token: &str,
+    ) -> Result<DownloadClaims, Box<dyn std::error::Error>> {
+        let verified_token =
+            PasetoParser::<V4, Local>::default().parse(token, &self.download_key)?;
+
+        let exp_str = verified_token["exp"]
+            .as_str()
+            .ok_or("Missing 'exp' claim")?;
+        let exp = chrono::DateTime::parse_from_rfc3339(exp_str)?.timestamp();
+        if exp < Utc::now().timestamp() {
+            return Err("Token expired".into());
+        }
+        let iat_str = verified_token["iat"]
+            .as_str()
+            .ok_or("Missing 'iat' claim")?;
+        let iat = chrono::DateTime::parse_from_rfc3339(iat_str)?.timestamp();
+
+        Ok(DownloadClaims {
+            sub: verified_token["sub"]
+                .as_str()
+                .ok_or("Missing 'sub' claim")?
+                .to_string(),
+            file_id: verified_token["fid"]
+                .as_str()
+                .ok_or("Missing 'fid' claim")?
+                .to_string(),
+            ip: verified_token["ip"].as_str().map(str::to_string),
+            iat,
+        })
+    }
 }
@@ -1 +1,2 @@
+pub(crate) mod at_rest;
 pub(crate) mod authentication;
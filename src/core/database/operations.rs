@@ -1,10 +1,20 @@
-use crate::core::database::schema::{files, usuarios};
+use crate::core::database::schema::{
+    blob_chunks, blobs, chunks, file_tags, file_versions, files, folders, grants, integrity_issues,
+    share_links, team_members, teams, tus_uploads, upload_link_files, upload_links, usuarios,
+};
 use crate::core::db_url;
-use crate::core::structs::{File, NuevoFile, NuevoUsuario, Usuario};
+use crate::core::storage::blobs::CODEC_CHUNKED;
+use crate::core::structs::{
+    Blob, BlobChunk, CambioLote, Chunk, File, FileVersion, Folder, Grant, IntegrityIssue,
+    NuevoBlob, NuevoChunk, NuevoFile, NuevoFileTag, NuevoFolder, NuevoGrant, NuevoShareLink,
+    NuevoTeam, NuevoTusUpload, NuevoUploadLink, NuevoUsuario, ServerStorage, ShareLink, Team,
+    TeamMember, TusUpload, UploadLink, UploadLinkFile, Usuario,
+};
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::sqlite::SqliteConnection;
 use once_cell::sync::OnceCell;
+use std::collections::HashMap;
 
 pub struct DbManager {
     pool: Pool<ConnectionManager<SqliteConnection>>,
@@ -40,11 +50,251 @@ impl DbManager {
         user_name: &str,
     ) -> Result<Usuario, diesel::result::Error> {
         let mut conn = self.get_conn();
-        usuarios::table.find(user_name).first(&mut conn)
+        usuarios::table
+            .filter(usuarios::username.eq(user_name))
+            .order(usuarios::id.asc())
+            .first(&mut conn)
     }
-    pub fn borrar_usuario(&self, user_id: &str) -> Result<usize, diesel::result::Error> {
+
+    /// Todos los usuarios ordenados por username
+    pub fn obtener_usuarios(&self) -> Result<Vec<Usuario>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        usuarios::table
+            .order(usuarios::username.asc())
+            .load::<Usuario>(&mut conn)
+    }
+
+    /// Borra al usuario con todo lo que no son archivos: carpetas, permisos,
+    /// enlaces, membresías y subidas reanudables, en una sola transacción.
+    /// Sus archivos se liberan antes con `borrar_file_con_blob`; si todavía
+    /// tiene alguno no se borra nada y devuelve `None`.
+    ///
+    /// # Retorna
+    /// Los IDs de las subidas reanudables borradas, para descartar sus datos
+    pub fn borrar_usuario(
+        &self,
+        user_id: &str,
+    ) -> Result<Option<Vec<String>>, diesel::result::Error> {
         let mut conn = self.get_conn();
-        diesel::delete(usuarios::table.find(user_id)).execute(&mut conn)
+        conn.transaction(|conn| {
+            let archivos: i64 = files::table
+                .filter(files::owner_id.eq(user_id))
+                .count()
+                .get_result(conn)?;
+            if archivos > 0 {
+                return Ok(None);
+            }
+
+            diesel::delete(
+                grants::table.filter(
+                    grants::owner_id
+                        .eq(user_id)
+                        .or(grants::grantee_id.eq(user_id)),
+                ),
+            )
+            .execute(conn)?;
+            diesel::delete(share_links::table.filter(share_links::owner_id.eq(user_id)))
+                .execute(conn)?;
+            let enlaces = upload_links::table
+                .filter(upload_links::owner_id.eq(user_id))
+                .select(upload_links::token);
+            diesel::delete(
+                upload_link_files::table.filter(upload_link_files::token.eq_any(enlaces)),
+            )
+            .execute(conn)?;
+            diesel::delete(upload_links::table.filter(upload_links::owner_id.eq(user_id)))
+                .execute(conn)?;
+            diesel::delete(team_members::table.filter(team_members::user_id.eq(user_id)))
+                .execute(conn)?;
+            diesel::delete(folders::table.filter(folders::owner_id.eq(user_id))).execute(conn)?;
+            let subidas: Vec<String> = tus_uploads::table
+                .filter(tus_uploads::owner_id.eq(user_id))
+                .select(tus_uploads::id)
+                .load(conn)?;
+            diesel::delete(tus_uploads::table.filter(tus_uploads::owner_id.eq(user_id)))
+                .execute(conn)?;
+
+            if diesel::delete(usuarios::table.find(user_id)).execute(conn)? == 0 {
+                return Err(diesel::result::Error::NotFound);
+            }
+            Ok(Some(subidas))
+        })
+    }
+
+    pub fn actualizar_rol_usuario(
+        &self,
+        user_id: &str,
+        role: &str,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::update(usuarios::table.find(user_id))
+            .set(usuarios::role.eq(role))
+            .execute(&mut conn)
+    }
+
+    /// Deshabilita la cuenta en `ahora`, cortando sus sesiones, o la vuelve
+    /// a habilitar con `None`
+    pub fn actualizar_deshabilitado_usuario(
+        &self,
+        user_id: &str,
+        ahora: Option<i64>,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        match ahora {
+            Some(ahora) => diesel::update(usuarios::table.find(user_id))
+                .set((
+                    usuarios::disabled_at.eq(ahora),
+                    usuarios::sessions_revoked_at.eq(ahora),
+                ))
+                .execute(&mut conn),
+            None => diesel::update(usuarios::table.find(user_id))
+                .set(usuarios::disabled_at.eq(None::<i64>))
+                .execute(&mut conn),
+        }
+    }
+
+    /// Cambia la contraseña y corta las sesiones abiertas antes de `ahora`.
+    /// Con `reset_requerido` el usuario deberá cambiarla al entrar.
+    pub fn actualizar_password_usuario(
+        &self,
+        user_id: &str,
+        password_hash: &str,
+        reset_requerido: bool,
+        ahora: i64,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::update(usuarios::table.find(user_id))
+            .set((
+                usuarios::password.eq(password_hash),
+                usuarios::password_reset_required.eq(reset_requerido),
+                usuarios::sessions_revoked_at.eq(ahora),
+            ))
+            .execute(&mut conn)
+    }
+
+    /// IDs de todos los archivos de un dueño, también los de la papelera
+    pub fn obtener_ids_files_de_dueno(
+        &self,
+        owner_id: &str,
+    ) -> Result<Vec<String>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        files::table
+            .filter(files::owner_id.eq(owner_id))
+            .select(files::id)
+            .load(&mut conn)
+    }
+
+    /// Totales de almacenamiento de todo el servidor
+    pub fn estadisticas_almacenamiento(&self) -> Result<ServerStorage, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        let usuarios: i64 = usuarios::table.count().get_result(&mut conn)?;
+        let deshabilitados: i64 = usuarios::table
+            .filter(usuarios::disabled_at.is_not_null())
+            .count()
+            .get_result(&mut conn)?;
+        let equipos: i64 = teams::table.count().get_result(&mut conn)?;
+        let archivos: i64 = files::table
+            .filter(files::deleted_at.is_null())
+            .count()
+            .get_result(&mut conn)?;
+        let en_papelera: i64 = files::table
+            .filter(files::deleted_at.is_not_null())
+            .count()
+            .get_result(&mut conn)?;
+        let versiones: i64 = file_versions::table.count().get_result(&mut conn)?;
+        let uso_usuarios: Vec<i64> = usuarios::table
+            .select(usuarios::used_bytes)
+            .load(&mut conn)?;
+        let uso_equipos: Vec<i64> = teams::table.select(teams::used_bytes).load(&mut conn)?;
+        let tamanos_blobs: Vec<i64> = blobs::table.select(blobs::size).load(&mut conn)?;
+
+        Ok(ServerStorage {
+            users: usuarios as u64,
+            disabled_users: deshabilitados as u64,
+            teams: equipos as u64,
+            files: archivos as u64,
+            trashed_files: en_papelera as u64,
+            file_versions: versiones as u64,
+            used_bytes: uso_usuarios.iter().chain(&uso_equipos).sum::<i64>().max(0) as u64,
+            blobs: tamanos_blobs.len() as u64,
+            blob_bytes: tamanos_blobs.iter().sum::<i64>().max(0) as u64,
+        })
+    }
+
+    /// Fija la cuota propia del usuario (`None` vuelve a la cuota por defecto)
+    pub fn actualizar_cuota_usuario(
+        &self,
+        user_id: &str,
+        quota_bytes: Option<i64>,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::update(usuarios::table.find(user_id))
+            .set(usuarios::quota_bytes.eq(quota_bytes))
+            .execute(&mut conn)
+    }
+
+    /// Recalcula el uso del usuario (o equipo) a partir de sus archivos (y
+    /// sus versiones anteriores) y lo devuelve
+    pub fn recalcular_uso_usuario(&self, user_id: &str) -> Result<i64, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            let mut tamanos: Vec<i64> = files::table
+                .filter(files::owner_id.eq(user_id))
+                .select(files::size)
+                .load(conn)?;
+            tamanos.extend(
+                file_versions::table
+                    .inner_join(files::table)
+                    .filter(files::owner_id.eq(user_id))
+                    .select(file_versions::size)
+                    .load::<i64>(conn)?,
+            );
+            let usado = tamanos.iter().sum();
+
+            diesel::update(usuarios::table.find(user_id))
+                .set(usuarios::used_bytes.eq(usado))
+                .execute(conn)?;
+            diesel::update(teams::table.find(user_id))
+                .set(teams::used_bytes.eq(usado))
+                .execute(conn)?;
+            Ok(usado)
+        })
+    }
+
+    /// Bytes registrados y bytes reales (según sus archivos y versiones) de
+    /// cada usuario y cada equipo
+    pub fn calcular_uso_usuarios(&self) -> Result<Vec<(String, i64, i64)>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        let mut registrados: Vec<(String, i64)> = usuarios::table
+            .select((usuarios::id, usuarios::used_bytes))
+            .load(&mut conn)?;
+        registrados.extend(
+            teams::table
+                .select((teams::id, teams::used_bytes))
+                .load::<(String, i64)>(&mut conn)?,
+        );
+        let mut tamanos: Vec<(String, i64)> = files::table
+            .select((files::owner_id, files::size))
+            .load(&mut conn)?;
+        tamanos.extend(
+            file_versions::table
+                .inner_join(files::table)
+                .select((files::owner_id, file_versions::size))
+                .load::<(String, i64)>(&mut conn)?,
+        );
+
+        let mut reales: HashMap<String, i64> = HashMap::new();
+        for (owner_id, size) in tamanos {
+            *reales.entry(owner_id).or_default() += size;
+        }
+
+        Ok(registrados
+            .into_iter()
+            .map(|(id, usado)| {
+                let real = reales.get(&id).copied().unwrap_or(0);
+                (id, usado, real)
+            })
+            .collect())
     }
 
     // -------------------
@@ -62,27 +312,1450 @@ impl DbManager {
         diesel::delete(files::table.find(file_id)).execute(&mut conn)
     }
 
+    pub fn buscar_file(&self, file_id: &str) -> Result<File, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        files::table.find(file_id).first(&mut conn)
+    }
+
+    pub fn obtener_todos_los_files(&self) -> Result<Vec<File>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        files::table.load::<File>(&mut conn)
+    }
+
+    /// Registra la fecha del último acceso (descarga) al archivo
+    pub fn marcar_acceso_file(
+        &self,
+        file_id: &str,
+        ahora: i64,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::update(files::table.find(file_id))
+            .set(files::last_accessed_at.eq(Some(ahora)))
+            .execute(&mut conn)
+    }
+
+    /// Inserta el archivo, suma una referencia a su blob y cuenta su tamaño en
+    /// el uso del dueño, en una sola transacción. Devuelve `true` si el blob no
+    /// existía.
+    pub fn insertar_file_con_blob(&self, nuevo: &NuevoFile) -> Result<bool, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            diesel::insert_into(files::table)
+                .values(nuevo)
+                .execute(conn)?;
+            Self::sumar_uso(conn, nuevo.owner_id, nuevo.size)?;
+            Self::retener_blob(conn, nuevo.hash, nuevo.size)
+        })
+    }
+
+    /// Borra el archivo con todas sus versiones, resta sus referencias a los
+    /// blobs y descuenta su tamaño del uso del dueño, en una sola transacción.
+    /// Devuelve los hashes de los blobs que ya no tienen ninguna referencia.
+    pub fn borrar_file_con_blob(
+        &self,
+        file_id: &str,
+    ) -> Result<Vec<String>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            let file: File = files::table.find(file_id).first(conn)?;
+            let versiones: Vec<FileVersion> = file_versions::table
+                .filter(file_versions::file_id.eq(file_id))
+                .load(conn)?;
+            diesel::delete(file_versions::table.filter(file_versions::file_id.eq(file_id)))
+                .execute(conn)?;
+            diesel::delete(file_tags::table.filter(file_tags::file_id.eq(file_id)))
+                .execute(conn)?;
+            diesel::delete(share_links::table.filter(share_links::file_id.eq(file_id)))
+                .execute(conn)?;
+            diesel::delete(grants::table.filter(grants::file_id.eq(file_id))).execute(conn)?;
+            diesel::delete(upload_link_files::table.find(file_id)).execute(conn)?;
+            diesel::delete(files::table.find(file_id)).execute(conn)?;
+
+            let liberado = file.size + versiones.iter().map(|v| v.size).sum::<i64>();
+            Self::sumar_uso(conn, &file.owner_id, -liberado)?;
+
+            let mut huerfanos = Vec::new();
+            for hash in std::iter::once(file.hash).chain(versiones.into_iter().map(|v| v.hash)) {
+                if Self::liberar_blob(conn, &hash)? {
+                    diesel::delete(integrity_issues::table.find(&hash)).execute(conn)?;
+                    huerfanos.push(hash);
+                }
+            }
+            Ok(huerfanos)
+        })
+    }
+
+    /// Cambia el nombre original del archivo
+    pub fn renombrar_file(
+        &self,
+        file_id: &str,
+        filename: &str,
+        ahora: i64,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::update(files::table.find(file_id))
+            .set((files::filename.eq(filename), files::updated_at.eq(ahora)))
+            .execute(&mut conn)
+    }
+
+    /// Mueve el archivo a `folder_id` (`None` = raíz)
+    pub fn mover_file(
+        &self,
+        file_id: &str,
+        folder_id: Option<&str>,
+        ahora: i64,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::update(files::table.find(file_id))
+            .set((files::folder_id.eq(folder_id), files::updated_at.eq(ahora)))
+            .execute(&mut conn)
+    }
+
+    /// Archivos activos del usuario dentro de `folder_id` (`None` = raíz)
+    pub fn obtener_files_de_carpeta(
+        &self,
+        user_id: &str,
+        folder_id: Option<&str>,
+    ) -> Result<Vec<File>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        let query = files::table
+            .filter(files::owner_id.eq(user_id))
+            .filter(files::deleted_at.is_null())
+            .order(files::filename.asc())
+            .into_boxed();
+        let query = match folder_id {
+            Some(id) => query.filter(files::folder_id.eq(id)),
+            None => query.filter(files::folder_id.is_null()),
+        };
+        query.load::<File>(&mut conn)
+    }
+
+    // -------------------
+    // Versiones
+    // -------------------
+    /// Guarda el contenido actual del archivo `nuevo.id` como versión
+    /// anterior y lo reemplaza por el de `nuevo` (hash, mime y tamaño), en
+    /// una sola transacción. Suma una referencia al blob nuevo y su tamaño al
+    /// uso del dueño. Devuelve `true` si el blob no existía.
+    pub fn insertar_version_file(&self, nuevo: &NuevoFile) -> Result<bool, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            let file: File = files::table.find(nuevo.id).first(conn)?;
+            Self::archivar_version_actual(conn, &file)?;
+            diesel::update(files::table.find(nuevo.id))
+                .set((
+                    files::hash.eq(nuevo.hash),
+                    files::mime.eq(nuevo.mime),
+                    files::size.eq(nuevo.size),
+                    files::version.eq(file.version + 1),
+                    files::version_created_at.eq(nuevo.version_created_at),
+                    files::updated_at.eq(nuevo.updated_at),
+                ))
+                .execute(conn)?;
+            Self::sumar_uso(conn, &file.owner_id, nuevo.size)?;
+            Self::retener_blob(conn, nuevo.hash, nuevo.size)
+        })
+    }
+
+    /// Deshace `insertar_version_file`: la última versión anterior vuelve a
+    /// ser la actual. Devuelve el hash del blob descartado si ya no queda
+    /// ninguna referencia.
+    pub fn deshacer_version_file(
+        &self,
+        file_id: &str,
+    ) -> Result<Option<String>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            let file: File = files::table.find(file_id).first(conn)?;
+            let anterior: FileVersion = file_versions::table
+                .filter(file_versions::file_id.eq(file_id))
+                .order(file_versions::version.desc())
+                .first(conn)?;
+
+            diesel::update(files::table.find(file_id))
+                .set((
+                    files::hash.eq(&anterior.hash),
+                    files::mime.eq(&anterior.mime),
+                    files::size.eq(anterior.size),
+                    files::version.eq(anterior.version),
+                    files::version_created_at.eq(anterior.created_at),
+                ))
+                .execute(conn)?;
+            diesel::delete(file_versions::table.find((file_id, anterior.version))).execute(conn)?;
+            Self::sumar_uso(conn, &file.owner_id, -file.size)?;
+
+            if Self::liberar_blob(conn, &file.hash)? {
+                diesel::delete(integrity_issues::table.find(&file.hash)).execute(conn)?;
+                Ok(Some(file.hash))
+            } else {
+                Ok(None)
+            }
+        })
+    }
+
+    /// Convierte la versión `version` en la actual. El contenido actual se
+    /// guarda como versión anterior y la promovida se conserva en el
+    /// historial, así que se suma una referencia a su blob y su tamaño al uso.
+    pub fn promover_version_file(
+        &self,
+        file_id: &str,
+        version: i32,
+        ahora: i64,
+    ) -> Result<(), diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            let file: File = files::table.find(file_id).first(conn)?;
+            let promovida: FileVersion =
+                file_versions::table.find((file_id, version)).first(conn)?;
+
+            Self::archivar_version_actual(conn, &file)?;
+            diesel::update(files::table.find(file_id))
+                .set((
+                    files::hash.eq(&promovida.hash),
+                    files::mime.eq(&promovida.mime),
+                    files::size.eq(promovida.size),
+                    files::version.eq(file.version + 1),
+                    files::version_created_at.eq(ahora),
+                    files::updated_at.eq(ahora),
+                ))
+                .execute(conn)?;
+            Self::sumar_uso(conn, &file.owner_id, promovida.size)?;
+            Self::retener_blob(conn, &promovida.hash, promovida.size)?;
+            Ok(())
+        })
+    }
+
+    /// Inserta el contenido actual de `file` en el historial de versiones
+    fn archivar_version_actual(
+        conn: &mut SqliteConnection,
+        file: &File,
+    ) -> Result<usize, diesel::result::Error> {
+        diesel::insert_into(file_versions::table)
+            .values(&FileVersion {
+                file_id: file.id.clone(),
+                version: file.version,
+                hash: file.hash.clone(),
+                mime: file.mime.clone(),
+                size: file.size,
+                created_at: file.version_created_at,
+            })
+            .execute(conn)
+    }
+
+    pub fn buscar_version_file(
+        &self,
+        file_id: &str,
+        version: i32,
+    ) -> Result<FileVersion, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        file_versions::table
+            .find((file_id, version))
+            .first(&mut conn)
+    }
+
+    /// Versiones anteriores del archivo, de la más reciente a la más antigua
+    pub fn obtener_versiones_file(
+        &self,
+        file_id: &str,
+    ) -> Result<Vec<FileVersion>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        file_versions::table
+            .filter(file_versions::file_id.eq(file_id))
+            .order(file_versions::version.desc())
+            .load::<FileVersion>(&mut conn)
+    }
+
+    /// Borra una versión anterior, resta su referencia al blob y descuenta su
+    /// tamaño del uso del dueño. Devuelve el hash del blob si ya no queda
+    /// ninguna referencia.
+    pub fn borrar_version_file(
+        &self,
+        file_id: &str,
+        version: i32,
+    ) -> Result<Option<String>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            let file: File = files::table.find(file_id).first(conn)?;
+            let borrada: FileVersion = file_versions::table.find((file_id, version)).first(conn)?;
+            diesel::delete(file_versions::table.find((file_id, version))).execute(conn)?;
+            Self::sumar_uso(conn, &file.owner_id, -borrada.size)?;
+
+            if Self::liberar_blob(conn, &borrada.hash)? {
+                diesel::delete(integrity_issues::table.find(&borrada.hash)).execute(conn)?;
+                Ok(Some(borrada.hash))
+            } else {
+                Ok(None)
+            }
+        })
+    }
+
+    // -------------------
+    // Operaciones por lotes
+    // -------------------
+    /// Aplica los cambios en una única transacción. Cada cambio corre en su
+    /// propio savepoint: si falla se deshace solo ese y el resto continúa.
+    /// Un archivo que ya no está activo da `NotFound`; los permisos se
+    /// validan antes, en `apply_batch`.
+    ///
+    /// # Retorna
+    /// El resultado de cada cambio, en el mismo orden
+    pub fn aplicar_lote(
+        &self,
+        cambios: &[CambioLote],
+        ahora: i64,
+    ) -> Result<Vec<Result<(), diesel::result::Error>>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            Ok(cambios
+                .iter()
+                .map(|cambio| conn.transaction(|conn| Self::aplicar_cambio(conn, cambio, ahora)))
+                .collect())
+        })
+    }
+
+    fn aplicar_cambio(
+        conn: &mut SqliteConnection,
+        cambio: &CambioLote,
+        ahora: i64,
+    ) -> Result<(), diesel::result::Error> {
+        let activo = |file_id: &str| {
+            files::table
+                .find(file_id.to_string())
+                .filter(files::deleted_at.is_null())
+        };
+
+        let filas = match cambio {
+            CambioLote::Papelera { file_id } => diesel::update(activo(file_id))
+                .set(files::deleted_at.eq(Some(ahora)))
+                .execute(conn)?,
+            CambioLote::Mover { file_id, folder_id } => diesel::update(activo(file_id))
+                .set((files::folder_id.eq(folder_id), files::updated_at.eq(ahora)))
+                .execute(conn)?,
+            CambioLote::Renombrar { file_id, filename } => diesel::update(activo(file_id))
+                .set((files::filename.eq(filename), files::updated_at.eq(ahora)))
+                .execute(conn)?,
+            CambioLote::Etiquetar {
+                file_id,
+                agregar,
+                quitar,
+            } => {
+                let filas = diesel::update(activo(file_id))
+                    .set(files::updated_at.eq(ahora))
+                    .execute(conn)?;
+                if filas > 0 {
+                    diesel::delete(
+                        file_tags::table
+                            .filter(file_tags::file_id.eq(file_id))
+                            .filter(file_tags::tag.eq_any(quitar)),
+                    )
+                    .execute(conn)?;
+                    for tag in agregar {
+                        diesel::insert_or_ignore_into(file_tags::table)
+                            .values(&NuevoFileTag { file_id, tag })
+                            .execute(conn)?;
+                    }
+                }
+                filas
+            }
+        };
+
+        if filas == 0 {
+            return Err(diesel::result::Error::NotFound);
+        }
+        Ok(())
+    }
+
+    // -------------------
+    // Papelera
+    // -------------------
+    /// Envía el archivo a la papelera. El blob y el uso del dueño no cambian
+    /// hasta que se purga.
+    pub fn mover_file_a_papelera(
+        &self,
+        file_id: &str,
+        ahora: i64,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::update(
+            files::table
+                .find(file_id)
+                .filter(files::deleted_at.is_null()),
+        )
+        .set(files::deleted_at.eq(Some(ahora)))
+        .execute(&mut conn)
+    }
+
+    /// Saca el archivo de la papelera dejándolo en `folder_id` (`None` = raíz)
+    pub fn restaurar_file(
+        &self,
+        file_id: &str,
+        folder_id: Option<&str>,
+        ahora: i64,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::update(files::table.find(file_id))
+            .set((
+                files::deleted_at.eq(None::<i64>),
+                files::folder_id.eq(folder_id),
+                files::updated_at.eq(ahora),
+            ))
+            .execute(&mut conn)
+    }
+
+    /// Archivos en la papelera del usuario, del más reciente al más antiguo
+    pub fn obtener_papelera_de_usuario(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<File>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        files::table
+            .filter(files::owner_id.eq(user_id))
+            .filter(files::deleted_at.is_not_null())
+            .order(files::deleted_at.desc())
+            .load::<File>(&mut conn)
+    }
+
+    /// Archivos enviados a la papelera antes de `limite`
+    pub fn obtener_papelera_vencida(
+        &self,
+        limite: i64,
+    ) -> Result<Vec<File>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        files::table
+            .filter(files::deleted_at.lt(limite))
+            .load::<File>(&mut conn)
+    }
+
+    // -------------------
+    // Carpetas
+    // -------------------
+    pub fn insertar_folder(&self, nuevo: &NuevoFolder) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::insert_into(folders::table)
+            .values(nuevo)
+            .execute(&mut conn)
+    }
+
+    pub fn buscar_folder(&self, folder_id: &str) -> Result<Folder, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        folders::table.find(folder_id).first(&mut conn)
+    }
+
+    /// Subcarpetas del usuario dentro de `parent_id` (`None` = raíz)
+    pub fn obtener_folders_hijos(
+        &self,
+        user_id: &str,
+        parent_id: Option<&str>,
+    ) -> Result<Vec<Folder>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        let query = folders::table
+            .filter(folders::owner_id.eq(user_id))
+            .order(folders::name.asc())
+            .into_boxed();
+        let query = match parent_id {
+            Some(id) => query.filter(folders::parent_id.eq(id)),
+            None => query.filter(folders::parent_id.is_null()),
+        };
+        query.load::<Folder>(&mut conn)
+    }
+
+    pub fn renombrar_folder(
+        &self,
+        folder_id: &str,
+        name: &str,
+        ahora: i64,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::update(folders::table.find(folder_id))
+            .set((folders::name.eq(name), folders::updated_at.eq(ahora)))
+            .execute(&mut conn)
+    }
+
+    /// Mueve la carpeta dentro de `parent_id` (`None` = raíz)
+    pub fn mover_folder(
+        &self,
+        folder_id: &str,
+        parent_id: Option<&str>,
+        ahora: i64,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::update(folders::table.find(folder_id))
+            .set((
+                folders::parent_id.eq(parent_id),
+                folders::updated_at.eq(ahora),
+            ))
+            .execute(&mut conn)
+    }
+
+    /// Borra la carpeta solo si ya no contiene archivos activos ni
+    /// subcarpetas. Los archivos en la papelera conservan su `folder_id`.
+    pub fn borrar_folder_vacio(&self, folder_id: &str) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            let archivos: i64 = files::table
+                .filter(files::folder_id.eq(folder_id))
+                .filter(files::deleted_at.is_null())
+                .count()
+                .get_result(conn)?;
+            let subcarpetas: i64 = folders::table
+                .filter(folders::parent_id.eq(folder_id))
+                .count()
+                .get_result(conn)?;
+            if archivos > 0 || subcarpetas > 0 {
+                return Ok(0);
+            }
+            diesel::delete(grants::table.filter(grants::folder_id.eq(folder_id))).execute(conn)?;
+            let enlaces = upload_links::table
+                .filter(upload_links::folder_id.eq(folder_id))
+                .select(upload_links::token);
+            diesel::delete(
+                upload_link_files::table.filter(upload_link_files::token.eq_any(enlaces)),
+            )
+            .execute(conn)?;
+            diesel::delete(upload_links::table.filter(upload_links::folder_id.eq(folder_id)))
+                .execute(conn)?;
+            diesel::delete(folders::table.find(folder_id)).execute(conn)
+        })
+    }
+
+    // -------------------
+    // Blobs (almacenamiento deduplicado por hash)
+    // -------------------
+    pub fn buscar_blob(&self, hash: &str) -> Result<Blob, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        blobs::table.find(hash).first(&mut conn)
+    }
+
+    /// Fija el tamaño del blob y el de los archivos que lo referencian sin
+    /// tamaño conocido (registrados antes de guardar los tamaños)
+    pub fn actualizar_tamano_blob(
+        &self,
+        hash: &str,
+        size: i64,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            diesel::update(
+                files::table
+                    .filter(files::hash.eq(hash))
+                    .filter(files::size.eq(0)),
+            )
+            .set(files::size.eq(size))
+            .execute(conn)?;
+            diesel::update(blobs::table.find(hash))
+                .set(blobs::size.eq(size))
+                .execute(conn)
+        })
+    }
+
+    pub fn marcar_blob_cifrado(&self, hash: &str) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::update(blobs::table.find(hash))
+            .set(blobs::encrypted.eq(true))
+            .execute(&mut conn)
+    }
+
+    /// Registra la compresión con la que se escribió el contenido del blob
+    pub fn actualizar_codec_blob(
+        &self,
+        hash: &str,
+        codec: &str,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::update(blobs::table.find(hash))
+            .set(blobs::codec.eq(codec))
+            .execute(&mut conn)
+    }
+
+    /// Registra el SHA-256 del contenido del blob si todavía no lo tenía
+    pub fn completar_sha256_blob(
+        &self,
+        hash: &str,
+        sha256: &str,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::update(blobs::table.find(hash).filter(blobs::sha256.is_null()))
+            .set(blobs::sha256.eq(sha256))
+            .execute(&mut conn)
+    }
+
+    pub fn obtener_blobs_sin_cifrar(&self) -> Result<Vec<Blob>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        blobs::table
+            .filter(blobs::encrypted.eq(false))
+            .load::<Blob>(&mut conn)
+    }
+
+    /// Suma `delta` bytes al uso del dueño de un archivo, sea un usuario o un
+    /// equipo
+    fn sumar_uso(
+        conn: &mut SqliteConnection,
+        owner_id: &str,
+        delta: i64,
+    ) -> Result<(), diesel::result::Error> {
+        let actualizados = diesel::update(usuarios::table.find(owner_id))
+            .set(usuarios::used_bytes.eq(usuarios::used_bytes + delta))
+            .execute(conn)?;
+        if actualizados == 0 {
+            diesel::update(teams::table.find(owner_id))
+                .set(teams::used_bytes.eq(teams::used_bytes + delta))
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Bytes usados por el dueño de un archivo, sea un usuario o un equipo
+    pub fn buscar_uso_dueno(&self, owner_id: &str) -> Result<i64, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        let usado = usuarios::table
+            .find(owner_id)
+            .select(usuarios::used_bytes)
+            .first(&mut conn)
+            .optional()?;
+        match usado {
+            Some(usado) => Ok(usado),
+            None => teams::table
+                .find(owner_id)
+                .select(teams::used_bytes)
+                .first(&mut conn),
+        }
+    }
+
+    /// Suma una referencia al blob `hash`, creándolo (como cifrado) si no existe.
+    /// Devuelve `true` si el blob es nuevo.
+    fn retener_blob(
+        conn: &mut SqliteConnection,
+        hash: &str,
+        size: i64,
+    ) -> Result<bool, diesel::result::Error> {
+        let actualizados = diesel::update(blobs::table.find(hash))
+            .set(blobs::refcount.eq(blobs::refcount + 1))
+            .execute(conn)?;
+        if actualizados > 0 {
+            return Ok(false);
+        }
+
+        diesel::insert_into(blobs::table)
+            .values(&NuevoBlob {
+                hash,
+                size,
+                refcount: 1,
+                encrypted: true,
+            })
+            .execute(conn)?;
+        Ok(true)
+    }
+
+    /// Resta una referencia al blob `hash`. Si no quedan referencias borra
+    /// el registro y devuelve `true`.
+    fn liberar_blob(
+        conn: &mut SqliteConnection,
+        hash: &str,
+    ) -> Result<bool, diesel::result::Error> {
+        diesel::update(blobs::table.find(hash))
+            .set(blobs::refcount.eq(blobs::refcount - 1))
+            .execute(conn)?;
+        let borrados = diesel::delete(
+            blobs::table
+                .filter(blobs::hash.eq(hash))
+                .filter(blobs::refcount.le(0)),
+        )
+        .execute(conn)?;
+        Ok(borrados > 0)
+    }
+
+    pub fn obtener_todos_los_blobs(&self) -> Result<Vec<Blob>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        blobs::table.load::<Blob>(&mut conn)
+    }
+
+    pub fn obtener_files_por_hash(&self, hash: &str) -> Result<Vec<File>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        files::table
+            .filter(files::hash.eq(hash))
+            .load::<File>(&mut conn)
+    }
+
+    /// Cantidad de archivos y versiones anteriores que referencian cada hash
+    pub fn contar_referencias_blobs(&self) -> Result<Vec<(String, i64)>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        let mut referencias: HashMap<String, i64> = files::table
+            .group_by(files::hash)
+            .select((files::hash, diesel::dsl::count_star()))
+            .load::<(String, i64)>(&mut conn)?
+            .into_iter()
+            .collect();
+        let versiones: Vec<(String, i64)> = file_versions::table
+            .group_by(file_versions::hash)
+            .select((file_versions::hash, diesel::dsl::count_star()))
+            .load(&mut conn)?;
+        for (hash, cantidad) in versiones {
+            *referencias.entry(hash).or_default() += cantidad;
+        }
+        Ok(referencias.into_iter().collect())
+    }
+
+    /// Recalcula el refcount de `hash` a partir de los archivos y versiones
+    /// que lo referencian. Si no queda ninguno borra el registro y devuelve `true`.
+    pub fn recalcular_refcount_blob(&self, hash: &str) -> Result<bool, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            let referencias: i64 = files::table
+                .filter(files::hash.eq(hash))
+                .count()
+                .get_result::<i64>(conn)?
+                + file_versions::table
+                    .filter(file_versions::hash.eq(hash))
+                    .count()
+                    .get_result::<i64>(conn)?;
+
+            if referencias == 0 {
+                diesel::delete(blobs::table.find(hash)).execute(conn)?;
+                diesel::delete(integrity_issues::table.find(hash)).execute(conn)?;
+                return Ok(true);
+            }
+
+            diesel::update(blobs::table.find(hash))
+                .set(blobs::refcount.eq(referencias as i32))
+                .execute(conn)?;
+            Ok(false)
+        })
+    }
+
+    // -------------------
+    // Trozos (blobs guardados por contenido)
+    // -------------------
+    pub fn buscar_chunk(&self, hash: &str) -> Result<Chunk, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        chunks::table.find(hash).first(&mut conn)
+    }
+
+    pub fn obtener_todos_los_chunks(&self) -> Result<Vec<Chunk>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        chunks::table.load::<Chunk>(&mut conn)
+    }
+
+    /// Trozos de un blob en orden, con el registro de cada trozo
+    pub fn obtener_manifiesto_blob(
+        &self,
+        blob_hash: &str,
+    ) -> Result<Vec<(BlobChunk, Chunk)>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        blob_chunks::table
+            .inner_join(chunks::table)
+            .filter(blob_chunks::blob_hash.eq(blob_hash))
+            .order(blob_chunks::seq.asc())
+            .load::<(BlobChunk, Chunk)>(&mut conn)
+    }
+
+    /// Reemplaza el manifiesto del blob `blob_hash` por `manifiesto` y marca
+    /// el blob como guardado por trozos.
+    ///
+    /// `escritos` son los trozos que se acaban de escribir en el storage: se
+    /// crean o se actualiza su codec. El resto de los trozos del manifiesto
+    /// tienen que estar registrados.
+    ///
+    /// # Retorna
+    /// Los trozos del manifiesto anterior que se quedaron sin referencias
+    pub fn registrar_chunks_blob(
+        &self,
+        blob_hash: &str,
+        escritos: &[NuevoChunk],
+        manifiesto: &[BlobChunk],
+    ) -> Result<Vec<String>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            let anteriores = Self::soltar_manifiesto(conn, blob_hash)?;
+
+            for trozo in escritos {
+                let actualizados = diesel::update(chunks::table.find(trozo.hash))
+                    .set((chunks::size.eq(trozo.size), chunks::codec.eq(trozo.codec)))
+                    .execute(conn)?;
+                if actualizados == 0 {
+                    diesel::insert_into(chunks::table)
+                        .values(trozo)
+                        .execute(conn)?;
+                }
+            }
+
+            for parte in manifiesto {
+                let retenidos = diesel::update(chunks::table.find(&parte.chunk_hash))
+                    .set(chunks::refcount.eq(chunks::refcount + 1))
+                    .execute(conn)?;
+                if retenidos == 0 {
+                    return Err(diesel::result::Error::NotFound);
+                }
+            }
+            diesel::insert_into(blob_chunks::table)
+                .values(manifiesto)
+                .execute(conn)?;
+
+            diesel::update(blobs::table.find(blob_hash))
+                .set(blobs::codec.eq(CODEC_CHUNKED))
+                .execute(conn)?;
+
+            Self::borrar_chunks_sin_referencias(conn, &anteriores)
+        })
+    }
+
+    /// Borra el manifiesto de un blob y resta las referencias de sus trozos.
+    ///
+    /// # Retorna
+    /// Los trozos que se quedaron sin referencias (ya sin registro)
+    pub fn liberar_chunks_de_blob(
+        &self,
+        blob_hash: &str,
+    ) -> Result<Vec<String>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            let anteriores = Self::soltar_manifiesto(conn, blob_hash)?;
+            Self::borrar_chunks_sin_referencias(conn, &anteriores)
+        })
+    }
+
+    /// Borra las filas del manifiesto de `blob_hash` y resta una referencia a
+    /// cada trozo por cada aparición. Devuelve los trozos afectados.
+    fn soltar_manifiesto(
+        conn: &mut SqliteConnection,
+        blob_hash: &str,
+    ) -> Result<Vec<String>, diesel::result::Error> {
+        let anteriores: Vec<String> = blob_chunks::table
+            .filter(blob_chunks::blob_hash.eq(blob_hash))
+            .select(blob_chunks::chunk_hash)
+            .load(conn)?;
+        diesel::delete(blob_chunks::table.filter(blob_chunks::blob_hash.eq(blob_hash)))
+            .execute(conn)?;
+        for hash in &anteriores {
+            diesel::update(chunks::table.find(hash))
+                .set(chunks::refcount.eq(chunks::refcount - 1))
+                .execute(conn)?;
+        }
+        Ok(anteriores)
+    }
+
+    /// Borra los registros de los trozos de `hashes` que ya no tienen
+    /// referencias y los devuelve
+    fn borrar_chunks_sin_referencias(
+        conn: &mut SqliteConnection,
+        hashes: &[String],
+    ) -> Result<Vec<String>, diesel::result::Error> {
+        let huerfanos: Vec<String> = chunks::table
+            .filter(chunks::hash.eq_any(hashes))
+            .filter(chunks::refcount.le(0))
+            .select(chunks::hash)
+            .load(conn)?;
+        diesel::delete(chunks::table.filter(chunks::hash.eq_any(&huerfanos))).execute(conn)?;
+        Ok(huerfanos)
+    }
+
+    /// Blobs con manifiesto de trozos pero sin registro en `blobs`
+    pub fn obtener_manifiestos_huerfanos(&self) -> Result<Vec<String>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        blob_chunks::table
+            .filter(diesel::dsl::not(
+                blob_chunks::blob_hash.eq_any(blobs::table.select(blobs::hash)),
+            ))
+            .select(blob_chunks::blob_hash)
+            .distinct()
+            .load(&mut conn)
+    }
+
+    /// Cantidad de posiciones de manifiestos que referencian cada trozo
+    pub fn contar_referencias_chunks(&self) -> Result<Vec<(String, i64)>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        blob_chunks::table
+            .group_by(blob_chunks::chunk_hash)
+            .select((blob_chunks::chunk_hash, diesel::dsl::count_star()))
+            .load(&mut conn)
+    }
+
+    /// Recalcula el refcount de un trozo a partir de los manifiestos. Si no
+    /// queda ninguno borra el registro y devuelve `true`.
+    pub fn recalcular_refcount_chunk(&self, hash: &str) -> Result<bool, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            let referencias: i64 = blob_chunks::table
+                .filter(blob_chunks::chunk_hash.eq(hash))
+                .count()
+                .get_result(conn)?;
+
+            if referencias == 0 {
+                diesel::delete(chunks::table.find(hash)).execute(conn)?;
+                return Ok(true);
+            }
+
+            diesel::update(chunks::table.find(hash))
+                .set(chunks::refcount.eq(referencias as i32))
+                .execute(conn)?;
+            Ok(false)
+        })
+    }
+
+    // -------------------
+    // Problemas de integridad
+    // -------------------
+    /// Registra (o actualiza) el problema de integridad de un blob
+    pub fn registrar_problema_integridad(
+        &self,
+        problema: &IntegrityIssue,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::replace_into(integrity_issues::table)
+            .values(problema)
+            .execute(&mut conn)
+    }
+
+    pub fn buscar_problema_integridad(
+        &self,
+        hash: &str,
+    ) -> Result<Option<IntegrityIssue>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        integrity_issues::table
+            .find(hash)
+            .first(&mut conn)
+            .optional()
+    }
+
+    pub fn borrar_problema_integridad(&self, hash: &str) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::delete(integrity_issues::table.find(hash)).execute(&mut conn)
+    }
+
+    pub fn obtener_problemas_integridad(
+        &self,
+    ) -> Result<Vec<IntegrityIssue>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        integrity_issues::table
+            .order(integrity_issues::detected_at.desc())
+            .load::<IntegrityIssue>(&mut conn)
+    }
+
+    // -------------------
+    // Subidas reanudables (tus)
+    // -------------------
+    pub fn insertar_tus_upload(
+        &self,
+        nuevo: &NuevoTusUpload,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::insert_into(tus_uploads::table)
+            .values(nuevo)
+            .execute(&mut conn)
+    }
+
+    pub fn buscar_tus_upload(&self, upload_id: &str) -> Result<TusUpload, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        tus_uploads::table.find(upload_id).first(&mut conn)
+    }
+
+    pub fn actualizar_offset_tus_upload(
+        &self,
+        upload_id: &str,
+        offset: i64,
+        ahora: i64,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::update(tus_uploads::table.find(upload_id))
+            .set((
+                tus_uploads::upload_offset.eq(offset),
+                tus_uploads::updated_at.eq(ahora),
+            ))
+            .execute(&mut conn)
+    }
+
+    pub fn completar_tus_upload(
+        &self,
+        upload_id: &str,
+        file_id: &str,
+        ahora: i64,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::update(tus_uploads::table.find(upload_id))
+            .set((
+                tus_uploads::file_id.eq(Some(file_id)),
+                tus_uploads::updated_at.eq(ahora),
+            ))
+            .execute(&mut conn)
+    }
+
+    pub fn borrar_tus_upload(&self, upload_id: &str) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::delete(tus_uploads::table.find(upload_id)).execute(&mut conn)
+    }
+
+    /// Subidas sin actividad desde antes de `limite` (timestamp unix)
+    pub fn obtener_tus_uploads_inactivos(
+        &self,
+        limite: i64,
+    ) -> Result<Vec<TusUpload>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        tus_uploads::table
+            .filter(tus_uploads::updated_at.lt(limite))
+            .load::<TusUpload>(&mut conn)
+    }
+
+    // -------------------
+    // Equipos
+    // -------------------
+    /// Crea el equipo con su primer miembro (el dueño) en una sola transacción
+    pub fn insertar_team(
+        &self,
+        nuevo: &NuevoTeam,
+        owner: &TeamMember,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            diesel::insert_into(teams::table)
+                .values(nuevo)
+                .execute(conn)?;
+            diesel::insert_into(team_members::table)
+                .values(owner)
+                .execute(conn)
+        })
+    }
+
+    pub fn buscar_team(&self, team_id: &str) -> Result<Team, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        teams::table.find(team_id).first(&mut conn)
+    }
+
+    /// Equipos del usuario con su pertenencia, ordenados por nombre
+    pub fn obtener_teams_de_usuario(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<(Team, TeamMember)>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        teams::table
+            .inner_join(team_members::table)
+            .filter(team_members::user_id.eq(user_id))
+            .order(teams::name.asc())
+            .select((teams::all_columns, team_members::all_columns))
+            .load::<(Team, TeamMember)>(&mut conn)
+    }
+
+    pub fn buscar_miembro_team(
+        &self,
+        team_id: &str,
+        user_id: &str,
+    ) -> Result<TeamMember, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        team_members::table
+            .find((team_id, user_id))
+            .first(&mut conn)
+    }
+
+    pub fn obtener_miembros_team(
+        &self,
+        team_id: &str,
+    ) -> Result<Vec<TeamMember>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        team_members::table
+            .filter(team_members::team_id.eq(team_id))
+            .order(team_members::created_at.asc())
+            .load::<TeamMember>(&mut conn)
+    }
+
+    /// Agrega al usuario al equipo o, si ya era miembro, cambia su rol
+    pub fn guardar_miembro_team(
+        &self,
+        miembro: &TeamMember,
+    ) -> Result<TeamMember, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            let clave = (miembro.team_id.as_str(), miembro.user_id.as_str());
+            let actualizados = diesel::update(team_members::table.find(clave))
+                .set(team_members::role.eq(&miembro.role))
+                .execute(conn)?;
+            if actualizados == 0 {
+                diesel::insert_into(team_members::table)
+                    .values(miembro)
+                    .execute(conn)?;
+            }
+            team_members::table.find(clave).first(conn)
+        })
+    }
+
+    pub fn borrar_miembro_team(
+        &self,
+        team_id: &str,
+        user_id: &str,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::delete(team_members::table.find((team_id, user_id))).execute(&mut conn)
+    }
+
+    /// Borra el equipo y sus miembros solo si su espacio no tiene archivos
+    /// (ni en la papelera) ni carpetas. Devuelve 0 si no estaba vacío.
+    pub fn borrar_team_vacio(&self, team_id: &str) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            let archivos: i64 = files::table
+                .filter(files::owner_id.eq(team_id))
+                .count()
+                .get_result(conn)?;
+            let carpetas: i64 = folders::table
+                .filter(folders::owner_id.eq(team_id))
+                .count()
+                .get_result(conn)?;
+            if archivos > 0 || carpetas > 0 {
+                return Ok(0);
+            }
+            diesel::delete(team_members::table.filter(team_members::team_id.eq(team_id)))
+                .execute(conn)?;
+            diesel::delete(teams::table.find(team_id)).execute(conn)
+        })
+    }
+
+    pub fn actualizar_cuota_team(
+        &self,
+        team_id: &str,
+        quota_bytes: Option<i64>,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::update(teams::table.find(team_id))
+            .set(teams::quota_bytes.eq(quota_bytes))
+            .execute(&mut conn)
+    }
+
+    // -------------------
+    // Permisos compartidos
+    // -------------------
+    /// Da un permiso al usuario; si ya tenía uno sobre el mismo archivo o
+    /// carpeta solo se cambia el rol. Devuelve el permiso resultante.
+    pub fn otorgar_permiso(&self, nuevo: &NuevoGrant) -> Result<Grant, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            let mut query = grants::table
+                .filter(grants::grantee_id.eq(nuevo.grantee_id))
+                .into_boxed();
+            query = match (nuevo.file_id, nuevo.folder_id) {
+                (Some(file_id), _) => query.filter(grants::file_id.eq(file_id)),
+                (None, Some(folder_id)) => query.filter(grants::folder_id.eq(folder_id)),
+                (None, None) => return Err(diesel::result::Error::NotFound),
+            };
+            let existente: Option<Grant> = query.first(conn).optional()?;
+
+            let id = match existente {
+                Some(grant) => {
+                    diesel::update(grants::table.find(&grant.id))
+                        .set(grants::role.eq(nuevo.role))
+                        .execute(conn)?;
+                    grant.id
+                }
+                None => {
+                    diesel::insert_into(grants::table)
+                        .values(nuevo)
+                        .execute(conn)?;
+                    nuevo.id.to_string()
+                }
+            };
+            grants::table.find(id).first(conn)
+        })
+    }
+
+    pub fn buscar_permiso(&self, grant_id: &str) -> Result<Grant, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        grants::table.find(grant_id).first(&mut conn)
+    }
+
+    pub fn borrar_permiso(&self, grant_id: &str) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::delete(grants::table.find(grant_id)).execute(&mut conn)
+    }
+
+    /// Permisos que dio un usuario, opcionalmente solo los de un archivo o
+    /// una carpeta
+    pub fn obtener_permisos_otorgados(
+        &self,
+        owner_id: &str,
+        file_id: Option<&str>,
+        folder_id: Option<&str>,
+    ) -> Result<Vec<Grant>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        let mut query = grants::table
+            .filter(grants::owner_id.eq(owner_id))
+            .into_boxed();
+        if let Some(file_id) = file_id {
+            query = query.filter(grants::file_id.eq(file_id));
+        }
+        if let Some(folder_id) = folder_id {
+            query = query.filter(grants::folder_id.eq(folder_id));
+        }
+        query
+            .order(grants::created_at.desc())
+            .load::<Grant>(&mut conn)
+    }
+
+    /// Permisos recibidos por un usuario, del más reciente al más antiguo
+    pub fn obtener_permisos_recibidos(
+        &self,
+        grantee_id: &str,
+    ) -> Result<Vec<Grant>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        grants::table
+            .filter(grants::grantee_id.eq(grantee_id))
+            .order(grants::created_at.desc())
+            .load::<Grant>(&mut conn)
+    }
+
+    /// Permisos del usuario sobre el archivo `file_id` o sobre cualquiera de
+    /// las carpetas `folder_ids`
+    pub fn buscar_permisos_aplicables(
+        &self,
+        grantee_id: &str,
+        file_id: Option<&str>,
+        folder_ids: &[String],
+    ) -> Result<Vec<Grant>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        grants::table
+            .filter(grants::grantee_id.eq(grantee_id))
+            .filter(
+                grants::file_id
+                    .eq(file_id)
+                    .or(grants::folder_id.eq_any(folder_ids)),
+            )
+            .load::<Grant>(&mut conn)
+    }
+
+    // -------------------
+    // Enlaces públicos
+    // -------------------
+    pub fn insertar_share_link(
+        &self,
+        nuevo: &NuevoShareLink,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::insert_into(share_links::table)
+            .values(nuevo)
+            .execute(&mut conn)
+    }
+
+    pub fn buscar_share_link(&self, token: &str) -> Result<ShareLink, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        share_links::table.find(token).first(&mut conn)
+    }
+
+    /// Enlaces de un usuario, opcionalmente solo los de un archivo, del más
+    /// reciente al más antiguo
+    pub fn obtener_share_links_de_usuario(
+        &self,
+        owner_id: &str,
+        file_id: Option<&str>,
+    ) -> Result<Vec<ShareLink>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        let mut query = share_links::table
+            .filter(share_links::owner_id.eq(owner_id))
+            .into_boxed();
+        if let Some(file_id) = file_id {
+            query = query.filter(share_links::file_id.eq(file_id));
+        }
+        query
+            .order(share_links::created_at.desc())
+            .load::<ShareLink>(&mut conn)
+    }
+
+    /// Borra un enlace si pertenece a `owner_id`
+    pub fn borrar_share_link(
+        &self,
+        token: &str,
+        owner_id: &str,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::delete(
+            share_links::table
+                .filter(share_links::token.eq(token))
+                .filter(share_links::owner_id.eq(owner_id)),
+        )
+        .execute(&mut conn)
+    }
+
+    /// Cuenta una descarga del enlace si todavía está vigente en `ahora` y
+    /// no alcanzó su límite. Devuelve `false` si no se pudo contar.
+    pub fn consumir_descarga_share_link(
+        &self,
+        token: &str,
+        ahora: i64,
+    ) -> Result<bool, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        let actualizados = diesel::update(
+            share_links::table
+                .filter(share_links::token.eq(token))
+                .filter(
+                    share_links::expires_at
+                        .is_null()
+                        .or(share_links::expires_at.gt(ahora)),
+                )
+                .filter(
+                    share_links::max_downloads
+                        .is_null()
+                        .or(share_links::download_count
+                            .nullable()
+                            .lt(share_links::max_downloads)),
+                ),
+        )
+        .set(share_links::download_count.eq(share_links::download_count + 1))
+        .execute(&mut conn)?;
+        Ok(actualizados > 0)
+    }
+
+    // -------------------
+    // Enlaces de subida
+    // -------------------
+    pub fn insertar_upload_link(
+        &self,
+        nuevo: &NuevoUploadLink,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::insert_into(upload_links::table)
+            .values(nuevo)
+            .execute(&mut conn)
+    }
+
+    pub fn buscar_upload_link(&self, token: &str) -> Result<UploadLink, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        upload_links::table.find(token).first(&mut conn)
+    }
+
+    /// Enlaces de subida de un usuario, opcionalmente solo los de una
+    /// carpeta, del más reciente al más antiguo
+    pub fn obtener_upload_links_de_usuario(
+        &self,
+        owner_id: &str,
+        folder_id: Option<&str>,
+    ) -> Result<Vec<UploadLink>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        let mut query = upload_links::table
+            .filter(upload_links::owner_id.eq(owner_id))
+            .into_boxed();
+        if let Some(folder_id) = folder_id {
+            query = query.filter(upload_links::folder_id.eq(folder_id));
+        }
+        query
+            .order(upload_links::created_at.desc())
+            .load::<UploadLink>(&mut conn)
+    }
+
+    /// Borra un enlace de subida de `owner_id` junto con el registro de lo
+    /// que se subió por él; los archivos recibidos se conservan
+    pub fn borrar_upload_link(
+        &self,
+        token: &str,
+        owner_id: &str,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        conn.transaction(|conn| {
+            let existe: i64 = upload_links::table
+                .filter(upload_links::token.eq(token))
+                .filter(upload_links::owner_id.eq(owner_id))
+                .count()
+                .get_result(conn)?;
+            if existe == 0 {
+                return Ok(0);
+            }
+            diesel::delete(upload_link_files::table.filter(upload_link_files::token.eq(token)))
+                .execute(conn)?;
+            diesel::delete(upload_links::table.find(token)).execute(conn)
+        })
+    }
+
+    /// Reserva una subida del enlace si todavía está vigente en `ahora` y
+    /// no alcanzó su límite. Devuelve `false` si no se pudo reservar.
+    pub fn consumir_subida_upload_link(
+        &self,
+        token: &str,
+        ahora: i64,
+    ) -> Result<bool, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        let actualizados = diesel::update(
+            upload_links::table
+                .filter(upload_links::token.eq(token))
+                .filter(
+                    upload_links::expires_at
+                        .is_null()
+                        .or(upload_links::expires_at.gt(ahora)),
+                )
+                .filter(
+                    upload_links::max_files
+                        .is_null()
+                        .or(upload_links::upload_count
+                            .nullable()
+                            .lt(upload_links::max_files)),
+                ),
+        )
+        .set(upload_links::upload_count.eq(upload_links::upload_count + 1))
+        .execute(&mut conn)?;
+        Ok(actualizados > 0)
+    }
+
+    /// Devuelve una subida reservada que no llegó a guardarse
+    pub fn devolver_subida_upload_link(&self, token: &str) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::update(
+            upload_links::table
+                .filter(upload_links::token.eq(token))
+                .filter(upload_links::upload_count.gt(0)),
+        )
+        .set(upload_links::upload_count.eq(upload_links::upload_count - 1))
+        .execute(&mut conn)
+    }
+
+    pub fn insertar_upload_link_file(
+        &self,
+        nuevo: &UploadLinkFile,
+    ) -> Result<usize, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        diesel::insert_into(upload_link_files::table)
+            .values(nuevo)
+            .execute(&mut conn)
+    }
+
+    /// Archivos recibidos por un enlace, del más reciente al más antiguo
+    pub fn obtener_files_de_upload_link(
+        &self,
+        token: &str,
+    ) -> Result<Vec<(UploadLinkFile, File)>, diesel::result::Error> {
+        let mut conn = self.get_conn();
+        upload_link_files::table
+            .inner_join(files::table)
+            .filter(upload_link_files::token.eq(token))
+            .order(upload_link_files::created_at.desc())
+            .select((upload_link_files::all_columns, files::all_columns))
+            .load(&mut conn)
+    }
+
     // -------------------
     // Obtener archivos de un usuario
     // -------------------
-    /// Obtiene los archivos de un usuario.
+    /// Obtiene los archivos de un usuario que no están en la papelera.
     /// `mime_filtro` permite filtrar por tipo MIME opcional.
     /// `limite` limita la cantidad de resultados opcionalmente.
     pub fn obtener_files_de_usuario(
         &self,
         user_id: &str,
         mime_filtro: Option<&str>,
+        tag_filtro: Option<&str>,
         limite: Option<i64>,
     ) -> Result<Vec<File>, diesel::result::Error> {
         let mut conn = self.get_conn();
         let mut query = files::table
             .filter(files::owner_id.eq(user_id))
+            .filter(files::deleted_at.is_null())
             .into_boxed();
 
         if let Some(mime) = mime_filtro {
             query = query.filter(files::mime.eq(mime));
         }
 
+        if let Some(tag) = tag_filtro {
+            query = query.filter(
+                files::id.eq_any(
+                    file_tags::table
+                        .filter(file_tags::tag.eq(tag))
+                        .select(file_tags::file_id),
+                ),
+            );
+        }
+
         if let Some(lim) = limite {
             query = query.limit(lim);
         }
@@ -1,11 +1,170 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    blob_chunks (blob_hash, seq) {
+        blob_hash -> Text,
+        seq -> Integer,
+        chunk_hash -> Text,
+        byte_offset -> BigInt,
+        size -> BigInt,
+    }
+}
+
+diesel::table! {
+    blobs (hash) {
+        hash -> Text,
+        size -> BigInt,
+        refcount -> Integer,
+        encrypted -> Bool,
+        codec -> Text,
+        sha256 -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    chunks (hash) {
+        hash -> Text,
+        size -> BigInt,
+        codec -> Text,
+        refcount -> Integer,
+    }
+}
+
 diesel::table! {
     files (id) {
         id -> Text,
         mime -> Text,
         hash -> Text,
         owner_id -> Text,
+        filename -> Text,
+        size -> BigInt,
+        created_at -> BigInt,
+        updated_at -> BigInt,
+        last_accessed_at -> Nullable<BigInt>,
+        folder_id -> Nullable<Text>,
+        deleted_at -> Nullable<BigInt>,
+        version -> Integer,
+        version_created_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    file_tags (file_id, tag) {
+        file_id -> Text,
+        tag -> Text,
+    }
+}
+
+diesel::table! {
+    file_versions (file_id, version) {
+        file_id -> Text,
+        version -> Integer,
+        hash -> Text,
+        mime -> Text,
+        size -> BigInt,
+        created_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    folders (id) {
+        id -> Text,
+        owner_id -> Text,
+        parent_id -> Nullable<Text>,
+        name -> Text,
+        created_at -> BigInt,
+        updated_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    grants (id) {
+        id -> Text,
+        owner_id -> Text,
+        grantee_id -> Text,
+        file_id -> Nullable<Text>,
+        folder_id -> Nullable<Text>,
+        role -> Text,
+        created_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    integrity_issues (hash) {
+        hash -> Text,
+        status -> Text,
+        detail -> Text,
+        detected_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    share_links (token) {
+        token -> Text,
+        file_id -> Text,
+        owner_id -> Text,
+        password_hash -> Nullable<Text>,
+        expires_at -> Nullable<BigInt>,
+        max_downloads -> Nullable<Integer>,
+        download_count -> Integer,
+        created_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    team_members (team_id, user_id) {
+        team_id -> Text,
+        user_id -> Text,
+        role -> Text,
+        created_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    teams (id) {
+        id -> Text,
+        name -> Text,
+        used_bytes -> BigInt,
+        quota_bytes -> Nullable<BigInt>,
+        created_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    tus_uploads (id) {
+        id -> Text,
+        owner_id -> Text,
+        mime -> Text,
+        upload_length -> BigInt,
+        upload_offset -> BigInt,
+        file_id -> Nullable<Text>,
+        created_at -> BigInt,
+        updated_at -> BigInt,
+        filename -> Text,
+    }
+}
+
+diesel::table! {
+    upload_link_files (file_id) {
+        file_id -> Text,
+        token -> Text,
+        uploader_name -> Nullable<Text>,
+        created_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    upload_links (token) {
+        token -> Text,
+        folder_id -> Text,
+        owner_id -> Text,
+        password_hash -> Nullable<Text>,
+        expires_at -> Nullable<BigInt>,
+        max_files -> Nullable<Integer>,
+        max_file_size -> Nullable<BigInt>,
+        allowed_mime_types -> Nullable<Text>,
+        upload_count -> Integer,
+        created_at -> BigInt,
     }
 }
 
@@ -15,7 +174,44 @@ diesel::table! {
         username -> Text,
         password -> Text,
         b64_pubkey -> Nullable<Text>,
+        used_bytes -> BigInt,
+        quota_bytes -> Nullable<BigInt>,
+        role -> Text,
+        disabled_at -> Nullable<BigInt>,
+        password_reset_required -> Bool,
+        sessions_revoked_at -> Nullable<BigInt>,
     }
 }
 
-diesel::allow_tables_to_appear_in_same_query!(files, usuarios,);
+diesel::joinable!(blob_chunks -> chunks (chunk_hash));
+diesel::joinable!(file_tags -> files (file_id));
+diesel::joinable!(file_versions -> files (file_id));
+diesel::joinable!(grants -> files (file_id));
+diesel::joinable!(grants -> folders (folder_id));
+diesel::joinable!(share_links -> files (file_id));
+diesel::joinable!(share_links -> usuarios (owner_id));
+diesel::joinable!(team_members -> teams (team_id));
+diesel::joinable!(team_members -> usuarios (user_id));
+diesel::joinable!(upload_link_files -> files (file_id));
+diesel::joinable!(upload_link_files -> upload_links (token));
+diesel::joinable!(upload_links -> folders (folder_id));
+diesel::joinable!(upload_links -> usuarios (owner_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    blob_chunks,
+    blobs,
+    chunks,
+    file_tags,
+    file_versions,
+    files,
+    folders,
+    grants,
+    integrity_issues,
+    share_links,
+    team_members,
+    teams,
+    tus_uploads,
+    upload_link_files,
+    upload_links,
+    usuarios,
+);
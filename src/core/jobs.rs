@@ -0,0 +1,59 @@
+use std::future::Future;
+use std::time::Duration;
+use tracing::{error, info};
+
+use crate::core::procedures::{purge_expired_trash, purge_expired_uploads};
+use crate::core::storage::integrity::scrub_blobs;
+use crate::core::storage::reconcile::reconcile_storage;
+use crate::core::utils::{reconcile_interval_hours, scrub_interval_hours};
+
+/// Lanza las tareas periódicas de mantenimiento en el runtime de tokio.
+///
+/// Debe llamarse una vez, después de cargar la configuración y aplicar las
+/// migraciones.
+pub fn spawn_background_jobs() {
+    spawn_periodic("tus_gc", Duration::from_secs(60 * 60), || async {
+        purge_expired_uploads().await.map(|_| ())
+    });
+
+    spawn_periodic("trash_purge", Duration::from_secs(60 * 60), || async {
+        purge_expired_trash().await.map(|_| ())
+    });
+
+    let scrub_horas = scrub_interval_hours();
+    if scrub_horas > 0 {
+        spawn_periodic(
+            "scrubber",
+            Duration::from_secs(scrub_horas * 60 * 60),
+            || async { scrub_blobs().await.map(|_| ()) },
+        );
+    }
+
+    let reconcile_horas = reconcile_interval_hours();
+    if reconcile_horas > 0 {
+        spawn_periodic(
+            "reconcile",
+            Duration::from_secs(reconcile_horas * 60 * 60),
+            || async { reconcile_storage(false).await.map(|_| ()) },
+        );
+    }
+}
+
+/// Ejecuta `job` cada `every` (la primera vez tras esperar `every`),
+/// registrando los errores sin detener el ciclo
+fn spawn_periodic<F, Fut>(name: &'static str, every: Duration, job: F)
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = anyhow::Result<()>> + Send,
+{
+    info!("Tarea periódica '{}' programada cada {:?}", name, every);
+    tokio::spawn(async move {
+        let mut intervalo = tokio::time::interval_at(tokio::time::Instant::now() + every, every);
+        loop {
+            intervalo.tick().await;
+            if let Err(e) = job().await {
+                error!("Error en la tarea periódica '{}': {}", name, e);
+            }
+        }
+    });
+}
@@ -2,15 +2,23 @@
 // ── Internal modules ─────────────────────────────────────────────────
 pub(crate) mod cryptography;
 mod database;
+pub mod jobs;
 pub mod procedures;
+pub mod storage;
 pub mod structs;
+#[cfg(test)]
+mod testing;
 mod utils;
 
 // ── Direct re-exports for easier access ──────────────────────────────
 pub use database::{get_db_manager, init_db_manager, run_migrations};
+pub use storage::{StorageBackend, init_storage};
 pub use structs::{File, NuevoFile, NuevoUsuario, Usuario};
 pub use utils::{
-    Config, check_temp_perms, db_url, http_port, load_config, paseto_keys_path, write_file,
+    Config, admin_user_ids, check_temp_perms, compression_level_for, db_url, default_quota,
+    http_port, load_config, max_upload_size, orphan_grace_hours, paseto_keys_path,
+    reconcile_interval_hours, scrub_interval_hours, staging_path, trash_retention_days,
+    tus_expiration_hours, uploads_path, write_file, write_stream,
 };
 
 // ── Organized sub-modules (if you prefer) ────────────────────────────
@@ -1,215 +1,3302 @@
 use crate::core::File;
 use crate::core::database::init_db_manager;
-use crate::core::structs::NuevoFile;
-use crate::core::structs::NuevoUsuario;
-use crate::core::utils::write_file;
+use crate::core::storage::integrity::STATUS_MISSING;
+use crate::core::storage::{BlobReader, blobs};
+use crate::core::structs::{
+    AdminUserInfo, Folder, FolderListing, NuevoFolder, NuevoUsuario, ServerStorage, StorageUsage,
+    Usuario,
+};
+use crate::core::structs::{
+    ArchiveEntry, BatchOperation, Blob, CambioLote, FileDownload, FileVersion, Grant,
+    IntegrityIssueInfo, NuevoFile, NuevoGrant, NuevoShareLink, NuevoTeam, NuevoTusUpload,
+    NuevoUploadLink, ShareLink, SharedEntry, TeamMember, TeamMembership, TusUpload, UploadLink,
+    UploadLinkEntry, UploadLinkFile,
+};
+use crate::core::utils::{
+    admin_user_ids, default_quota, max_upload_size, staging_path, trash_retention_days,
+    tus_expiration_hours, write_stream,
+};
 use anyhow::{Context, Result, anyhow};
 use argon2::{
     Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
     password_hash::{SaltString, rand_core::OsRng},
 };
+use async_zip::base::write::ZipFileWriter;
+use async_zip::{Compression, ZipDateTime, ZipEntryBuilder};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
 use blake2::{Blake2b512, Digest};
-use std::path::PathBuf;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use sha2::Sha256;
+use std::collections::{HashMap, HashSet};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex as StdMutex;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+use tokio_util::compat::FuturesAsyncWriteCompatExt;
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
-/// Sube un archivo al sistema
-///
-/// 1. Genera un ID único (UUID)
-/// 2. Calcula el hash Blake2b512
-/// 3. Guarda el registro en la base de datos
-/// 4. Escribe el archivo en disco
+/// Sube un archivo al sistema a partir de un stream
+///
+/// 1. Genera un ID único (UUID) y normaliza el nombre original (sin nombre
+///    se usa el ID). Con `folder_id` el archivo se guarda en esa carpeta y
+///    con `team_id` en la raíz del espacio de ese equipo.
+/// 2. Escribe el stream en un archivo temporal mientras calcula el hash Blake2b512
+/// 3. Guarda el registro en la base de datos y suma una referencia al blob
+/// 4. Publica el temporal en el `StorageBackend` bajo su hash, salvo que
+///    ya exista un blob idéntico
+///
+/// Los archivos subidos al espacio de un equipo son del equipo y ocupan su
+/// cuota, no la de quien los sube.
+///
+/// # Errores
+/// - Si la carpeta destino no existe o el usuario no puede escribir en ella
+/// - Si el stream está vacío o excede `max_upload_size()`, se descarta el temporal
+/// - Si el archivo no cabe en la cuota del espacio, se descarta el temporal
+/// - Si falla la inserción en DB, se descarta el temporal
+/// - Si falla la publicación en el storage, se hace rollback en DB
+pub async fn upload_file<R>(
+    user_id: &str,
+    mime: &str,
+    filename: Option<&str>,
+    folder_id: Option<&str>,
+    team_id: Option<&str>,
+    reader: &mut R,
+) -> Result<String>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let owner_id = drive_owner(user_id, team_id, folder_id)?;
+    // Sin espacio libre no tiene sentido recibir el stream
+    check_quota(&owner_id, 1)?;
+
+    let file_id = Uuid::new_v4().to_string();
+    let filename = normalize_filename(filename, &file_id);
+    let temp_path = staging_path().join(format!("{}.tmp", file_id));
+
+    let (hash, sha256, size) = stage_stream(&temp_path, reader, max_upload_size()).await?;
+    let ahora = Utc::now().timestamp();
+    let nuevo_file = NuevoFile {
+        id: &file_id,
+        mime,
+        hash: &hash,
+        owner_id: &owner_id,
+        filename: &filename,
+        size: size as i64,
+        created_at: ahora,
+        updated_at: ahora,
+        folder_id,
+        version_created_at: ahora,
+    };
+
+    register_staged_file(&nuevo_file, &sha256, &temp_path).await?;
+    Ok(file_id)
+}
+
+/// Escribe el stream en `temp_path` calculando sus hashes Blake2b512 y
+/// SHA-256 por bloques. Si falla, el stream está vacío o supera `limite`
+/// bytes se descarta el temporal.
+///
+/// # Retorna
+/// Los hashes en hexadecimal y el tamaño en bytes
+async fn stage_stream<R>(
+    temp_path: &Path,
+    reader: &mut R,
+    limite: u64,
+) -> Result<(String, String, u64)>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let mut hasher = Blake2b512::new();
+    let mut sha256 = Sha256::new();
+    let size = match write_stream(temp_path, reader, limite, |chunk| {
+        hasher.update(chunk);
+        sha256.update(chunk);
+    })
+    .await
+    {
+        Ok(size) => size,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(temp_path).await;
+            return Err(e);
+        }
+    };
+
+    if size == 0 {
+        let _ = tokio::fs::remove_file(temp_path).await;
+        return Err(anyhow!("El archivo no puede estar vacío"));
+    }
+
+    Ok((
+        format!("{:x}", hasher.finalize()),
+        format!("{:x}", sha256.finalize()),
+        size,
+    ))
+}
+
+/// Nombre de archivo seguro para guardar y devolver en `Content-Disposition`:
+/// sin rutas ni caracteres de control y de como máximo 255 caracteres.
+/// Si no queda nada utilizable se usa `fallback`.
+fn normalize_filename(filename: Option<&str>, fallback: &str) -> String {
+    let nombre: String = filename
+        .unwrap_or_default()
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(255)
+        .collect();
+    let nombre = nombre.trim();
+
+    if nombre.is_empty() || nombre == "." || nombre == ".." {
+        fallback.to_string()
+    } else {
+        nombre.to_string()
+    }
+}
+
+/// Valida el nuevo nombre de un archivo: 1-255 caracteres, sin separadores
+/// de ruta ni caracteres de control
+fn validate_filename(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty()
+        || name.chars().count() > 255
+        || name == "."
+        || name == ".."
+        || name
+            .chars()
+            .any(|c| c == '/' || c == '\\' || c.is_control())
+    {
+        return Err(anyhow!("Nombre de archivo inválido"));
+    }
+    Ok(name)
+}
+
+/// Normaliza una etiqueta a minúsculas: 1-64 caracteres, sin comas ni
+/// caracteres de control
+fn normalize_tag(tag: &str) -> Result<String> {
+    let tag = tag.trim().to_lowercase();
+    if tag.is_empty() || tag.chars().count() > 64 || tag.chars().any(|c| c == ',' || c.is_control())
+    {
+        return Err(anyhow!("Etiqueta inválida: '{}'", tag));
+    }
+    Ok(tag)
+}
+
+/// Registra un archivo ya escrito en staging y lo publica en el storage.
+/// Compartido por la subida directa y la reanudable.
+async fn register_staged_file(
+    nuevo_file: &NuevoFile<'_>,
+    sha256: &str,
+    staged: &Path,
+) -> Result<()> {
+    info!(
+        "Procesando archivo: {} para usuario: {} (tamaño: {} bytes)",
+        nuevo_file.id, nuevo_file.owner_id, nuevo_file.size
+    );
+
+    // Guardar en la base de datos y publicar el blob (deduplicado por hash)
+    let cuota = match check_quota(nuevo_file.owner_id, nuevo_file.size as u64) {
+        Ok(cuota) => cuota,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(staged).await;
+            return Err(e);
+        }
+    };
+    blobs::store_file(nuevo_file, sha256, staged, cuota).await?;
+
+    info!("Archivo {} guardado exitosamente", nuevo_file.id);
+    Ok(())
+}
+
+// ============================================================================
+// Cuotas
+// ============================================================================
+
+/// Uso y cuota efectiva (`None` = sin límite) a partir de los bytes usados
+/// y la cuota propia de un usuario o equipo
+fn usage_for(used_bytes: i64, quota_bytes: Option<i64>) -> StorageUsage {
+    let used_bytes = used_bytes.max(0) as u64;
+    let cuota = match quota_bytes {
+        Some(cuota) => Some(cuota.max(0) as u64),
+        None => default_quota(),
+    };
+    StorageUsage {
+        used_bytes,
+        quota_bytes: cuota,
+        available_bytes: cuota.map(|cuota| cuota.saturating_sub(used_bytes)),
+        custom_quota: quota_bytes.is_some(),
+    }
+}
+
+/// Uso del espacio de `owner_id`, que puede ser un usuario o un equipo
+fn drive_usage(owner_id: &str) -> Result<StorageUsage> {
+    let db = init_db_manager();
+    if let Ok(usuario) = db.buscar_usuario(owner_id) {
+        return Ok(usage_for(usuario.used_bytes, usuario.quota_bytes));
+    }
+    let team = db
+        .buscar_team(owner_id)
+        .map_err(|_| anyhow!("Usuario '{}' no encontrado", owner_id))?;
+    Ok(usage_for(team.used_bytes, team.quota_bytes))
+}
+
+/// Comprueba que `incoming` bytes más quepan en la cuota del dueño del
+/// espacio (usuario o equipo). Devuelve la cuota aplicada.
+fn check_quota(owner_id: &str, incoming: u64) -> Result<Option<u64>> {
+    let uso = drive_usage(owner_id)?;
+
+    let cuota = uso.quota_bytes;
+    let usado = uso.used_bytes;
+    if let Some(cuota) = cuota
+        && usado + incoming > cuota
+    {
+        warn!(
+            "Espacio {} sin lugar: {} + {} bytes supera la cuota de {}",
+            owner_id, usado, incoming, cuota
+        );
+        return Err(anyhow!(
+            "El archivo excede la cuota de almacenamiento ({} de {} bytes usados)",
+            usado,
+            cuota
+        ));
+    }
+    Ok(cuota)
+}
+
+/// Uso de almacenamiento y cuota del usuario
+pub async fn storage_usage(user_id: &str) -> Result<StorageUsage> {
+    let usuario = init_db_manager()
+        .buscar_usuario(user_id)
+        .map_err(|_| anyhow!("Usuario '{}' no encontrado", user_id))?;
+
+    Ok(usage_for(usuario.used_bytes, usuario.quota_bytes))
+}
+
+/// Fija la cuota propia de un usuario; `None` vuelve a la cuota por defecto
+pub async fn set_user_quota(user_id: &str, quota_bytes: Option<u64>) -> Result<StorageUsage> {
+    let quota_bytes = quota_bytes
+        .map(|cuota| i64::try_from(cuota).map_err(|_| anyhow!("Cuota inválida")))
+        .transpose()?;
+
+    let actualizados = init_db_manager()
+        .actualizar_cuota_usuario(user_id, quota_bytes)
+        .context("Error al actualizar la cuota")?;
+    if actualizados == 0 {
+        return Err(anyhow!("Usuario '{}' no encontrado", user_id));
+    }
+
+    info!("Cuota de {} actualizada a {:?} bytes", user_id, quota_bytes);
+    storage_usage(user_id).await
+}
+
+// ============================================================================
+// Subidas reanudables (protocolo tus 1.0)
+// ============================================================================
+
+/// IDs de subidas reanudables con un PATCH en curso en este proceso
+static TUS_EN_CURSO: Lazy<StdMutex<HashSet<String>>> = Lazy::new(Default::default);
+
+/// Marca una subida como en curso mientras viva el guard
+struct TusLock(String);
+
+impl TusLock {
+    fn acquire(upload_id: &str) -> Result<Self> {
+        let mut en_curso = TUS_EN_CURSO.lock().unwrap_or_else(|e| e.into_inner());
+        if !en_curso.insert(upload_id.to_string()) {
+            return Err(anyhow!(
+                "La subida {} está en uso por otra petición",
+                upload_id
+            ));
+        }
+        Ok(TusLock(upload_id.to_string()))
+    }
+}
+
+impl Drop for TusLock {
+    fn drop(&mut self) {
+        let mut en_curso = TUS_EN_CURSO.lock().unwrap_or_else(|e| e.into_inner());
+        en_curso.remove(&self.0);
+    }
+}
+
+fn tus_part_path(upload_id: &str) -> PathBuf {
+    staging_path().join(format!("{}.part", upload_id))
+}
+
+/// Busca una subida reanudable verificando que pertenezca al usuario
+fn find_resumable_upload(user_id: &str, upload_id: &str) -> Result<TusUpload> {
+    if upload_id.contains("..") || upload_id.contains('/') || upload_id.contains('\\') {
+        error!("Intento de path traversal detectado en tus: {}", upload_id);
+        return Err(anyhow!("ID de subida inválido"));
+    }
+
+    match init_db_manager().buscar_tus_upload(upload_id) {
+        Ok(upload) if upload.owner_id == user_id => Ok(upload),
+        _ => {
+            warn!(
+                "Subida {} no encontrada o no pertenece al usuario {}",
+                upload_id, user_id
+            );
+            Err(anyhow!("Subida no encontrada"))
+        }
+    }
+}
+
+/// Crea una subida reanudable de `length` bytes
+///
+/// # Validaciones
+/// - `length` mayor que cero y dentro de `max_upload_size()`
+/// - `length` cabe en la cuota del usuario
+pub async fn create_resumable_upload(
+    user_id: &str,
+    mime: &str,
+    filename: Option<&str>,
+    length: u64,
+) -> Result<TusUpload> {
+    if length == 0 {
+        return Err(anyhow!("El archivo no puede estar vacío"));
+    }
+    if length > max_upload_size() {
+        return Err(anyhow!(
+            "El archivo excede el tamaño máximo permitido ({} bytes)",
+            max_upload_size()
+        ));
+    }
+    check_quota(user_id, length)?;
+
+    let upload_id = Uuid::new_v4().simple().to_string();
+    // Sin nombre se guarda vacío y al completarse se usa el ID del archivo
+    let filename = normalize_filename(filename, "");
+    let ahora = Utc::now().timestamp();
+
+    // El archivo parcial existe desde el principio para que HEAD sea coherente
+    tokio::fs::File::create(tus_part_path(&upload_id))
+        .await
+        .context("Error al crear el archivo parcial")?;
+
+    init_db_manager()
+        .insertar_tus_upload(&NuevoTusUpload {
+            id: &upload_id,
+            owner_id: user_id,
+            mime,
+            upload_length: length as i64,
+            upload_offset: 0,
+            created_at: ahora,
+            updated_at: ahora,
+            filename: &filename,
+        })
+        .context("Error al registrar la subida en la base de datos")?;
+
+    info!(
+        "Subida reanudable {} creada para usuario {} ({} bytes)",
+        upload_id, user_id, length
+    );
+    init_db_manager()
+        .buscar_tus_upload(&upload_id)
+        .context("Error al leer la subida recién creada")
+}
+
+/// Estado actual de una subida reanudable
+pub async fn resumable_upload_status(user_id: &str, upload_id: &str) -> Result<TusUpload> {
+    find_resumable_upload(user_id, upload_id)
+}
+
+/// Añade bytes a una subida reanudable a partir de `offset`
+///
+/// 1. Verifica propiedad y que `offset` coincida con lo ya recibido
+/// 2. Escribe el stream al final del archivo parcial
+/// 3. Persiste el nuevo offset, incluso si la conexión se cortó a mitad
+/// 4. Si se completó, calcula el hash y registra el archivo como una subida normal
+///
+/// # Errores
+/// - Offset distinto del actual
+/// - Más datos que los declarados en `Upload-Length`
+pub async fn append_resumable_upload<R>(
+    user_id: &str,
+    upload_id: &str,
+    offset: u64,
+    reader: &mut R,
+) -> Result<TusUpload>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let _lock = TusLock::acquire(upload_id)?;
+    let upload = find_resumable_upload(user_id, upload_id)?;
+    let actual = upload.upload_offset as u64;
+    let length = upload.upload_length as u64;
+
+    if offset != actual {
+        return Err(anyhow!(
+            "El offset {} no coincide con el actual ({})",
+            offset,
+            actual
+        ));
+    }
+    if upload.file_id.is_some() || actual == length {
+        return Ok(upload);
+    }
+
+    // Descartar cualquier byte más allá del offset confirmado
+    let part_path = tus_part_path(upload_id);
+    let mut part = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(&part_path)
+        .await
+        .context("Error al abrir el archivo parcial")?;
+    part.set_len(actual).await?;
+    part.seek(SeekFrom::End(0)).await?;
+
+    let restante = length - actual;
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut escritos: u64 = 0;
+    let mut fallo = None;
+    loop {
+        let leidos = match reader.read(&mut buffer).await {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => {
+                fallo = Some(anyhow!("Error al leer datos del stream: {}", e));
+                break;
+            }
+        };
+        if escritos + leidos as u64 > restante {
+            fallo = Some(anyhow!(
+                "El archivo excede el tamaño declarado ({} bytes)",
+                length
+            ));
+            break;
+        }
+        if let Err(e) = part.write_all(&buffer[..leidos]).await {
+            fallo = Some(anyhow!("Error al escribir el archivo parcial: {}", e));
+            break;
+        }
+        escritos += leidos as u64;
+    }
+    part.flush().await?;
+    part.sync_all().await?;
+    drop(part);
+
+    // Lo recibido se conserva aunque la petición haya fallado
+    let nuevo_offset = actual + escritos;
+    let ahora = Utc::now().timestamp();
+    init_db_manager()
+        .actualizar_offset_tus_upload(upload_id, nuevo_offset as i64, ahora)
+        .context("Error al actualizar el offset de la subida")?;
+
+    if let Some(e) = fallo {
+        warn!(
+            "Subida {} interrumpida en el offset {}: {}",
+            upload_id, nuevo_offset, e
+        );
+        return Err(e);
+    }
+
+    if nuevo_offset == length {
+        let (hash, sha256) = hash_file(&part_path).await?;
+        let file_id = Uuid::new_v4().to_string();
+        let filename = normalize_filename(Some(&upload.filename), &file_id);
+        let nuevo_file = NuevoFile {
+            id: &file_id,
+            mime: &upload.mime,
+            hash: &hash,
+            owner_id: user_id,
+            filename: &filename,
+            size: length as i64,
+            created_at: ahora,
+            updated_at: ahora,
+            folder_id: None,
+            version_created_at: ahora,
+        };
+        // Si el registro falla el parcial ya se descartó: la sesión deja de servir
+        if let Err(e) = register_staged_file(&nuevo_file, &sha256, &part_path).await {
+            let _ = init_db_manager().borrar_tus_upload(upload_id);
+            return Err(e);
+        }
+        init_db_manager()
+            .completar_tus_upload(upload_id, &file_id, ahora)
+            .context("Error al completar la subida")?;
+        info!(
+            "Subida reanudable {} completada como archivo {}",
+            upload_id, file_id
+        );
+    }
+
+    find_resumable_upload(user_id, upload_id)
+}
+
+/// Cancela una subida reanudable y descarta lo recibido
+///
+/// Si la subida ya se había completado, el archivo resultante se conserva.
+pub async fn terminate_resumable_upload(user_id: &str, upload_id: &str) -> Result<()> {
+    let _lock = TusLock::acquire(upload_id)?;
+    find_resumable_upload(user_id, upload_id)?;
+
+    init_db_manager()
+        .borrar_tus_upload(upload_id)
+        .context("Error al eliminar la subida de la base de datos")?;
+    let _ = tokio::fs::remove_file(tus_part_path(upload_id)).await;
+
+    info!("Subida reanudable {} cancelada", upload_id);
+    Ok(())
+}
+
+/// Elimina las subidas reanudables sin actividad durante `tus_expiration_hours`
+///
+/// # Retorna
+/// Cantidad de subidas eliminadas
+pub async fn purge_expired_uploads() -> Result<usize> {
+    let limite = Utc::now().timestamp() - tus_expiration_hours() * 3600;
+    let db = init_db_manager();
+    let expiradas = db
+        .obtener_tus_uploads_inactivos(limite)
+        .context("Error al buscar subidas expiradas")?;
+
+    let mut eliminadas = 0;
+    for upload in expiradas {
+        // Una subida con un PATCH en curso no está abandonada
+        let Ok(_lock) = TusLock::acquire(&upload.id) else {
+            continue;
+        };
+        let _ = tokio::fs::remove_file(tus_part_path(&upload.id)).await;
+        if db.borrar_tus_upload(&upload.id).is_ok() {
+            eliminadas += 1;
+        }
+    }
+
+    if eliminadas > 0 {
+        info!("{} subida(s) reanudables expiradas eliminadas", eliminadas);
+    }
+    Ok(eliminadas)
+}
+
+/// Calcula los hashes Blake2b512 y SHA-256 de un archivo local leyéndolo
+/// por bloques
+async fn hash_file(path: &Path) -> Result<(String, String)> {
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("No se pudo abrir {:?}", path))?;
+    let mut hasher = Blake2b512::new();
+    let mut sha256 = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let leidos = file.read(&mut buffer).await?;
+        if leidos == 0 {
+            break;
+        }
+        hasher.update(&buffer[..leidos]);
+        sha256.update(&buffer[..leidos]);
+    }
+    Ok((
+        format!("{:x}", hasher.finalize()),
+        format!("{:x}", sha256.finalize()),
+    ))
+}
+
+/// Lista los archivos de un usuario con filtros opcionales
+///
+/// # Parámetros
+/// - `user_id`: ID del usuario propietario
+/// - `mime_filter`: Filtro opcional por tipo MIME
+/// - `tag_filter`: Filtro opcional por etiqueta
+/// - `limit`: Límite opcional de resultados (1-1000)
+///
+/// # Retorna
+/// Vector de archivos que cumplen los criterios
+pub async fn list_user_files(
+    user_id: &str,
+    mime_filter: Option<&str>,
+    tag_filter: Option<&str>,
+    limit: Option<i64>,
+) -> Result<Vec<File>> {
+    info!(
+        "Listando archivos para usuario: {} (mime: {:?}, tag: {:?}, limit: {:?})",
+        user_id, mime_filter, tag_filter, limit
+    );
+
+    let tag_filter = tag_filter.map(normalize_tag).transpose()?;
+    let files = init_db_manager()
+        .obtener_files_de_usuario(user_id, mime_filter, tag_filter.as_deref(), limit)
+        .context("Error al obtener archivos de la base de datos")?;
+
+    info!(
+        "Encontrados {} archivos para usuario {}",
+        files.len(),
+        user_id
+    );
+    Ok(files)
+}
+
+/// Prepara la descarga de un archivo verificando propiedad y registra el
+/// acceso. Las validaciones son las de `stat_file`.
+///
+/// # Retorna
+/// Metadatos del archivo; el contenido se abre con `open_file_range`
+pub async fn download_file(user_id: &str, file_id: &str) -> Result<FileDownload> {
+    let file = stat_file(user_id, file_id).await?;
+
+    info!(
+        "Usuario {} solicitando descarga del archivo {}",
+        user_id, file_id
+    );
+
+    if let Err(e) = init_db_manager().marcar_acceso_file(file_id, Utc::now().timestamp()) {
+        warn!(
+            "No se pudo registrar el acceso al archivo {}: {}",
+            file_id, e
+        );
+    }
+
+    Ok(file)
+}
+
+/// Obtiene los metadatos de descarga de un archivo sin registrar un acceso
+/// (peticiones `HEAD`)
+///
+/// # Validaciones
+/// - El archivo debe existir
+/// - El archivo debe pertenecer al usuario o estar compartido con él
+/// - El blob del archivo debe estar registrado
+/// - El blob no debe estar marcado como corrupto por el scrubber
+///
+/// # Retorna
+/// Metadatos del archivo, incluidos los validadores de caché
+pub async fn stat_file(user_id: &str, file_id: &str) -> Result<FileDownload> {
+    let file = find_accessible_file(user_id, file_id, false)?;
+    file_download(file)
+}
+
+/// Metadatos de descarga de un archivo ya autorizado
+fn file_download(file: File) -> Result<FileDownload> {
+    let blob = find_servable_blob(&file.id, &file.hash)?;
+
+    Ok(FileDownload {
+        id: file.id,
+        mime: file.mime,
+        hash: file.hash,
+        filename: file.filename,
+        size: blob.size.max(0) as u64,
+        modified_at: file.version_created_at,
+        sha256: blob.sha256,
+    })
+}
+
+/// Busca el blob con el contenido de un archivo, rechazando los que el
+/// scrubber o la reconciliación marcaron como dañados o ausentes
+fn find_servable_blob(file_id: &str, hash: &str) -> Result<Blob> {
+    let blob = init_db_manager()
+        .buscar_blob(hash)
+        .with_context(|| format!("Blob del archivo {} no encontrado", file_id))?;
+
+    let problema = init_db_manager()
+        .buscar_problema_integridad(hash)
+        .context("Error al consultar el estado de integridad")?;
+    if let Some(problema) = problema {
+        error!(
+            "Descarga rechazada: archivo {} con blob {} ({})",
+            file_id, problema.status, problema.detail
+        );
+        if problema.status == STATUS_MISSING {
+            return Err(anyhow!(
+                "Contenido del archivo {} no encontrado en el storage",
+                file_id
+            ));
+        }
+        return Err(anyhow!(
+            "El archivo {} está corrupto y no se puede descargar",
+            file_id
+        ));
+    }
+    Ok(blob)
+}
+
+/// Abre `len` bytes del contenido de un archivo a partir de `start`
+///
+/// El contenido se descifra de forma transparente y solo se leen del
+/// storage los bloques que cubren el rango pedido.
+pub async fn open_file_range(file: &FileDownload, start: u64, len: u64) -> Result<BlobReader> {
+    let reader = blobs::open_blob_range(&file.hash, start, len)
+        .await
+        .with_context(|| format!("Error al leer archivo {} del storage", file.id))?;
+
+    info!(
+        "Archivo {} abierto para descarga (bytes {}-{})",
+        file.id,
+        start,
+        start + len.saturating_sub(1)
+    );
+    Ok(reader)
+}
+
+/// Envía un archivo a la papelera
+///
+/// # Validaciones
+/// - El archivo debe existir y no estar ya en la papelera
+/// - El archivo debe pertenecer al usuario o estar compartido con él como
+///   `editor`
+///
+/// # Acciones
+/// 1. Verifica propiedad o permiso
+/// 2. Marca el archivo con `deleted_at`; deja de listarse y descargarse
+///
+/// El blob y el uso de la cuota se liberan recién al purgarlo (ver
+/// `purge_file`, `empty_trash` y `purge_expired_trash`).
+pub async fn delete_file(user_id: &str, file_id: &str) -> Result<()> {
+    info!("Usuario {} eliminando archivo {}", user_id, file_id);
+
+    find_accessible_file(user_id, file_id, true)?;
+
+    init_db_manager()
+        .mover_file_a_papelera(file_id, Utc::now().timestamp())
+        .context("Error al enviar el archivo a la papelera")?;
+
+    info!("Archivo {} enviado a la papelera", file_id);
+    Ok(())
+}
+
+/// Busca un archivo activo del usuario o del espacio de uno de sus equipos
+/// donde puede escribir. Un permiso compartido como `editor` alcanza para
+/// renombrar, versionar o enviar a la papelera, pero no para mover el
+/// archivo ni volver a compartirlo.
+fn find_drive_file(user_id: &str, file_id: &str) -> Result<File> {
+    let file = find_accessible_file(user_id, file_id, true)?;
+    if file.owner_id != user_id && team_access(user_id, &file.owner_id).is_none() {
+        return Err(anyhow!(
+            "Permiso insuficiente: el archivo no está en tu espacio ni en el de tus equipos"
+        ));
+    }
+    Ok(file)
+}
+
+/// Mueve un archivo a otra carpeta de su mismo espacio (`None` = raíz del
+/// espacio)
+///
+/// # Validaciones
+/// - El archivo está en el espacio del usuario o de un equipo donde puede
+///   escribir
+/// - La carpeta destino es del mismo espacio que el archivo
+pub async fn move_file(user_id: &str, file_id: &str, folder_id: Option<&str>) -> Result<File> {
+    let file = find_drive_file(user_id, file_id)?;
+    check_move_target(user_id, &file.owner_id, folder_id)?;
+
+    init_db_manager()
+        .mover_file(file_id, folder_id, Utc::now().timestamp())
+        .context("Error al mover el archivo")?;
+
+    info!("Archivo {} movido a {:?}", file_id, folder_id);
+    find_drive_file(user_id, file_id)
+}
+
+/// Renombra un archivo del usuario o compartido con él como `editor`
+///
+/// # Validaciones
+/// - El archivo existe, es accesible como editor y no está en la papelera
+/// - Nombre de 1-255 caracteres sin separadores de ruta
+pub async fn rename_file(user_id: &str, file_id: &str, name: &str) -> Result<File> {
+    find_accessible_file(user_id, file_id, true)?;
+    let name = validate_filename(name)?;
+
+    init_db_manager()
+        .renombrar_file(file_id, name, Utc::now().timestamp())
+        .context("Error al renombrar el archivo")?;
+
+    info!("Archivo {} renombrado a '{}'", file_id, name);
+    find_accessible_file(user_id, file_id, true)
+}
+
+/// Copia un archivo con un ID nuevo, sin volver a subir ni duplicar su
+/// contenido: la copia comparte el blob del original. Solo se copia la
+/// versión actual; el historial y las etiquetas no.
+///
+/// # Parámetros
+/// - `folder_id`: carpeta destino (`None` = raíz)
+/// - `name`: nombre de la copia; sin él se conserva el del original
+///
+/// # Validaciones
+/// - Las mismas que la descarga: el archivo es accesible para el usuario y
+///   su blob está disponible y no está marcado como dañado
+/// - La carpeta destino es del usuario o de un equipo donde puede escribir;
+///   sin carpeta la copia va a la raíz del usuario
+/// - La copia cabe en la cuota del espacio destino
+pub async fn copy_file(
+    user_id: &str,
+    file_id: &str,
+    folder_id: Option<&str>,
+    name: Option<&str>,
+) -> Result<File> {
+    let original = find_accessible_file(user_id, file_id, false)?;
+    let owner_id = drive_owner(user_id, None, folder_id)?;
+    copy_into(user_id, &original, &owner_id, folder_id, name).await
+}
+
+/// Copia `original` en la carpeta `folder_id` del espacio `owner_id`, ya
+/// validados por quien llama
+async fn copy_into(
+    user_id: &str,
+    original: &File,
+    owner_id: &str,
+    folder_id: Option<&str>,
+    name: Option<&str>,
+) -> Result<File> {
+    let file_id = original.id.as_str();
+    find_servable_blob(file_id, &original.hash)?;
+    let filename = match name {
+        Some(name) => validate_filename(name)?,
+        None => original.filename.as_str(),
+    };
+    let cuota = check_quota(owner_id, original.size.max(0) as u64)?;
+
+    let copia_id = Uuid::new_v4().to_string();
+    let ahora = Utc::now().timestamp();
+    blobs::copy_file(
+        &NuevoFile {
+            id: &copia_id,
+            mime: &original.mime,
+            hash: &original.hash,
+            owner_id,
+            filename,
+            size: original.size,
+            created_at: ahora,
+            updated_at: ahora,
+            folder_id,
+            version_created_at: ahora,
+        },
+        cuota,
+    )
+    .await?;
+
+    info!(
+        "Archivo {} copiado como {} en {:?}",
+        file_id, copia_id, folder_id
+    );
+    find_accessible_file(user_id, &copia_id, false)
+}
+
+/// Duplica un archivo en su misma carpeta con un nombre libre
+/// (`informe (copia).pdf`, `informe (copia 2).pdf`, ...). Las validaciones
+/// son las de `copy_file`, y además el archivo debe estar en el espacio del
+/// usuario o de un equipo donde puede escribir.
+pub async fn duplicate_file(user_id: &str, file_id: &str) -> Result<File> {
+    let original = find_drive_file(user_id, file_id)?;
+    let hermanos: HashSet<String> = init_db_manager()
+        .obtener_files_de_carpeta(&original.owner_id, original.folder_id.as_deref())
+        .context("Error al obtener los archivos de la carpeta")?
+        .into_iter()
+        .map(|f| f.filename)
+        .collect();
+
+    let (base, extension) = match original.filename.rsplit_once('.') {
+        Some((base, extension)) if !base.is_empty() => (base, format!(".{}", extension)),
+        _ => (original.filename.as_str(), String::new()),
+    };
+    let mut nombre = format!("{} (copia){}", base, extension);
+    let mut n = 2;
+    while hermanos.contains(&nombre) {
+        nombre = format!("{} (copia {}){}", base, n, extension);
+        n += 1;
+    }
+
+    copy_into(
+        user_id,
+        &original,
+        &original.owner_id,
+        original.folder_id.as_deref(),
+        Some(&nombre),
+    )
+    .await
+}
+
+// ============================================================================
+// Operaciones por lotes
+// ============================================================================
+
+/// Máximo de operaciones en un mismo lote
+const MAX_BATCH_OPERATIONS: usize = 1000;
+
+/// Valida una operación del lote y la traduce al cambio que se aplica en DB
+fn validate_batch_operation(user_id: &str, operacion: &BatchOperation) -> Result<CambioLote> {
+    let file_id = operacion.file_id().to_string();
+    let file = find_accessible_file(user_id, &file_id, true)?;
+
+    Ok(match operacion {
+        BatchOperation::Delete { .. } => CambioLote::Papelera { file_id },
+        BatchOperation::Move { folder_id, .. } => {
+            // Mover exige lo mismo que `move_file`, no solo el rol `editor`
+            find_drive_file(user_id, &file_id)?;
+            check_move_target(user_id, &file.owner_id, folder_id.as_deref())?;
+            CambioLote::Mover {
+                file_id,
+                folder_id: folder_id.clone(),
+            }
+        }
+        BatchOperation::Rename { name, .. } => CambioLote::Renombrar {
+            file_id,
+            filename: validate_filename(name)?.to_string(),
+        },
+        BatchOperation::Tag { add, remove, .. } => {
+            if add.is_empty() && remove.is_empty() {
+                return Err(anyhow!("Operación inválida: sin etiquetas que cambiar"));
+            }
+            CambioLote::Etiquetar {
+                file_id,
+                agregar: add
+                    .iter()
+                    .map(|t| normalize_tag(t))
+                    .collect::<Result<_>>()?,
+                quitar: remove
+                    .iter()
+                    .map(|t| normalize_tag(t))
+                    .collect::<Result<_>>()?,
+            }
+        }
+    })
+}
+
+/// Aplica un lote de operaciones sobre archivos del usuario, del espacio de
+/// sus equipos o compartidos con él como `editor`
+///
+/// Cada operación se valida por separado y las válidas se aplican juntas en
+/// una transacción (`aplicar_lote`), donde cada una puede fallar sin
+/// afectar a las demás. Los fallos se informan en su posición del resultado.
+///
+/// # Errores
+/// - Si el lote está vacío o supera `MAX_BATCH_OPERATIONS`
+/// - Si falla la transacción completa
+///
+/// # Retorna
+/// El resultado de cada operación, en el mismo orden que `operaciones`
+pub async fn apply_batch(user_id: &str, operaciones: &[BatchOperation]) -> Result<Vec<Result<()>>> {
+    if operaciones.is_empty() || operaciones.len() > MAX_BATCH_OPERATIONS {
+        return Err(anyhow!(
+            "Lote inválido: debe tener entre 1 y {} operaciones",
+            MAX_BATCH_OPERATIONS
+        ));
+    }
+
+    let mut resultados = Vec::with_capacity(operaciones.len());
+    let mut cambios = Vec::new();
+    let mut posiciones = Vec::new();
+    for (i, operacion) in operaciones.iter().enumerate() {
+        match validate_batch_operation(user_id, operacion) {
+            Ok(cambio) => {
+                cambios.push(cambio);
+                posiciones.push(i);
+                resultados.push(Ok(()));
+            }
+            Err(e) => resultados.push(Err(e)),
+        }
+    }
+
+    let aplicados = init_db_manager()
+        .aplicar_lote(&cambios, Utc::now().timestamp())
+        .context("Error al aplicar el lote")?;
+    for (i, aplicado) in posiciones.into_iter().zip(aplicados) {
+        resultados[i] = aplicado.map_err(|e| match e {
+            diesel::result::Error::NotFound => anyhow!("Archivo no encontrado"),
+            e => anyhow!(e).context("Error al aplicar la operación"),
+        });
+    }
+
+    let fallidas = resultados.iter().filter(|r| r.is_err()).count();
+    info!(
+        "Lote de {} operación(es) del usuario {}: {} fallida(s)",
+        operaciones.len(),
+        user_id,
+        fallidas
+    );
+    Ok(resultados)
+}
+
+// ============================================================================
+// Versiones
+// ============================================================================
+
+/// Sube una nueva versión del contenido de un archivo existente
+///
+/// El contenido actual pasa al historial con su número de versión, hash,
+/// tamaño y fecha; el nuevo ocupa su lugar con el número siguiente. El ID,
+/// el nombre y la carpeta del archivo no cambian. Sin `mime` se conserva el
+/// del archivo.
+///
+/// # Errores
+/// - Si el archivo no existe, está en la papelera o el usuario no puede
+///   escribirlo (dueño, miembro del equipo dueño o `editor`)
+/// - Si el stream está vacío, excede `max_upload_size()` o la cuota del
+///   dueño del archivo
+/// - Si el contenido es idéntico al de la versión actual
+pub async fn upload_file_version<R>(
+    user_id: &str,
+    file_id: &str,
+    mime: Option<&str>,
+    reader: &mut R,
+) -> Result<File>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let file = find_accessible_file(user_id, file_id, true)?;
+    check_quota(&file.owner_id, 1)?;
+
+    let temp_path = staging_path().join(format!("{}.tmp", Uuid::new_v4()));
+    let (hash, sha256, size) = stage_stream(&temp_path, reader, max_upload_size()).await?;
+
+    if hash == file.hash {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(anyhow!("El contenido ya es la versión actual del archivo"));
+    }
+
+    let ahora = Utc::now().timestamp();
+    let nueva = NuevoFile {
+        id: &file.id,
+        mime: mime.unwrap_or(&file.mime),
+        hash: &hash,
+        owner_id: &file.owner_id,
+        filename: &file.filename,
+        size: size as i64,
+        created_at: file.created_at,
+        updated_at: ahora,
+        folder_id: file.folder_id.as_deref(),
+        version_created_at: ahora,
+    };
+
+    let cuota = match check_quota(&file.owner_id, size) {
+        Ok(cuota) => cuota,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(e);
+        }
+    };
+    blobs::store_file_version(&nueva, &sha256, &temp_path, cuota).await?;
+
+    info!(
+        "Versión {} del archivo {} guardada ({} bytes)",
+        file.version + 1,
+        file_id,
+        size
+    );
+    find_accessible_file(user_id, file_id, true)
+}
+
+/// Busca una versión anterior de un archivo accesible para el usuario (con
+/// `editor`, como en `find_accessible_file`)
+fn find_file_version(
+    user_id: &str,
+    file_id: &str,
+    version: i32,
+    editor: bool,
+) -> Result<(File, FileVersion)> {
+    let file = find_accessible_file(user_id, file_id, editor)?;
+    match init_db_manager().buscar_version_file(file_id, version) {
+        Ok(anterior) => Ok((file, anterior)),
+        Err(_) => Err(anyhow!(
+            "Versión {} del archivo {} no encontrada",
+            version,
+            file_id
+        )),
+    }
+}
+
+/// Lista las versiones de un archivo, empezando por la actual
+///
+/// # Retorna
+/// El archivo y sus versiones anteriores, de la más reciente a la más antigua
+pub async fn list_file_versions(user_id: &str, file_id: &str) -> Result<(File, Vec<FileVersion>)> {
+    let file = find_accessible_file(user_id, file_id, false)?;
+    let versiones = init_db_manager()
+        .obtener_versiones_file(file_id)
+        .context("Error al obtener las versiones del archivo")?;
+    Ok((file, versiones))
+}
+
+/// Prepara la descarga de una versión anterior de un archivo
+///
+/// Se sirve con el nombre del archivo y el tipo MIME que tenía esa versión.
+pub async fn download_file_version(
+    user_id: &str,
+    file_id: &str,
+    version: i32,
+) -> Result<FileDownload> {
+    let (file, anterior) = find_file_version(user_id, file_id, version, false)?;
+    let blob = find_servable_blob(file_id, &anterior.hash)?;
+
+    info!(
+        "Usuario {} descargando la versión {} del archivo {}",
+        user_id, version, file_id
+    );
+    Ok(FileDownload {
+        id: file.id,
+        mime: anterior.mime,
+        hash: anterior.hash,
+        filename: file.filename,
+        size: blob.size.max(0) as u64,
+        modified_at: anterior.created_at,
+        sha256: blob.sha256,
+    })
+}
+
+/// Convierte una versión anterior en la actual
+///
+/// El contenido actual pasa al historial y la versión promovida se copia
+/// como una versión nueva, así que el historial nunca pierde entradas.
+pub async fn promote_file_version(user_id: &str, file_id: &str, version: i32) -> Result<File> {
+    let (file, anterior) = find_file_version(user_id, file_id, version, true)?;
+    let cuota = check_quota(&file.owner_id, anterior.size.max(0) as u64)?;
+
+    blobs::promote_version(file_id, &file.owner_id, version, cuota).await?;
+
+    info!(
+        "Versión {} del archivo {} promovida a actual",
+        version, file_id
+    );
+    find_accessible_file(user_id, file_id, true)
+}
+
+/// Elimina versiones anteriores de un archivo. La versión actual nunca se
+/// elimina.
+///
+/// # Parámetros
+/// - `keep`: cuántas versiones anteriores (las más recientes) conservar
+/// - `older_than_days`: eliminar las subidas hace más de esa cantidad de días
+///
+/// Hace falta al menos uno de los dos; con ambos se elimina la versión que
+/// cumpla cualquiera de las condiciones.
+///
+/// # Retorna
+/// Cantidad de versiones eliminadas
+pub async fn prune_file_versions(
+    user_id: &str,
+    file_id: &str,
+    keep: Option<usize>,
+    older_than_days: Option<i64>,
+) -> Result<usize> {
+    if keep.is_none() && older_than_days.is_none() {
+        return Err(anyhow!(
+            "Criterio inválido: indica `keep` u `older_than_days`"
+        ));
+    }
+    let limite = match older_than_days {
+        Some(dias) => Some(
+            dias.checked_mul(24 * 3600)
+                .and_then(|segundos| Utc::now().timestamp().checked_sub(segundos))
+                .filter(|_| dias >= 0)
+                .ok_or_else(|| anyhow!("Criterio inválido: `older_than_days` fuera de rango"))?,
+        ),
+        None => None,
+    };
+
+    find_accessible_file(user_id, file_id, true)?;
+    let versiones = init_db_manager()
+        .obtener_versiones_file(file_id)
+        .context("Error al obtener las versiones del archivo")?;
+
+    let mut eliminadas = 0;
+    for (i, anterior) in versiones.iter().enumerate() {
+        let sobra = keep.is_some_and(|keep| i >= keep);
+        let vieja = limite.is_some_and(|limite| anterior.created_at < limite);
+        if sobra || vieja {
+            blobs::release_version(file_id, anterior.version).await?;
+            eliminadas += 1;
+        }
+    }
+
+    info!(
+        "{} versiones anteriores del archivo {} eliminadas",
+        eliminadas, file_id
+    );
+    Ok(eliminadas)
+}
+
+// ============================================================================
+// Papelera
+// ============================================================================
+
+/// Busca un archivo en la papelera del usuario o en la de un equipo donde
+/// puede escribir
+fn find_trashed_file(user_id: &str, file_id: &str) -> Result<File> {
+    if file_id.contains("..") || file_id.contains('/') || file_id.contains('\\') {
+        error!("Intento de path traversal detectado: {}", file_id);
+        return Err(anyhow!("ID de archivo inválido"));
+    }
+
+    match init_db_manager().buscar_file(file_id) {
+        Ok(file) if file.deleted_at.is_some() && file.owner_id == user_id => Ok(file),
+        Ok(file) if file.deleted_at.is_some() => match team_access(user_id, &file.owner_id) {
+            Some(rol) => {
+                check_role(Some(rol), true, "Archivo")?;
+                Ok(file)
+            }
+            None => Err(anyhow!("Archivo no encontrado en la papelera")),
+        },
+        _ => {
+            warn!(
+                "Archivo {} no encontrado en la papelera del usuario {}",
+                file_id, user_id
+            );
+            Err(anyhow!("Archivo no encontrado en la papelera"))
+        }
+    }
+}
+
+/// Dueño de la papelera que se consulta: el equipo `team_id`, donde el
+/// usuario debe ser al menos `member`, o el propio usuario
+fn trash_owner(user_id: &str, team_id: Option<&str>) -> Result<String> {
+    match team_id {
+        Some(team_id) => {
+            find_team_member(user_id, team_id, TEAM_MEMBER)?;
+            Ok(team_id.to_string())
+        }
+        None => Ok(user_id.to_string()),
+    }
+}
+
+/// Lista los archivos en la papelera del usuario o, con `team_id`, en la
+/// del espacio de ese equipo
+pub async fn list_trash(user_id: &str, team_id: Option<&str>) -> Result<Vec<File>> {
+    let owner_id = trash_owner(user_id, team_id)?;
+    init_db_manager()
+        .obtener_papelera_de_usuario(&owner_id)
+        .context("Error al obtener la papelera")
+}
+
+/// Restaura un archivo de la papelera del usuario o de uno de sus equipos
+///
+/// Vuelve a su carpeta original si todavía existe; si no, a la raíz de su
+/// espacio.
+pub async fn restore_file(user_id: &str, file_id: &str) -> Result<File> {
+    let file = find_trashed_file(user_id, file_id)?;
+
+    let db = init_db_manager();
+    let carpeta = file.folder_id.as_deref().filter(|id| {
+        db.buscar_folder(id)
+            .is_ok_and(|folder| folder.owner_id == file.owner_id)
+    });
+
+    db.restaurar_file(file_id, carpeta, Utc::now().timestamp())
+        .context("Error al restaurar el archivo")?;
+
+    info!("Archivo {} restaurado en {:?}", file_id, carpeta);
+    find_accessible_file(user_id, file_id, false)
+}
+
+/// Elimina definitivamente un archivo de la papelera, liberando su
+/// referencia al blob y su tamaño en la cuota
+pub async fn purge_file(user_id: &str, file_id: &str) -> Result<()> {
+    find_trashed_file(user_id, file_id)?;
+    blobs::release_file(file_id).await?;
+
+    info!("Archivo {} eliminado definitivamente", file_id);
+    Ok(())
+}
+
+/// Vacía la papelera del usuario o, con `team_id`, la de ese equipo
+///
+/// # Retorna
+/// Cantidad de archivos eliminados definitivamente
+pub async fn empty_trash(user_id: &str, team_id: Option<&str>) -> Result<usize> {
+    let files = list_trash(user_id, team_id).await?;
+    for file in &files {
+        blobs::release_file(&file.id).await?;
+    }
+
+    info!(
+        "Papelera de {} vaciada por el usuario {} ({} archivos)",
+        team_id.unwrap_or(user_id),
+        user_id,
+        files.len()
+    );
+    Ok(files.len())
+}
+
+/// Elimina definitivamente los archivos que llevan en la papelera más de
+/// `trash_retention_days()` días (con 0 no se purga nada)
+///
+/// # Retorna
+/// Cantidad de archivos eliminados
+pub async fn purge_expired_trash() -> Result<usize> {
+    let dias = trash_retention_days();
+    if dias <= 0 {
+        return Ok(0);
+    }
+
+    let limite = Utc::now().timestamp() - dias * 24 * 3600;
+    let vencidos = init_db_manager()
+        .obtener_papelera_vencida(limite)
+        .context("Error al buscar archivos vencidos en la papelera")?;
+
+    let mut purgados = 0;
+    for file in vencidos {
+        match blobs::release_file(&file.id).await {
+            Ok(_) => purgados += 1,
+            Err(e) => warn!("No se pudo purgar el archivo {}: {}", file.id, e),
+        }
+    }
+
+    if purgados > 0 {
+        info!("{} archivos purgados de la papelera", purgados);
+    }
+    Ok(purgados)
+}
+
+// ============================================================================
+// Descargas en ZIP
+// ============================================================================
+
+/// Máximo de archivos en una misma descarga ZIP
+const MAX_ARCHIVE_FILES: usize = 10_000;
+
+/// Tamaño del buffer entre el generador del ZIP y la respuesta
+const ARCHIVE_BUFFER: usize = 64 * 1024;
+
+/// Ruta libre para `filename` dentro de `prefix`. Si ya está usada se
+/// agrega un sufijo numérico antes de la extensión: `foto (1).jpg`.
+/// La comparación ignora mayúsculas para no pisar archivos al extraer en
+/// sistemas que no las distinguen.
+fn unique_entry_path(usadas: &mut HashSet<String>, prefix: &str, filename: &str) -> String {
+    let (base, extension) = match filename.rsplit_once('.') {
+        Some((base, extension)) if !base.is_empty() => (base, format!(".{}", extension)),
+        _ => (filename, String::new()),
+    };
+
+    let mut ruta = format!("{}{}", prefix, filename);
+    let mut n = 1;
+    while !usadas.insert(ruta.to_lowercase()) {
+        ruta = format!("{}{} ({}){}", prefix, base, n, extension);
+        n += 1;
+    }
+    ruta
+}
+
+/// Prepara la descarga en ZIP de varios archivos o de una carpeta completa
+///
+/// Los archivos se incluyen con su nombre original; los de una carpeta
+/// conservan la estructura de subcarpetas. Los nombres repetidos reciben un
+/// sufijo numérico.
+///
+/// # Validaciones
+/// - Se indica `file_ids` o `folder_id`, pero no ambos
+/// - Todos los archivos existen, pertenecen al usuario y se pueden descargar
+/// - No se superan `MAX_ARCHIVE_FILES` archivos
+///
+/// # Retorna
+/// Nombre sugerido para el ZIP y sus entradas; el contenido se genera con
+/// `open_archive`
+pub async fn prepare_archive(
+    user_id: &str,
+    file_ids: &[String],
+    folder_id: Option<&str>,
+) -> Result<(String, Vec<ArchiveEntry>)> {
+    let db = init_db_manager();
+    let mut usadas = HashSet::new();
+    let mut entradas = Vec::new();
+
+    let nombre = match (file_ids.is_empty(), folder_id) {
+        (false, None) => {
+            if file_ids.len() > MAX_ARCHIVE_FILES {
+                return Err(anyhow!(
+                    "Selección inválida: máximo {} archivos",
+                    MAX_ARCHIVE_FILES
+                ));
+            }
+            let mut vistos = HashSet::new();
+            for file_id in file_ids {
+                if !vistos.insert(file_id.as_str()) {
+                    continue;
+                }
+                let file = file_download(find_accessible_file(user_id, file_id, false)?)?;
+                let path = unique_entry_path(&mut usadas, "", &file.filename);
+                entradas.push(ArchiveEntry { path, file });
+            }
+            format!("privafile-{}.zip", Utc::now().format("%Y%m%d-%H%M%S"))
+        }
+        (true, Some(folder_id)) => {
+            let raiz = find_accessible_folder(user_id, folder_id, false)?
+                .0
+                .remove(0);
+            let owner_id = raiz.owner_id.clone();
+
+            // Recorrido de las subcarpetas acumulando la ruta de cada una
+            let mut pendientes = vec![(raiz.id.clone(), String::new())];
+            while let Some((id, prefix)) = pendientes.pop() {
+                let subcarpetas = db
+                    .obtener_folders_hijos(&owner_id, Some(&id))
+                    .context("Error al obtener las subcarpetas")?;
+                // Las carpetas reservan su nombre antes que los archivos
+                for sub in subcarpetas {
+                    let ruta = unique_entry_path(&mut usadas, &prefix, &sub.name);
+                    pendientes.push((sub.id, format!("{}/", ruta)));
+                }
+
+                let files = db
+                    .obtener_files_de_carpeta(&owner_id, Some(&id))
+                    .context("Error al obtener los archivos de la carpeta")?;
+                if entradas.len() + files.len() > MAX_ARCHIVE_FILES {
+                    return Err(anyhow!(
+                        "Carpeta inválida para ZIP: supera los {} archivos",
+                        MAX_ARCHIVE_FILES
+                    ));
+                }
+                for file in files {
+                    let file = file_download(file)?;
+                    let path = unique_entry_path(&mut usadas, &prefix, &file.filename);
+                    entradas.push(ArchiveEntry { path, file });
+                }
+            }
+            format!("{}.zip", raiz.name)
+        }
+        _ => {
+            return Err(anyhow!("Selección inválida: indicar file_ids o folder_id"));
+        }
+    };
+
+    let ahora = Utc::now().timestamp();
+    for entrada in &entradas {
+        if let Err(e) = db.marcar_acceso_file(&entrada.file.id, ahora) {
+            warn!(
+                "No se pudo registrar el acceso al archivo {}: {}",
+                entrada.file.id, e
+            );
+        }
+    }
+
+    info!(
+        "Usuario {} descargando ZIP con {} archivo(s)",
+        user_id,
+        entradas.len()
+    );
+    Ok((nombre, entradas))
+}
+
+/// Genera el ZIP de `entradas` mientras se lee, sin guardarlo en memoria ni
+/// en disco. Los archivos se guardan sin comprimir y con los campos ZIP64,
+/// así que no hay límites de tamaño ni de cantidad.
+///
+/// Si un archivo falla a mitad de camino el ZIP queda truncado: los
+/// errores ya no se pueden informar con el status de la respuesta.
+pub fn open_archive(entradas: Vec<ArchiveEntry>) -> BlobReader {
+    let (lector, escritor) = tokio::io::duplex(ARCHIVE_BUFFER);
+    tokio::spawn(async move {
+        if let Err(e) = write_archive(entradas, escritor).await {
+            error!(
+                "Error al generar ZIP, la descarga queda incompleta: {:#}",
+                e
+            );
+        }
+    });
+    Box::new(lector)
+}
+
+async fn write_archive<W>(entradas: Vec<ArchiveEntry>, destino: W) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut zip = ZipFileWriter::with_tokio(destino).force_zip64();
+
+    for entrada in entradas {
+        let fecha = DateTime::from_timestamp(entrada.file.modified_at, 0).unwrap_or_default();
+        let entry = ZipEntryBuilder::new(entrada.path.into(), Compression::Stored)
+            .last_modification_date(ZipDateTime::from_chrono(&fecha));
+
+        let mut contenido = open_file_range(&entrada.file, 0, entrada.file.size).await?;
+        let mut escritor = zip.write_entry_stream(entry).await?.compat_write();
+        tokio::io::copy(&mut contenido, &mut escritor)
+            .await
+            .with_context(|| format!("Error al agregar el archivo {} al ZIP", entrada.file.id))?;
+        escritor.into_inner().close().await?;
+    }
+
+    let mut destino = zip.close().await?.into_inner();
+    destino.shutdown().await?;
+    Ok(())
+}
+
+// ============================================================================
+// Enlaces públicos
+// ============================================================================
+
+/// Bytes aleatorios del token de un enlace (256 bits)
+const SHARE_TOKEN_BYTES: usize = 32;
+
+/// Contraseñas incorrectas admitidas por enlace en cada ventana
+const LINK_PASSWORD_MAX_FAILURES: u32 = 5;
+
+/// Duración de la ventana de intentos fallidos (15 minutos)
+const LINK_PASSWORD_WINDOW_SECONDS: i64 = 15 * 60;
+
+/// Tipo de enlace con contraseña, para llevar sus intentos por separado
+#[derive(Clone, Copy)]
+enum LinkKind {
+    Share,
+    Upload,
+}
+
+/// Contraseñas incorrectas por enlace: cantidad y comienzo de la ventana.
+/// Solo se registran enlaces existentes con contraseña.
+static FALLOS_ENLACE: Lazy<StdMutex<HashMap<String, (u32, i64)>>> = Lazy::new(Default::default);
+
+/// Clave de un enlace en `FALLOS_ENLACE`; los tokens de descarga y de subida
+/// se generan igual, así que se separan por tipo
+fn password_attempts_key(kind: LinkKind, token: &str) -> String {
+    match kind {
+        LinkKind::Share => format!("share:{}", token),
+        LinkKind::Upload => format!("upload:{}", token),
+    }
+}
+
+/// Rechaza el intento si el enlace agotó sus contraseñas incorrectas, antes
+/// de gastar un hash Argon2 en verificarla
+fn check_password_attempts(kind: LinkKind, token: &str, ahora: i64) -> Result<()> {
+    let clave = password_attempts_key(kind, token);
+    let mut fallos = FALLOS_ENLACE.lock().unwrap_or_else(|e| e.into_inner());
+    match fallos.get(&clave) {
+        Some(&(_, desde)) if ahora - desde >= LINK_PASSWORD_WINDOW_SECONDS => {
+            fallos.remove(&clave);
+            Ok(())
+        }
+        Some(&(cantidad, desde)) if cantidad >= LINK_PASSWORD_MAX_FAILURES => Err(anyhow!(
+            "Demasiados intentos de contraseña: reintenta en {} segundos",
+            LINK_PASSWORD_WINDOW_SECONDS - (ahora - desde)
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Suma una contraseña incorrecta a la ventana del enlace
+fn record_password_failure(kind: LinkKind, token: &str, ahora: i64) {
+    let mut fallos = FALLOS_ENLACE.lock().unwrap_or_else(|e| e.into_inner());
+    fallos
+        .entry(password_attempts_key(kind, token))
+        .or_insert((0, ahora))
+        .0 += 1;
+}
+
+/// Crea un enlace público para descargar un archivo sin cuenta
+///
+/// # Parámetros
+/// - `expires_in_hours`: horas de validez; sin valor el enlace no expira
+/// - `max_downloads`: descargas permitidas; sin valor no hay límite
+/// - `password`: contraseña que se pedirá al descargar, guardada con Argon2
+///
+/// # Validaciones
+/// - El archivo debe estar en el espacio del usuario o de un equipo donde
+///   puede escribir, y no estar en la papelera
+/// - La expiración y el límite de descargas deben ser positivos
+/// - La contraseña debe tener al menos 8 caracteres
+pub async fn create_share_link(
+    user_id: &str,
+    file_id: &str,
+    expires_in_hours: Option<i64>,
+    max_downloads: Option<i32>,
+    password: Option<&str>,
+) -> Result<ShareLink> {
+    find_drive_file(user_id, file_id)?;
+
+    let ahora = Utc::now().timestamp();
+    let expires_at = match expires_in_hours {
+        Some(horas) => Some(
+            horas
+                .checked_mul(3600)
+                .and_then(|segundos| ahora.checked_add(segundos))
+                .filter(|_| horas > 0)
+                .ok_or_else(|| anyhow!("Expiración inválida: debe ser de al menos una hora"))?,
+        ),
+        None => None,
+    };
+    if max_downloads.is_some_and(|max| max <= 0) {
+        return Err(anyhow!(
+            "Límite de descargas inválido: debe ser mayor que cero"
+        ));
+    }
+    let password_hash = match password {
+        Some(password) if password.len() < 8 => {
+            return Err(anyhow!(
+                "Contraseña inválida: debe tener al menos 8 caracteres"
+            ));
+        }
+        Some(password) => Some(hash_password(password)?),
+        None => None,
+    };
+
+    let mut bytes = [0u8; SHARE_TOKEN_BYTES];
+    getrandom::fill(&mut bytes).map_err(|e| anyhow!("Error al generar el token: {}", e))?;
+    let token = URL_SAFE_NO_PAD.encode(bytes);
+
+    let db = init_db_manager();
+    db.insertar_share_link(&NuevoShareLink {
+        token: &token,
+        file_id,
+        owner_id: user_id,
+        password_hash: password_hash.as_deref(),
+        expires_at,
+        max_downloads,
+        created_at: ahora,
+    })
+    .context("Error al guardar el enlace")?;
+
+    info!("Enlace público creado para el archivo {}", file_id);
+    db.buscar_share_link(&token)
+        .context("Error al leer el enlace creado")
+}
+
+/// Lista los enlaces del usuario, opcionalmente solo los de un archivo
+pub async fn list_share_links(user_id: &str, file_id: Option<&str>) -> Result<Vec<ShareLink>> {
+    init_db_manager()
+        .obtener_share_links_de_usuario(user_id, file_id)
+        .context("Error al listar los enlaces")
+}
+
+/// Revoca un enlace del usuario; deja de funcionar de inmediato
+pub async fn revoke_share_link(user_id: &str, token: &str) -> Result<()> {
+    let borrados = init_db_manager()
+        .borrar_share_link(token, user_id)
+        .context("Error al revocar el enlace")?;
+    if borrados == 0 {
+        return Err(anyhow!("Enlace no encontrado"));
+    }
+
+    info!("Enlace revocado por el usuario {}", user_id);
+    Ok(())
+}
+
+/// Valida un enlace público y devuelve los metadatos de descarga de su
+/// archivo, sin consumir descargas (ver `count_share_download`)
+///
+/// # Validaciones
+/// - El enlace debe existir, no haber expirado ni alcanzado su límite
+/// - Si tiene contraseña, `password` debe coincidir; tras
+///   `LINK_PASSWORD_MAX_FAILURES` contraseñas incorrectas el enlace deja de
+///   aceptar intentos hasta que pasa `LINK_PASSWORD_WINDOW_SECONDS`
+/// - El archivo no debe estar en la papelera y quien creó el enlace debe
+///   seguir pudiendo escribir en su espacio
+pub async fn open_share_link(token: &str, password: Option<&str>) -> Result<FileDownload> {
+    let db = init_db_manager();
+    let link = db
+        .buscar_share_link(token)
+        .ok()
+        .filter(|link| account_enabled(&link.owner_id))
+        .ok_or_else(|| anyhow!("Enlace no encontrado"))?;
+
+    let ahora = Utc::now().timestamp();
+    if link.expires_at.is_some_and(|expira| expira <= ahora) {
+        return Err(anyhow!("El enlace ha expirado"));
+    }
+    if link
+        .max_downloads
+        .is_some_and(|max| link.download_count >= max)
+    {
+        return Err(anyhow!("El enlace alcanzó su límite de descargas"));
+    }
+
+    if let Some(hash) = &link.password_hash {
+        let Some(password) = password else {
+            return Err(anyhow!("El enlace requiere contraseña"));
+        };
+        check_password_attempts(LinkKind::Share, token, ahora)?;
+        if !verify_password(hash, password)? {
+            record_password_failure(LinkKind::Share, token, ahora);
+            warn!(
+                "Contraseña incorrecta para un enlace del archivo {}",
+                link.file_id
+            );
+            return Err(anyhow!("Contraseña del enlace incorrecta"));
+        }
+    }
+
+    // Quien creó el enlace debe seguir pudiendo escribir en el espacio del
+    // archivo: el suyo o el de un equipo del que sigue siendo miembro
+    let file = find_drive_file(&link.owner_id, &link.file_id)
+        .map_err(|_| anyhow!("Archivo no encontrado"))?;
+    file_download(file)
+}
+
+/// Consume una descarga de un enlace ya validado con `open_share_link` y
+/// registra el acceso al archivo
+///
+/// # Errores
+/// - Si otra descarga agotó el límite del enlace mientras tanto
+pub async fn count_share_download(token: &str, file_id: &str) -> Result<()> {
+    let db = init_db_manager();
+    let ahora = Utc::now().timestamp();
+    if !db
+        .consumir_descarga_share_link(token, ahora)
+        .context("Error al registrar la descarga del enlace")?
+    {
+        return Err(anyhow!("El enlace alcanzó su límite de descargas"));
+    }
+    if let Err(e) = db.marcar_acceso_file(file_id, ahora) {
+        warn!(
+            "No se pudo registrar el acceso al archivo {}: {}",
+            file_id, e
+        );
+    }
+
+    info!("Descarga del archivo {} por enlace público", file_id);
+    Ok(())
+}
+
+// ============================================================================
+// Enlaces de subida
+// ============================================================================
+
+/// Tipos MIME distintos que puede aceptar un enlace de subida
+const UPLOAD_LINK_MAX_MIME_TYPES: usize = 32;
+
+/// Normaliza un tipo MIME aceptado por un enlace: `tipo/subtipo` o `tipo/*`
+/// en minúsculas
+fn normalize_mime_pattern(patron: &str) -> Result<String> {
+    let patron = patron.trim().to_lowercase();
+    let valido = |parte: &str| {
+        !parte.is_empty()
+            && parte.len() <= 64
+            && parte
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$&^_.+-".contains(c))
+    };
+    match patron.split_once('/') {
+        Some((tipo, subtipo)) if valido(tipo) && (subtipo == "*" || valido(subtipo)) => Ok(patron),
+        _ => Err(anyhow!("Tipo MIME inválido: '{}'", patron)),
+    }
+}
+
+/// Tipos MIME aceptados por un enlace; vacío si acepta cualquiera
+pub fn upload_link_mime_types(link: &UploadLink) -> Vec<String> {
+    link.allowed_mime_types
+        .as_deref()
+        .map(|tipos| tipos.split(',').map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Indica si el enlace acepta archivos de tipo `mime`. Los parámetros del
+/// tipo (`; charset=...`) no cuentan.
+fn upload_link_accepts(link: &UploadLink, mime: &str) -> bool {
+    let mime = mime
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    let tipos = upload_link_mime_types(link);
+    tipos.is_empty()
+        || tipos.iter().any(|patron| match patron.strip_suffix("/*") {
+            Some(tipo) => mime.split_once('/').is_some_and(|(t, _)| t == tipo),
+            None => *patron == mime,
+        })
+}
+
+/// Tamaño máximo de cada archivo subido por el enlace
+pub fn upload_link_max_size(link: &UploadLink) -> u64 {
+    match link.max_file_size {
+        Some(max) => (max.max(0) as u64).min(max_upload_size()),
+        None => max_upload_size(),
+    }
+}
+
+/// Valida el nombre opcional de quien sube: hasta 100 caracteres sin
+/// caracteres de control. Vacío cuenta como sin nombre.
+fn normalize_uploader_name(name: Option<&str>) -> Result<Option<String>> {
+    let Some(name) = name.map(str::trim).filter(|name| !name.is_empty()) else {
+        return Ok(None);
+    };
+    if name.chars().count() > 100 || name.chars().any(char::is_control) {
+        return Err(anyhow!("Nombre inválido: hasta 100 caracteres"));
+    }
+    Ok(Some(name.to_string()))
+}
+
+/// Crea un enlace para que personas sin cuenta suban archivos a una carpeta
+///
+/// # Parámetros
+/// - `expires_in_hours`: horas de validez; sin valor el enlace no expira
+/// - `max_files`: archivos que se pueden subir; sin valor no hay límite
+/// - `max_file_size`: bytes por archivo; sin valor rige `max_upload_size()`
+/// - `allowed_mime_types`: tipos aceptados (`image/png` o `image/*`); vacío
+///   acepta cualquiera
+/// - `password`: contraseña que se pedirá al subir, guardada con Argon2
+///
+/// # Validaciones
+/// - El usuario debe poder escribir en la carpeta (propia o de un equipo)
+/// - La expiración y los límites deben ser positivos
+/// - La contraseña debe tener al menos 8 caracteres
+pub async fn create_upload_link(
+    user_id: &str,
+    folder_id: &str,
+    expires_in_hours: Option<i64>,
+    max_files: Option<i32>,
+    max_file_size: Option<i64>,
+    allowed_mime_types: &[String],
+    password: Option<&str>,
+) -> Result<UploadLink> {
+    find_drive_folder(user_id, folder_id)?;
+
+    let ahora = Utc::now().timestamp();
+    let expires_at = match expires_in_hours {
+        Some(horas) => Some(
+            horas
+                .checked_mul(3600)
+                .and_then(|segundos| ahora.checked_add(segundos))
+                .filter(|_| horas > 0)
+                .ok_or_else(|| anyhow!("Expiración inválida: debe ser de al menos una hora"))?,
+        ),
+        None => None,
+    };
+    if max_files.is_some_and(|max| max <= 0) {
+        return Err(anyhow!(
+            "Límite de archivos inválido: debe ser mayor que cero"
+        ));
+    }
+    if max_file_size.is_some_and(|max| max <= 0 || max as u64 > max_upload_size()) {
+        return Err(anyhow!(
+            "Tamaño máximo inválido: debe estar entre 1 y {} bytes",
+            max_upload_size()
+        ));
+    }
+    if allowed_mime_types.len() > UPLOAD_LINK_MAX_MIME_TYPES {
+        return Err(anyhow!(
+            "Tipos MIME inválidos: como máximo {}",
+            UPLOAD_LINK_MAX_MIME_TYPES
+        ));
+    }
+    let mut tipos = Vec::new();
+    for patron in allowed_mime_types {
+        let patron = normalize_mime_pattern(patron)?;
+        if !tipos.contains(&patron) {
+            tipos.push(patron);
+        }
+    }
+    let tipos = (!tipos.is_empty()).then(|| tipos.join(","));
+    let password_hash = match password {
+        Some(password) if password.len() < 8 => {
+            return Err(anyhow!(
+                "Contraseña inválida: debe tener al menos 8 caracteres"
+            ));
+        }
+        Some(password) => Some(hash_password(password)?),
+        None => None,
+    };
+
+    let mut bytes = [0u8; SHARE_TOKEN_BYTES];
+    getrandom::fill(&mut bytes).map_err(|e| anyhow!("Error al generar el token: {}", e))?;
+    let token = URL_SAFE_NO_PAD.encode(bytes);
+
+    let db = init_db_manager();
+    db.insertar_upload_link(&NuevoUploadLink {
+        token: &token,
+        folder_id,
+        owner_id: user_id,
+        password_hash: password_hash.as_deref(),
+        expires_at,
+        max_files,
+        max_file_size,
+        allowed_mime_types: tipos.as_deref(),
+        created_at: ahora,
+    })
+    .context("Error al guardar el enlace de subida")?;
+
+    info!("Enlace de subida creado para la carpeta {}", folder_id);
+    db.buscar_upload_link(&token)
+        .context("Error al leer el enlace creado")
+}
+
+/// Lista los enlaces de subida del usuario, opcionalmente solo los de una
+/// carpeta
+pub async fn list_upload_links(user_id: &str, folder_id: Option<&str>) -> Result<Vec<UploadLink>> {
+    init_db_manager()
+        .obtener_upload_links_de_usuario(user_id, folder_id)
+        .context("Error al listar los enlaces de subida")
+}
+
+/// Revoca un enlace de subida del usuario; deja de aceptar archivos de
+/// inmediato. Los archivos ya recibidos se quedan en la carpeta.
+pub async fn revoke_upload_link(user_id: &str, token: &str) -> Result<()> {
+    let borrados = init_db_manager()
+        .borrar_upload_link(token, user_id)
+        .context("Error al revocar el enlace de subida")?;
+    if borrados == 0 {
+        return Err(anyhow!("Enlace no encontrado"));
+    }
+
+    info!("Enlace de subida revocado por el usuario {}", user_id);
+    Ok(())
+}
+
+/// Archivos recibidos por un enlace del usuario y quién los subió
+pub async fn list_upload_link_files(user_id: &str, token: &str) -> Result<Vec<UploadLinkEntry>> {
+    let db = init_db_manager();
+    match db.buscar_upload_link(token) {
+        Ok(link) if link.owner_id == user_id => {}
+        _ => return Err(anyhow!("Enlace no encontrado")),
+    }
+
+    Ok(db
+        .obtener_files_de_upload_link(token)
+        .context("Error al listar los archivos del enlace")?
+        .into_iter()
+        .map(|(upload, file)| UploadLinkEntry { upload, file })
+        .collect())
+}
+
+/// Valida un enlace de subida para quien sube sin cuenta
+///
+/// # Validaciones
+/// - El enlace debe existir, no haber expirado ni alcanzado su límite
+/// - Si tiene contraseña, `password` debe coincidir; los intentos fallidos
+///   se limitan igual que en `open_share_link`
+pub async fn open_upload_link(token: &str, password: Option<&str>) -> Result<UploadLink> {
+    let link = init_db_manager()
+        .buscar_upload_link(token)
+        .ok()
+        .filter(|link| account_enabled(&link.owner_id))
+        .ok_or_else(|| anyhow!("Enlace no encontrado"))?;
+
+    let ahora = Utc::now().timestamp();
+    if link.expires_at.is_some_and(|expira| expira <= ahora) {
+        return Err(anyhow!("El enlace ha expirado"));
+    }
+    if link.max_files.is_some_and(|max| link.upload_count >= max) {
+        return Err(anyhow!("El enlace alcanzó su límite de archivos"));
+    }
+
+    if let Some(hash) = &link.password_hash {
+        let Some(password) = password else {
+            return Err(anyhow!("El enlace requiere contraseña"));
+        };
+        check_password_attempts(LinkKind::Upload, token, ahora)?;
+        if !verify_password(hash, password)? {
+            record_password_failure(LinkKind::Upload, token, ahora);
+            warn!(
+                "Contraseña incorrecta para un enlace de subida de la carpeta {}",
+                link.folder_id
+            );
+            return Err(anyhow!("Contraseña del enlace incorrecta"));
+        }
+    }
+
+    Ok(link)
+}
+
+/// Recibe un archivo por un enlace de subida, sin cuenta
+///
+/// El archivo se guarda en la carpeta del enlace como si lo hubiera subido
+/// su dueño y ocupa la cuota de ese espacio. Quien sube no recibe el ID del
+/// archivo ni puede ver nada de la carpeta.
+///
+/// # Validaciones
+/// - Las de `open_upload_link`
+/// - El tipo debe estar entre los aceptados por el enlace
+/// - El archivo no debe superar el tamaño máximo del enlace
+/// - El dueño del enlace debe poder seguir escribiendo en la carpeta
+///
+/// # Errores
+/// - Si el stream está vacío, excede el tamaño o la cuota, se descarta el
+///   temporal y la subida no cuenta para el límite del enlace
+pub async fn upload_via_link<R>(
+    token: &str,
+    password: Option<&str>,
+    mime: &str,
+    filename: Option<&str>,
+    uploader_name: Option<&str>,
+    reader: &mut R,
+) -> Result<String>
+where
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let link = open_upload_link(token, password).await?;
+    if !upload_link_accepts(&link, mime) {
+        return Err(anyhow!(
+            "Tipo de archivo no permitido por el enlace: '{}'",
+            mime
+        ));
+    }
+    let uploader_name = normalize_uploader_name(uploader_name)?;
+
+    // Si el dueño perdió acceso a la carpeta (p. ej. dejó el equipo) el
+    // enlace deja de servir
+    let folder = find_drive_folder(&link.owner_id, &link.folder_id)
+        .map_err(|_| anyhow!("Enlace no encontrado"))?;
+    check_quota(&folder.owner_id, 1)?;
+
+    let file_id = Uuid::new_v4().to_string();
+    let filename = normalize_filename(filename, &file_id);
+    let temp_path = staging_path().join(format!("{}.tmp", file_id));
+    let (hash, sha256, size) =
+        stage_stream(&temp_path, reader, upload_link_max_size(&link)).await?;
+
+    // Se reserva el lugar al final para que otra subida en paralelo no pase
+    // el límite de archivos
+    let db = init_db_manager();
+    let ahora = Utc::now().timestamp();
+    if !db
+        .consumir_subida_upload_link(token, ahora)
+        .context("Error al registrar la subida del enlace")?
+    {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(anyhow!("El enlace alcanzó su límite de archivos"));
+    }
+
+    let nuevo_file = NuevoFile {
+        id: &file_id,
+        mime,
+        hash: &hash,
+        owner_id: &folder.owner_id,
+        filename: &filename,
+        size: size as i64,
+        created_at: ahora,
+        updated_at: ahora,
+        folder_id: Some(&folder.id),
+        version_created_at: ahora,
+    };
+    if let Err(e) = register_staged_file(&nuevo_file, &sha256, &temp_path).await {
+        if let Err(e) = db.devolver_subida_upload_link(token) {
+            warn!("No se pudo devolver la subida del enlace: {}", e);
+        }
+        return Err(e);
+    }
+
+    if let Err(e) = db.insertar_upload_link_file(&UploadLinkFile {
+        file_id: file_id.clone(),
+        token: token.to_string(),
+        uploader_name: uploader_name.clone(),
+        created_at: ahora,
+    }) {
+        warn!(
+            "No se pudo registrar quién subió el archivo {}: {}",
+            file_id, e
+        );
+    }
+
+    info!(
+        "Archivo {} recibido por enlace de subida en la carpeta {} (de: {:?})",
+        file_id, folder.id, uploader_name
+    );
+    Ok(file_id)
+}
+
+// ============================================================================
+// Permisos compartidos
+// ============================================================================
+
+/// Rol de solo lectura: listar, ver metadatos y descargar
+pub const ROLE_VIEWER: &str = "viewer";
+/// Rol de lectura y escritura: además renombrar y enviar a la papelera
+pub const ROLE_EDITOR: &str = "editor";
+
+/// La carpeta `folder_id` seguida de sus ancestras hasta la raíz
+fn folder_chain(folder_id: &str) -> Result<Vec<Folder>> {
+    let db = init_db_manager();
+    let mut cadena = Vec::new();
+    let mut actual = Some(folder_id.to_string());
+    while let Some(id) = actual {
+        let folder = db
+            .buscar_folder(&id)
+            .map_err(|_| anyhow!("Carpeta no encontrada"))?;
+        actual = folder.parent_id.clone();
+        cadena.push(folder);
+    }
+    Ok(cadena)
+}
+
+/// Rol con el que el usuario accede a un archivo o carpeta ajenos: el de un
+/// permiso sobre el propio archivo o sobre cualquiera de las carpetas que lo
+/// contienen. Si hay varios, `editor` prevalece.
+///
+/// Los permisos se consultan en cada petición, así que una revocación se
+/// aplica de inmediato.
+fn shared_role(
+    user_id: &str,
+    file_id: Option<&str>,
+    carpetas: &[Folder],
+) -> Result<Option<String>> {
+    let ids: Vec<String> = carpetas.iter().map(|f| f.id.clone()).collect();
+    let permisos = init_db_manager()
+        .buscar_permisos_aplicables(user_id, file_id, &ids)
+        .context("Error al consultar los permisos")?;
+    Ok(permisos
+        .into_iter()
+        .map(|grant| grant.role)
+        .max_by_key(|rol| rol == ROLE_EDITOR))
+}
+
+/// Comprueba que un rol compartido alcance para la operación
+fn check_role(rol: Option<String>, editor: bool, tipo: &str) -> Result<()> {
+    match rol.as_deref() {
+        None => Err(anyhow!("{} no encontrado", tipo)),
+        Some(ROLE_VIEWER) if editor => Err(anyhow!(
+            "Permiso insuficiente: solo tienes acceso de lectura"
+        )),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Busca un archivo activo del usuario o compartido con él
+///
+/// Con `editor` se exige el rol `editor` a los usuarios que no son dueños.
+/// Sin ningún permiso el archivo se reporta como no encontrado, para no
+/// revelar que existe.
+fn find_accessible_file(user_id: &str, file_id: &str, editor: bool) -> Result<File> {
+    if file_id.contains("..") || file_id.contains('/') || file_id.contains('\\') {
+        error!("Intento de path traversal detectado: {}", file_id);
+        return Err(anyhow!("ID de archivo inválido"));
+    }
+
+    let file = match init_db_manager().buscar_file(file_id) {
+        Ok(file) if file.deleted_at.is_none() => file,
+        _ => return Err(anyhow!("Archivo no encontrado")),
+    };
+    if file.owner_id == user_id {
+        return Ok(file);
+    }
+    if let Some(rol) = team_access(user_id, &file.owner_id) {
+        check_role(Some(rol), editor, "Archivo")?;
+        return Ok(file);
+    }
+
+    let carpetas = match &file.folder_id {
+        Some(folder_id) => folder_chain(folder_id)?,
+        None => Vec::new(),
+    };
+    let rol = shared_role(user_id, Some(file_id), &carpetas)?;
+    if rol.is_none() {
+        warn!(
+            "Archivo {} no encontrado o no accesible para el usuario {}",
+            file_id, user_id
+        );
+    }
+    check_role(rol, editor, "Archivo")?;
+    Ok(file)
+}
+
+/// Busca una carpeta del usuario o compartida con él (directamente o a
+/// través de una ancestra)
+///
+/// # Retorna
+/// La carpeta seguida de sus ancestras hasta la raíz, y el rol compartido
+/// (`None` si el usuario es el dueño o miembro del equipo dueño)
+fn find_accessible_folder(
+    user_id: &str,
+    folder_id: &str,
+    editor: bool,
+) -> Result<(Vec<Folder>, Option<String>)> {
+    if folder_id.contains("..") || folder_id.contains('/') || folder_id.contains('\\') {
+        error!("Intento de path traversal detectado: {}", folder_id);
+        return Err(anyhow!("ID de carpeta inválido"));
+    }
+
+    let cadena = folder_chain(folder_id)?;
+    if cadena[0].owner_id == user_id {
+        return Ok((cadena, None));
+    }
+    if let Some(rol) = team_access(user_id, &cadena[0].owner_id) {
+        check_role(Some(rol), editor, "Carpeta")?;
+        return Ok((cadena, None));
+    }
+
+    let rol = shared_role(user_id, None, &cadena)?;
+    if rol.is_none() {
+        warn!(
+            "Carpeta {} no encontrada o no accesible para el usuario {}",
+            folder_id, user_id
+        );
+    }
+    check_role(rol.clone(), editor, "Carpeta")?;
+    Ok((cadena, rol))
+}
+
+/// Comparte un archivo o una carpeta del usuario con otro usuario
+///
+/// Si el otro usuario ya tenía un permiso sobre el mismo elemento se
+/// reemplaza su rol. Compartir una carpeta da acceso a todo su contenido,
+/// incluido el que se agregue después.
+///
+/// # Validaciones
+/// - Exactamente uno de `file_id` o `folder_id`
+/// - El archivo o la carpeta deben estar en el espacio del usuario o de un
+///   equipo donde puede escribir
+/// - El rol debe ser `viewer` o `editor`
+/// - El destinatario debe existir y no ser el propio usuario
+pub async fn create_grant(
+    user_id: &str,
+    grantee_id: &str,
+    role: &str,
+    file_id: Option<&str>,
+    folder_id: Option<&str>,
+) -> Result<Grant> {
+    if role != ROLE_VIEWER && role != ROLE_EDITOR {
+        return Err(anyhow!(
+            "Rol inválido: debe ser '{}' o '{}'",
+            ROLE_VIEWER,
+            ROLE_EDITOR
+        ));
+    }
+    match (file_id, folder_id) {
+        (Some(file_id), None) => {
+            find_drive_file(user_id, file_id)?;
+        }
+        (None, Some(folder_id)) => {
+            find_drive_folder(user_id, folder_id)?;
+        }
+        _ => {
+            return Err(anyhow!(
+                "Destino inválido: indica un archivo o una carpeta, no ambos"
+            ));
+        }
+    }
+    if grantee_id == user_id {
+        return Err(anyhow!(
+            "Usuario inválido: no puedes compartir contigo mismo"
+        ));
+    }
+
+    let db = init_db_manager();
+    if db.buscar_usuario(grantee_id).is_err() {
+        return Err(anyhow!("Usuario {} no encontrado", grantee_id));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let grant = db
+        .otorgar_permiso(&NuevoGrant {
+            id: &id,
+            owner_id: user_id,
+            grantee_id,
+            file_id,
+            folder_id,
+            role,
+            created_at: Utc::now().timestamp(),
+        })
+        .context("Error al guardar el permiso")?;
+
+    info!(
+        "Usuario {} compartió {:?}/{:?} con {} como {}",
+        user_id, file_id, folder_id, grantee_id, role
+    );
+    Ok(grant)
+}
+
+/// Lista los permisos dados por el usuario, opcionalmente solo los de un
+/// archivo o una carpeta
+pub async fn list_grants(
+    user_id: &str,
+    file_id: Option<&str>,
+    folder_id: Option<&str>,
+) -> Result<Vec<Grant>> {
+    init_db_manager()
+        .obtener_permisos_otorgados(user_id, file_id, folder_id)
+        .context("Error al listar los permisos")
+}
+
+/// Revoca un permiso. Lo puede hacer el dueño o el propio destinatario
+/// (para dejar de ver lo compartido); el acceso se corta de inmediato.
+pub async fn revoke_grant(user_id: &str, grant_id: &str) -> Result<()> {
+    let db = init_db_manager();
+    match db.buscar_permiso(grant_id) {
+        Ok(grant) if grant.owner_id == user_id || grant.grantee_id == user_id => {}
+        _ => return Err(anyhow!("Permiso no encontrado")),
+    }
+
+    db.borrar_permiso(grant_id)
+        .context("Error al revocar el permiso")?;
+
+    info!("Permiso {} revocado por el usuario {}", grant_id, user_id);
+    Ok(())
+}
+
+/// Archivos y carpetas compartidos con el usuario
+///
+/// Se omiten los archivos que su dueño envió a la papelera; vuelven a
+/// aparecer si los restaura.
+pub async fn shared_with_me(user_id: &str) -> Result<Vec<SharedEntry>> {
+    let db = init_db_manager();
+    let permisos = db
+        .obtener_permisos_recibidos(user_id)
+        .context("Error al listar los permisos recibidos")?;
+
+    let mut entradas = Vec::with_capacity(permisos.len());
+    for grant in permisos {
+        if let Some(file_id) = &grant.file_id {
+            match db.buscar_file(file_id) {
+                Ok(file) if file.deleted_at.is_none() => entradas.push(SharedEntry {
+                    grant,
+                    file: Some(file),
+                    folder: None,
+                }),
+                _ => {}
+            }
+        } else if let Some(folder_id) = &grant.folder_id
+            && let Ok(folder) = db.buscar_folder(folder_id)
+        {
+            entradas.push(SharedEntry {
+                grant,
+                file: None,
+                folder: Some(folder),
+            });
+        }
+    }
+    Ok(entradas)
+}
+
+// ============================================================================
+// Equipos
+// ============================================================================
+
+/// Dueño del equipo: todo lo de `TEAM_ADMIN` y además gestionar a otros
+/// dueños y administradores y eliminar el equipo
+pub const TEAM_OWNER: &str = "owner";
+/// Administrador: todo lo de `TEAM_MEMBER` y además gestionar miembros y
+/// lectores
+pub const TEAM_ADMIN: &str = "admin";
+/// Miembro: leer y escribir en el espacio del equipo
+pub const TEAM_MEMBER: &str = "member";
+/// Lector: solo listar y descargar
+pub const TEAM_VIEWER: &str = "viewer";
+
+/// Jerarquía de los roles de equipo; `None` si el rol no existe
+fn team_rank(rol: &str) -> Option<u8> {
+    match rol {
+        TEAM_VIEWER => Some(0),
+        TEAM_MEMBER => Some(1),
+        TEAM_ADMIN => Some(2),
+        TEAM_OWNER => Some(3),
+        _ => None,
+    }
+}
+
+/// Acceso (`viewer` o `editor`) que da al usuario ser miembro del equipo
+/// `owner_id`. `None` si `owner_id` no es un equipo del usuario.
+fn team_access(user_id: &str, owner_id: &str) -> Option<String> {
+    let miembro = init_db_manager()
+        .buscar_miembro_team(owner_id, user_id)
+        .ok()?;
+    let rol = if miembro.role == TEAM_VIEWER {
+        ROLE_VIEWER
+    } else {
+        ROLE_EDITOR
+    };
+    Some(rol.to_string())
+}
+
+/// Busca la pertenencia del usuario al equipo y exige al menos el rol
+/// `minimo`. A quien no es miembro el equipo se le reporta como no
+/// encontrado.
+fn find_team_member(user_id: &str, team_id: &str, minimo: &str) -> Result<TeamMember> {
+    let miembro = init_db_manager()
+        .buscar_miembro_team(team_id, user_id)
+        .map_err(|_| {
+            warn!(
+                "Equipo {} no encontrado o el usuario {} no es miembro",
+                team_id, user_id
+            );
+            anyhow!("Equipo no encontrado")
+        })?;
+    if team_rank(&miembro.role) < team_rank(minimo) {
+        return Err(anyhow!(
+            "Permiso insuficiente: se requiere el rol '{}' en el equipo",
+            minimo
+        ));
+    }
+    Ok(miembro)
+}
+
+/// Dueño del espacio donde el usuario quiere escribir: el de `folder_id`, el
+/// equipo `team_id` (en su raíz) o el propio usuario
+///
+/// # Validaciones
+/// - La carpeta es del usuario o de un equipo donde tiene rol de escritura
+/// - Con `team_id` el usuario debe ser al menos `member` del equipo y, si
+///   además hay carpeta, la carpeta debe ser de ese equipo
+fn drive_owner(user_id: &str, team_id: Option<&str>, folder_id: Option<&str>) -> Result<String> {
+    match (folder_id, team_id) {
+        (Some(folder_id), team_id) => {
+            let folder = find_drive_folder(user_id, folder_id)?;
+            if team_id.is_some_and(|team_id| team_id != folder.owner_id) {
+                return Err(anyhow!(
+                    "Destino inválido: la carpeta no pertenece al equipo"
+                ));
+            }
+            Ok(folder.owner_id)
+        }
+        (None, Some(team_id)) => {
+            find_team_member(user_id, team_id, TEAM_MEMBER)?;
+            Ok(team_id.to_string())
+        }
+        (None, None) => Ok(user_id.to_string()),
+    }
+}
+
+/// Busca una carpeta del usuario o del espacio de uno de sus equipos donde
+/// puede escribir. Los permisos compartidos no alcanzan para crear,
+/// renombrar ni borrar carpetas.
+fn find_drive_folder(user_id: &str, folder_id: &str) -> Result<Folder> {
+    if folder_id.contains("..") || folder_id.contains('/') || folder_id.contains('\\') {
+        error!("Intento de path traversal detectado: {}", folder_id);
+        return Err(anyhow!("ID de carpeta inválido"));
+    }
+
+    let folder = init_db_manager()
+        .buscar_folder(folder_id)
+        .map_err(|_| anyhow!("Carpeta no encontrada"))?;
+    if folder.owner_id == user_id {
+        return Ok(folder);
+    }
+    match team_access(user_id, &folder.owner_id) {
+        Some(rol) => {
+            check_role(Some(rol), true, "Carpeta")?;
+            Ok(folder)
+        }
+        None => {
+            warn!(
+                "Carpeta {} no encontrada o no pertenece al usuario {}",
+                folder_id, user_id
+            );
+            Err(anyhow!("Carpeta no encontrada"))
+        }
+    }
+}
+
+/// Valida el destino al mover algo del espacio `owner_id`: una carpeta del
+/// mismo espacio donde el usuario puede escribir, o su raíz con `None`. Al
+/// moverlos, los archivos y carpetas no cambian de espacio.
+fn check_move_target(user_id: &str, owner_id: &str, folder_id: Option<&str>) -> Result<()> {
+    if let Some(folder_id) = folder_id
+        && find_drive_folder(user_id, folder_id)?.owner_id != owner_id
+    {
+        return Err(anyhow!(
+            "Destino inválido: la carpeta pertenece a otro espacio"
+        ));
+    }
+    Ok(())
+}
+
+/// Valida el nombre de un equipo: 1-100 caracteres sin caracteres de control
+fn validate_team_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 100 || name.chars().any(char::is_control) {
+        return Err(anyhow!("Nombre de equipo inválido"));
+    }
+    Ok(name)
+}
+
+/// Datos del equipo vistos por uno de sus miembros
+fn team_membership(miembro: TeamMember) -> Result<TeamMembership> {
+    let team = init_db_manager()
+        .buscar_team(&miembro.team_id)
+        .map_err(|_| anyhow!("Equipo no encontrado"))?;
+    let usage = usage_for(team.used_bytes, team.quota_bytes);
+    Ok(TeamMembership {
+        team,
+        role: miembro.role,
+        usage,
+    })
+}
+
+/// Crea un equipo con el usuario como su primer dueño
+pub async fn create_team(user_id: &str, name: &str) -> Result<TeamMembership> {
+    let name = validate_team_name(name)?;
+
+    let team_id = Uuid::new_v4().to_string();
+    let ahora = Utc::now().timestamp();
+    let dueno = TeamMember {
+        team_id: team_id.clone(),
+        user_id: user_id.to_string(),
+        role: TEAM_OWNER.to_string(),
+        created_at: ahora,
+    };
+    init_db_manager()
+        .insertar_team(
+            &NuevoTeam {
+                id: &team_id,
+                name,
+                created_at: ahora,
+            },
+            &dueno,
+        )
+        .context("Error al crear el equipo")?;
+
+    info!("Equipo {} creado por el usuario {}", team_id, user_id);
+    team_membership(dueno)
+}
+
+/// Equipos a los que pertenece el usuario
+pub async fn list_teams(user_id: &str) -> Result<Vec<TeamMembership>> {
+    let equipos = init_db_manager()
+        .obtener_teams_de_usuario(user_id)
+        .context("Error al listar los equipos")?;
+
+    Ok(equipos
+        .into_iter()
+        .map(|(team, miembro)| {
+            let usage = usage_for(team.used_bytes, team.quota_bytes);
+            TeamMembership {
+                team,
+                role: miembro.role,
+                usage,
+            }
+        })
+        .collect())
+}
+
+/// Un equipo del usuario con su rol y el uso del espacio
+pub async fn get_team(user_id: &str, team_id: &str) -> Result<TeamMembership> {
+    team_membership(find_team_member(user_id, team_id, TEAM_VIEWER)?)
+}
+
+/// Miembros de un equipo; los puede ver cualquier miembro
+pub async fn list_team_members(user_id: &str, team_id: &str) -> Result<Vec<TeamMember>> {
+    find_team_member(user_id, team_id, TEAM_VIEWER)?;
+    init_db_manager()
+        .obtener_miembros_team(team_id)
+        .context("Error al listar los miembros")
+}
+
+/// Cuenta los dueños del equipo
+fn count_team_owners(team_id: &str) -> Result<usize> {
+    Ok(init_db_manager()
+        .obtener_miembros_team(team_id)
+        .context("Error al listar los miembros")?
+        .iter()
+        .filter(|miembro| miembro.role == TEAM_OWNER)
+        .count())
+}
+
+/// Agrega un usuario al equipo o cambia su rol
+///
+/// # Validaciones
+/// - El rol debe ser `owner`, `admin`, `member` o `viewer`
+/// - Quien lo hace debe ser `admin`; para dar o quitar los roles `owner` y
+///   `admin` debe ser `owner`
+/// - El usuario debe existir
+/// - El equipo debe conservar al menos un dueño
+pub async fn set_team_member(
+    user_id: &str,
+    team_id: &str,
+    member_id: &str,
+    role: &str,
+) -> Result<TeamMember> {
+    let nuevo_rango = team_rank(role).ok_or_else(|| {
+        anyhow!(
+            "Rol inválido: debe ser '{}', '{}', '{}' o '{}'",
+            TEAM_OWNER,
+            TEAM_ADMIN,
+            TEAM_MEMBER,
+            TEAM_VIEWER
+        )
+    })?;
+    let actor = find_team_member(user_id, team_id, TEAM_ADMIN)?;
+
+    let db = init_db_manager();
+    if db.buscar_usuario(member_id).is_err() {
+        return Err(anyhow!("Usuario {} no encontrado", member_id));
+    }
+    let actual = db.buscar_miembro_team(team_id, member_id).ok();
+
+    let toca_admins = Some(nuevo_rango) >= team_rank(TEAM_ADMIN)
+        || actual
+            .as_ref()
+            .is_some_and(|m| team_rank(&m.role) >= team_rank(TEAM_ADMIN));
+    if toca_admins && actor.role != TEAM_OWNER {
+        return Err(anyhow!(
+            "Permiso insuficiente: solo un dueño puede gestionar dueños y administradores"
+        ));
+    }
+    if actual.as_ref().is_some_and(|m| m.role == TEAM_OWNER)
+        && role != TEAM_OWNER
+        && count_team_owners(team_id)? <= 1
+    {
+        return Err(anyhow!("El equipo debe conservar al menos un dueño"));
+    }
+
+    let miembro = db
+        .guardar_miembro_team(&TeamMember {
+            team_id: team_id.to_string(),
+            user_id: member_id.to_string(),
+            role: role.to_string(),
+            created_at: Utc::now().timestamp(),
+        })
+        .context("Error al guardar el miembro")?;
+
+    info!(
+        "Usuario {} es ahora '{}' en el equipo {}",
+        member_id, role, team_id
+    );
+    Ok(miembro)
+}
+
+/// Quita a un usuario del equipo; pierde el acceso de inmediato
+///
+/// Cualquier miembro puede irse por su cuenta. Para quitar a otro hace falta
+/// ser `admin`, y `owner` si el otro es dueño o administrador. El último
+/// dueño no puede irse.
+pub async fn remove_team_member(user_id: &str, team_id: &str, member_id: &str) -> Result<()> {
+    let actor = find_team_member(user_id, team_id, TEAM_VIEWER)?;
+    let db = init_db_manager();
+    let miembro = db
+        .buscar_miembro_team(team_id, member_id)
+        .map_err(|_| anyhow!("Miembro no encontrado"))?;
+
+    if member_id != user_id {
+        let requerido = if team_rank(&miembro.role) >= team_rank(TEAM_ADMIN) {
+            TEAM_OWNER
+        } else {
+            TEAM_ADMIN
+        };
+        if team_rank(&actor.role) < team_rank(requerido) {
+            return Err(anyhow!(
+                "Permiso insuficiente: se requiere el rol '{}' en el equipo",
+                requerido
+            ));
+        }
+    }
+    if miembro.role == TEAM_OWNER && count_team_owners(team_id)? <= 1 {
+        return Err(anyhow!("El equipo debe conservar al menos un dueño"));
+    }
+
+    db.borrar_miembro_team(team_id, member_id)
+        .context("Error al quitar el miembro")?;
+
+    info!("Usuario {} quitado del equipo {}", member_id, team_id);
+    Ok(())
+}
+
+/// Raíz del espacio de un equipo; las subcarpetas se listan con
+/// `list_folder`
+pub async fn list_team_drive(user_id: &str, team_id: &str) -> Result<FolderListing> {
+    find_team_member(user_id, team_id, TEAM_VIEWER)?;
+
+    let db = init_db_manager();
+    let folders = db
+        .obtener_folders_hijos(team_id, None)
+        .context("Error al obtener las carpetas")?;
+    let files = db
+        .obtener_files_de_carpeta(team_id, None)
+        .context("Error al obtener los archivos")?;
+
+    Ok(FolderListing {
+        folder: None,
+        path: Vec::new(),
+        folders,
+        files,
+    })
+}
+
+/// Elimina un equipo. Solo lo puede hacer un dueño y su espacio no debe
+/// tener carpetas ni archivos; los que estén en la papelera se purgan.
+pub async fn delete_team(user_id: &str, team_id: &str) -> Result<()> {
+    find_team_member(user_id, team_id, TEAM_OWNER)?;
+
+    let db = init_db_manager();
+    let activos = db
+        .obtener_files_de_usuario(team_id, None, None, None)
+        .context("Error al buscar los archivos del equipo")?;
+    let carpetas = db
+        .obtener_folders_hijos(team_id, None)
+        .context("Error al buscar las carpetas del equipo")?;
+    if !activos.is_empty() || !carpetas.is_empty() {
+        return Err(anyhow!("El espacio del equipo no está vacío"));
+    }
+
+    empty_trash(user_id, Some(team_id)).await?;
+    if db
+        .borrar_team_vacio(team_id)
+        .context("Error al eliminar el equipo")?
+        == 0
+    {
+        // Algo se subió mientras tanto
+        return Err(anyhow!("El espacio del equipo no está vacío"));
+    }
+
+    info!("Equipo {} eliminado por el usuario {}", team_id, user_id);
+    Ok(())
+}
+
+/// Fija la cuota propia de un equipo; `None` vuelve a la cuota por defecto
+pub async fn set_team_quota(team_id: &str, quota_bytes: Option<u64>) -> Result<StorageUsage> {
+    let quota_bytes = quota_bytes
+        .map(|cuota| i64::try_from(cuota).map_err(|_| anyhow!("Cuota inválida")))
+        .transpose()?;
+
+    let actualizados = init_db_manager()
+        .actualizar_cuota_team(team_id, quota_bytes)
+        .context("Error al actualizar la cuota")?;
+    if actualizados == 0 {
+        return Err(anyhow!("Equipo '{}' no encontrado", team_id));
+    }
+
+    info!(
+        "Cuota del equipo {} actualizada a {:?} bytes",
+        team_id, quota_bytes
+    );
+    drive_usage(team_id)
+}
+
+// ============================================================================
+// Carpetas
+// ============================================================================
+
+/// Valida el nombre de una carpeta: 1-255 caracteres, sin separadores de
+/// ruta ni caracteres de control
+fn validate_folder_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty()
+        || name.chars().count() > 255
+        || name == "."
+        || name == ".."
+        || name
+            .chars()
+            .any(|c| c == '/' || c == '\\' || c.is_control())
+    {
+        return Err(anyhow!("Nombre de carpeta inválido"));
+    }
+    Ok(name)
+}
+
+/// Rechaza el nombre si otra carpeta del mismo destino ya lo usa
+fn check_folder_name_free(
+    user_id: &str,
+    parent_id: Option<&str>,
+    name: &str,
+    excluir: Option<&str>,
+) -> Result<()> {
+    let hermanas = init_db_manager()
+        .obtener_folders_hijos(user_id, parent_id)
+        .context("Error al buscar carpetas")?;
+
+    if hermanas
+        .iter()
+        .any(|f| f.name == name && Some(f.id.as_str()) != excluir)
+    {
+        return Err(anyhow!(
+            "Ya existe una carpeta llamada '{}' en ese destino",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Crea una carpeta en la raíz o dentro de `parent_id`. Con `team_id` (y
+/// sin `parent_id`) se crea en la raíz del espacio de ese equipo.
 ///
-/// # Errores
-/// - Si falla la inserción en DB, no se escribe el archivo
-/// - Si falla la escritura del archivo, se hace rollback en DB
-pub async fn upload_file(user_id: &str, mime: &str, file_content: Vec<u8>) -> Result<String> {
-    let file_id = Uuid::new_v4().to_string();
+/// # Validaciones
+/// - La carpeta padre existe y el usuario puede escribir en su espacio
+/// - Nombre válido y sin repetir entre sus hermanas
+pub async fn create_folder(
+    user_id: &str,
+    name: &str,
+    parent_id: Option<&str>,
+    team_id: Option<&str>,
+) -> Result<Folder> {
+    let name = validate_folder_name(name)?;
+    let owner_id = drive_owner(user_id, team_id, parent_id)?;
+    check_folder_name_free(&owner_id, parent_id, name, None)?;
 
-    // Generar hash del archivo
-    let mut hasher = Blake2b512::new();
-    hasher.update(&file_content);
-    let hash = format!("{:x}", hasher.finalize());
+    let folder_id = Uuid::new_v4().to_string();
+    let ahora = Utc::now().timestamp();
+    init_db_manager()
+        .insertar_folder(&NuevoFolder {
+            id: &folder_id,
+            owner_id: &owner_id,
+            parent_id,
+            name,
+            created_at: ahora,
+            updated_at: ahora,
+        })
+        .context("Error al crear la carpeta")?;
 
-    info!(
-        "Procesando archivo: {} para usuario: {} (tamaño: {} bytes)",
-        file_id,
-        user_id,
-        file_content.len()
-    );
+    info!("Carpeta {} creada en el espacio {}", folder_id, owner_id);
+    find_drive_folder(user_id, &folder_id)
+}
 
-    let nuevo_file = NuevoFile {
-        id: &file_id,
-        mime,
-        hash: &hash,
-        owner_id: user_id,
-    };
+/// Cambia el nombre de una carpeta
+pub async fn rename_folder(user_id: &str, folder_id: &str, name: &str) -> Result<Folder> {
+    let folder = find_drive_folder(user_id, folder_id)?;
+    let name = validate_folder_name(name)?;
+    check_folder_name_free(
+        &folder.owner_id,
+        folder.parent_id.as_deref(),
+        name,
+        Some(folder_id),
+    )?;
+
+    init_db_manager()
+        .renombrar_folder(folder_id, name, Utc::now().timestamp())
+        .context("Error al renombrar la carpeta")?;
+
+    info!("Carpeta {} renombrada", folder_id);
+    find_drive_folder(user_id, folder_id)
+}
+
+/// Mueve una carpeta (con todo su contenido) a otra carpeta o a la raíz
+///
+/// # Validaciones
+/// - La carpeta y el destino son del mismo espacio y el usuario puede
+///   escribir en él (`None` = raíz de ese espacio)
+/// - El destino no es la propia carpeta ni una de sus descendientes
+/// - El nombre no se repite en el destino
+pub async fn move_folder(
+    user_id: &str,
+    folder_id: &str,
+    parent_id: Option<&str>,
+) -> Result<Folder> {
+    let folder = find_drive_folder(user_id, folder_id)?;
+    check_move_target(user_id, &folder.owner_id, parent_id)?;
+
+    // Desde el destino hasta la raíz: si aparece la carpeta, habría un ciclo
+    if let Some(parent_id) = parent_id
+        && folder_chain(parent_id)?.iter().any(|f| f.id == folder_id)
+    {
+        return Err(anyhow!(
+            "Destino inválido: no se puede mover una carpeta dentro de sí misma"
+        ));
+    }
+    check_folder_name_free(&folder.owner_id, parent_id, &folder.name, Some(folder_id))?;
 
-    // Guardar en la base de datos primero
     init_db_manager()
-        .insertar_file(&nuevo_file)
-        .context("Error al insertar archivo en la base de datos")?;
+        .mover_folder(folder_id, parent_id, Utc::now().timestamp())
+        .context("Error al mover la carpeta")?;
 
-    info!("Registro de archivo creado en DB: {}", file_id);
+    info!("Carpeta {} movida a {:?}", folder_id, parent_id);
+    find_drive_folder(user_id, folder_id)
+}
 
-    // Guardar el archivo en el sistema de archivos
-    let file_path = format!("./Privafile/Uploads/{}.st", file_id);
+/// Contenido de una carpeta (o de la raíz con `None`) y su ruta desde la raíz
+///
+/// Las carpetas compartidas con el usuario y las del espacio de sus equipos
+/// también se pueden listar. Con un permiso compartido la ruta empieza en la
+/// carpeta compartida más alta y no muestra las carpetas del dueño que no se
+/// compartieron.
+pub async fn list_folder(user_id: &str, folder_id: Option<&str>) -> Result<FolderListing> {
+    let db = init_db_manager();
 
-    match write_file(&file_path, &file_content).await {
-        Ok(_) => {
-            info!("Archivo guardado exitosamente en: {}", file_path);
-            Ok(file_id)
-        }
-        Err(e) => {
-            // Rollback: eliminar el registro de la base de datos
-            error!("Error al escribir archivo, haciendo rollback en DB");
-            let _ = init_db_manager().borrar_file(&file_id);
-            Err(e).context("Error al guardar archivo en disco (rollback ejecutado)")
+    let (folder, path, owner_id) = match folder_id {
+        Some(id) => {
+            let (mut cadena, compartida) = find_accessible_folder(user_id, id, false)?;
+            let folder = cadena.remove(0);
+            let owner_id = folder.owner_id.clone();
+            if compartida.is_some() {
+                // Solo se muestran las ancestras hasta la más alta compartida
+                let ids: Vec<String> = cadena.iter().map(|f| f.id.clone()).collect();
+                let compartidas: HashSet<String> = db
+                    .buscar_permisos_aplicables(user_id, None, &ids)
+                    .context("Error al consultar los permisos")?
+                    .into_iter()
+                    .filter_map(|grant| grant.folder_id)
+                    .collect();
+                let visibles = cadena
+                    .iter()
+                    .rposition(|f| compartidas.contains(&f.id))
+                    .map_or(0, |i| i + 1);
+                cadena.truncate(visibles);
+            }
+            cadena.reverse();
+            (Some(folder), cadena, owner_id)
         }
-    }
+        None => (None, Vec::new(), user_id.to_string()),
+    };
+
+    let folders = db
+        .obtener_folders_hijos(&owner_id, folder_id)
+        .context("Error al obtener las carpetas")?;
+    let files = db
+        .obtener_files_de_carpeta(&owner_id, folder_id)
+        .context("Error al obtener los archivos")?;
+
+    Ok(FolderListing {
+        folder,
+        path,
+        folders,
+        files,
+    })
 }
 
-/// Lista los archivos de un usuario con filtros opcionales
+/// Elimina una carpeta. Sin `recursive` solo se permite si está vacía; con
+/// `recursive` se eliminan antes todos sus archivos y subcarpetas.
 ///
-/// # Parámetros
-/// - `user_id`: ID del usuario propietario
-/// - `mime_filter`: Filtro opcional por tipo MIME
-/// - `limit`: Límite opcional de resultados (1-1000)
+/// Los archivos se envían primero a la papelera con `delete_file`; si alguno
+/// falla se detiene el proceso y las carpetas quedan intactas para poder
+/// reintentar. Al restaurarlos vuelven a la raíz, porque su carpeta ya no
+/// existe.
 ///
 /// # Retorna
-/// Vector de archivos que cumplen los criterios
-pub async fn list_user_files(
-    user_id: &str,
-    mime_filter: Option<&str>,
-    limit: Option<i64>,
-) -> Result<Vec<File>> {
-    info!(
-        "Listando archivos para usuario: {} (mime: {:?}, limit: {:?})",
-        user_id, mime_filter, limit
-    );
+/// Cantidad de archivos enviados a la papelera
+pub async fn delete_folder(user_id: &str, folder_id: &str, recursive: bool) -> Result<usize> {
+    let db = init_db_manager();
+    let owner_id = find_drive_folder(user_id, folder_id)?.owner_id;
 
-    let files = init_db_manager()
-        .obtener_files_de_usuario(user_id, mime_filter, limit)
-        .context("Error al obtener archivos de la base de datos")?;
+    // Recorrido en anchura: `carpetas` queda ordenado de la raíz a las hojas
+    let mut carpetas = vec![folder_id.to_string()];
+    let mut archivos = Vec::new();
+    let mut i = 0;
+    while i < carpetas.len() {
+        let id = carpetas[i].clone();
+        archivos.extend(
+            db.obtener_files_de_carpeta(&owner_id, Some(&id))
+                .context("Error al obtener los archivos de la carpeta")?,
+        );
+        carpetas.extend(
+            db.obtener_folders_hijos(&owner_id, Some(&id))
+                .context("Error al obtener las subcarpetas")?
+                .into_iter()
+                .map(|f| f.id),
+        );
+        i += 1;
+    }
+
+    if !recursive && (carpetas.len() > 1 || !archivos.is_empty()) {
+        return Err(anyhow!("La carpeta no está vacía"));
+    }
+
+    for file in &archivos {
+        delete_file(user_id, &file.id).await?;
+    }
+
+    for id in carpetas.iter().rev() {
+        if db
+            .borrar_folder_vacio(id)
+            .context("Error al eliminar la carpeta")?
+            == 0
+        {
+            // Algo se agregó mientras tanto: no se borra nada que no se haya visto
+            return Err(anyhow!("La carpeta no está vacía"));
+        }
+    }
 
     info!(
-        "Encontrados {} archivos para usuario {}",
-        files.len(),
-        user_id
+        "Carpeta {} eliminada ({} subcarpetas, {} archivos)",
+        folder_id,
+        carpetas.len() - 1,
+        archivos.len()
     );
-    Ok(files)
+    Ok(archivos.len())
 }
 
-/// Descarga un archivo verificando propiedad
+// ============================================================================
+// Administración
+// ============================================================================
+
+/// Rol de una cuenta normal
+pub const USER_ROLE_USER: &str = "user";
+/// Rol con acceso a los endpoints de `/api/admin`
+pub const USER_ROLE_ADMIN: &str = "admin";
+
+/// Bytes aleatorios de una contraseña temporal (128 bits)
+const TEMP_PASSWORD_BYTES: usize = 16;
+
+/// Comprueba la sesión de un token ya verificado: la cuenta debe existir,
+/// estar habilitada y no haber cortado sus sesiones desde `issued_at`.
 ///
-/// # Validaciones
-/// - El archivo debe existir
-/// - El archivo debe pertenecer al usuario
-/// - El archivo debe existir en disco
+/// `iat` tiene resolución de segundos, así que un token emitido en el mismo
+/// segundo del corte se considera revocado; ver `wait_past_revocation`.
 ///
 /// # Retorna
-/// Tupla con (mime_type, contenido_binario)
-pub async fn download_file(user_id: &str, file_id: &str) -> Result<(String, Vec<u8>)> {
-    // Validación de seguridad: prevenir path traversal
-    if file_id.contains("..") || file_id.contains('/') || file_id.contains('\\') {
-        error!("Intento de path traversal detectado: {}", file_id);
-        return Err(anyhow::anyhow!("ID de archivo inválido"));
+/// El usuario de la sesión
+pub async fn session_user(user_id: &str, issued_at: i64) -> Result<Usuario> {
+    let usuario = init_db_manager()
+        .buscar_usuario(user_id)
+        .map_err(|_| anyhow!("Usuario '{}' no encontrado", user_id))?;
+    if usuario.disabled_at.is_some() {
+        return Err(anyhow!("Cuenta deshabilitada"));
+    }
+    if usuario
+        .sessions_revoked_at
+        .is_some_and(|cortadas| issued_at <= cortadas)
+    {
+        return Err(anyhow!("Sesión revocada"));
     }
+    Ok(usuario)
+}
 
-    info!(
-        "Usuario {} solicitando descarga del archivo {}",
-        user_id, file_id
-    );
+/// Espera a que pase el segundo del último corte de sesiones, para que el
+/// token que se emita a continuación no caiga dentro del corte
+async fn wait_past_revocation(revoked_at: Option<i64>) {
+    let Some(revoked_at) = revoked_at else {
+        return;
+    };
+    let ahora = Utc::now();
+    if ahora.timestamp() <= revoked_at {
+        let siguiente = DateTime::from_timestamp(revoked_at + 1, 0).unwrap_or(ahora);
+        if let Ok(espera) = (siguiente - ahora).to_std() {
+            tokio::time::sleep(espera).await;
+        }
+    }
+}
 
-    // Verificar que el archivo exista y pertenezca al usuario
-    let files = init_db_manager()
-        .obtener_files_de_usuario(user_id, None, None)
-        .context("Error al buscar archivos del usuario")?;
+/// Indica si la cuenta existe y está habilitada. Los enlaces públicos de
+/// una cuenta deshabilitada dejan de funcionar.
+fn account_enabled(user_id: &str) -> bool {
+    init_db_manager()
+        .buscar_usuario(user_id)
+        .is_ok_and(|usuario| usuario.disabled_at.is_none())
+}
 
-    let file_info = files.iter().find(|f| f.id == file_id).ok_or_else(|| {
-        warn!(
-            "Archivo {} no encontrado o no pertenece al usuario {}",
-            file_id, user_id
-        );
-        anyhow::anyhow!("Archivo no encontrado")
-    })?;
+/// Indica si el usuario tiene el rol de administrador
+pub async fn is_admin_user(user_id: &str) -> bool {
+    init_db_manager()
+        .buscar_usuario(user_id)
+        .is_ok_and(|usuario| usuario.role == USER_ROLE_ADMIN)
+}
+
+/// Da el rol de administrador a los usuarios de `admin_user_ids`. Se llama
+/// al arrancar para poder crear el primer administrador desde la
+/// configuración.
+///
+/// # Retorna
+/// Cantidad de usuarios promovidos
+pub fn bootstrap_admins() -> Result<usize> {
+    let db = init_db_manager();
+    let mut promovidos = 0;
+    for user_id in admin_user_ids() {
+        match db.buscar_usuario(&user_id) {
+            Ok(usuario) if usuario.role == USER_ROLE_ADMIN => {}
+            Ok(_) => {
+                db.actualizar_rol_usuario(&user_id, USER_ROLE_ADMIN)
+                    .context("Error al promover administrador")?;
+                info!("Usuario {} promovido a administrador", user_id);
+                promovidos += 1;
+            }
+            Err(_) => warn!(
+                "admin_user_ids: el usuario {} no existe, se ignora",
+                user_id
+            ),
+        }
+    }
+    Ok(promovidos)
+}
+
+/// Da o quita el rol de administrador por username, para
+/// `privafile admin grant|revoke <username>`
+///
+/// # Retorna
+/// El ID del usuario
+pub fn set_admin_by_username(username: &str, admin: bool) -> Result<String> {
+    let db = init_db_manager();
+    let usuario = db
+        .buscar_usuario_por_username(username)
+        .map_err(|_| anyhow!("Usuario '{}' no encontrado", username))?;
+    let rol = if admin {
+        USER_ROLE_ADMIN
+    } else {
+        USER_ROLE_USER
+    };
+    db.actualizar_rol_usuario(&usuario.id, rol)
+        .context("Error al actualizar el rol")?;
+
+    info!("Rol de {} ({}) cambiado a '{}'", username, usuario.id, rol);
+    Ok(usuario.id)
+}
 
-    // Construir la ruta del archivo
-    let file_path = PathBuf::from(format!("./Privafile/Uploads/{}.st", file_id));
+/// Cuenta vista por un administrador, con su uso de almacenamiento
+fn admin_user_info(usuario: Usuario) -> AdminUserInfo {
+    AdminUserInfo {
+        usage: usage_for(usuario.used_bytes, usuario.quota_bytes),
+        id: usuario.id,
+        username: usuario.username,
+        role: usuario.role,
+        disabled: usuario.disabled_at.is_some(),
+        disabled_at: usuario.disabled_at,
+        password_reset_required: usuario.password_reset_required,
+    }
+}
 
-    // Leer el archivo del disco
-    let file_content = tokio::fs::read(&file_path)
-        .await
-        .with_context(|| format!("Error al leer archivo desde disco: {:?}", file_path))?;
+/// Busca la cuenta que gestiona un administrador. Las operaciones que
+/// podrían dejarlo fuera no se pueden hacer sobre su propia cuenta.
+fn find_managed_user(admin_id: &str, user_id: &str, propia: bool) -> Result<Usuario> {
+    if !propia && admin_id == user_id {
+        return Err(anyhow!(
+            "Operación inválida sobre tu propia cuenta de administrador"
+        ));
+    }
+    init_db_manager()
+        .buscar_usuario(user_id)
+        .map_err(|_| anyhow!("Usuario '{}' no encontrado", user_id))
+}
+
+/// Lista todas las cuentas con su uso de almacenamiento
+pub async fn list_users() -> Result<Vec<AdminUserInfo>> {
+    Ok(init_db_manager()
+        .obtener_usuarios()
+        .context("Error al listar los usuarios")?
+        .into_iter()
+        .map(admin_user_info)
+        .collect())
+}
+
+/// Una cuenta con su uso de almacenamiento
+pub async fn get_user(user_id: &str) -> Result<AdminUserInfo> {
+    init_db_manager()
+        .buscar_usuario(user_id)
+        .map(admin_user_info)
+        .map_err(|_| anyhow!("Usuario '{}' no encontrado", user_id))
+}
+
+/// Cambia el rol de una cuenta
+///
+/// # Validaciones
+/// - El rol debe ser `user` o `admin`
+/// - Un administrador no puede quitarse el rol a sí mismo
+pub async fn set_user_role(admin_id: &str, user_id: &str, role: &str) -> Result<AdminUserInfo> {
+    if role != USER_ROLE_USER && role != USER_ROLE_ADMIN {
+        return Err(anyhow!("Rol inválido: debe ser 'user' o 'admin'"));
+    }
+    find_managed_user(admin_id, user_id, role == USER_ROLE_ADMIN)?;
 
+    init_db_manager()
+        .actualizar_rol_usuario(user_id, role)
+        .context("Error al actualizar el rol")?;
     info!(
-        "Archivo {} descargado exitosamente ({} bytes)",
-        file_id,
-        file_content.len()
+        "Admin {} cambió el rol de {} a '{}'",
+        admin_id, user_id, role
     );
+    get_user(user_id).await
+}
+
+/// Deshabilita o vuelve a habilitar una cuenta. Deshabilitarla corta sus
+/// sesiones y sus URLs firmadas, y sus enlaces públicos dejan de funcionar
+/// hasta que se habilite otra vez.
+pub async fn set_user_disabled(
+    admin_id: &str,
+    user_id: &str,
+    disabled: bool,
+) -> Result<AdminUserInfo> {
+    find_managed_user(admin_id, user_id, !disabled)?;
+
+    let ahora = disabled.then(|| Utc::now().timestamp());
+    init_db_manager()
+        .actualizar_deshabilitado_usuario(user_id, ahora)
+        .context("Error al actualizar la cuenta")?;
+    info!(
+        "Admin {} {} la cuenta {}",
+        admin_id,
+        if disabled {
+            "deshabilitó"
+        } else {
+            "habilitó"
+        },
+        user_id
+    );
+    get_user(user_id).await
+}
+
+/// Fija una contraseña temporal aleatoria y obliga al usuario a cambiarla
+/// al entrar. Las sesiones abiertas dejan de valer.
+///
+/// # Retorna
+/// La contraseña temporal, que no se guarda en claro
+pub async fn reset_user_password(admin_id: &str, user_id: &str) -> Result<String> {
+    find_managed_user(admin_id, user_id, true)?;
 
-    Ok((file_info.mime.clone(), file_content))
+    let mut bytes = [0u8; TEMP_PASSWORD_BYTES];
+    getrandom::fill(&mut bytes).map_err(|e| anyhow!("Error al generar la contraseña: {}", e))?;
+    let temporal = URL_SAFE_NO_PAD.encode(bytes);
+
+    init_db_manager()
+        .actualizar_password_usuario(
+            user_id,
+            &hash_password(&temporal)?,
+            true,
+            Utc::now().timestamp(),
+        )
+        .context("Error al actualizar la contraseña")?;
+    info!("Admin {} reinició la contraseña de {}", admin_id, user_id);
+    Ok(temporal)
 }
 
-/// Elimina un archivo del sistema
+/// Elimina una cuenta con todos sus archivos, carpetas, enlaces y permisos.
+/// Los archivos de los equipos son del equipo y se conservan.
 ///
 /// # Validaciones
-/// - El archivo debe existir
-/// - El archivo debe pertenecer al usuario
+/// - Un administrador no puede eliminarse a sí mismo
+/// - La cuenta no puede ser el único dueño de un equipo
 ///
-/// # Acciones
-/// 1. Verifica propiedad
-/// 2. Elimina de la base de datos
-/// 3. Elimina del disco
-pub async fn delete_file(user_id: &str, file_id: &str) -> Result<()> {
-    // Validación de seguridad
-    if file_id.contains("..") || file_id.contains('/') || file_id.contains('\\') {
-        error!("Intento de path traversal detectado en delete: {}", file_id);
-        return Err(anyhow::anyhow!("ID de archivo inválido"));
-    }
+/// # Retorna
+/// Cantidad de archivos eliminados
+pub async fn delete_user(admin_id: &str, user_id: &str) -> Result<usize> {
+    find_managed_user(admin_id, user_id, false)?;
 
-    info!("Usuario {} eliminando archivo {}", user_id, file_id);
+    let db = init_db_manager();
+    for (team, miembro) in db
+        .obtener_teams_de_usuario(user_id)
+        .context("Error al buscar los equipos del usuario")?
+    {
+        if miembro.role != TEAM_OWNER {
+            continue;
+        }
+        let duenos = db
+            .obtener_miembros_team(&team.id)
+            .context("Error al buscar los miembros del equipo")?
+            .iter()
+            .filter(|m| m.role == TEAM_OWNER)
+            .count();
+        if duenos <= 1 {
+            return Err(anyhow!(
+                "El usuario es el único dueño del equipo '{}' y debe quedar al menos un dueño",
+                team.name
+            ));
+        }
+    }
 
-    // Verificar que el archivo exista y pertenezca al usuario
-    let files = init_db_manager()
-        .obtener_files_de_usuario(user_id, None, None)
-        .context("Error al buscar archivos del usuario")?;
+    // Deshabilitada no puede subir nada mientras se borran sus archivos
+    db.actualizar_deshabilitado_usuario(user_id, Some(Utc::now().timestamp()))
+        .context("Error al deshabilitar la cuenta")?;
+    let archivos = db
+        .obtener_ids_files_de_dueno(user_id)
+        .context("Error al buscar los archivos del usuario")?;
+    for file_id in &archivos {
+        blobs::release_file(file_id).await?;
+    }
 
-    let file_exists = files.iter().any(|f| f.id == file_id);
-    if !file_exists {
-        warn!(
-            "Archivo {} no encontrado o no pertenece al usuario {}",
-            file_id, user_id
-        );
-        return Err(anyhow::anyhow!("Archivo no encontrado"));
+    let subidas = db
+        .borrar_usuario(user_id)
+        .context("Error al eliminar el usuario")?
+        .ok_or_else(|| anyhow!("El usuario recibió archivos durante el borrado, reintenta"))?;
+    for upload_id in subidas {
+        let _ = tokio::fs::remove_file(tus_part_path(&upload_id)).await;
     }
 
-    // Eliminar de la base de datos
-    init_db_manager()
-        .borrar_file(file_id)
-        .context("Error al eliminar archivo de la base de datos")?;
+    info!(
+        "Admin {} eliminó al usuario {} ({} archivos)",
+        admin_id,
+        user_id,
+        archivos.len()
+    );
+    Ok(archivos.len())
+}
 
-    info!("Archivo {} eliminado de la base de datos", file_id);
+/// Totales de almacenamiento de todo el servidor
+pub async fn server_storage() -> Result<ServerStorage> {
+    init_db_manager()
+        .estadisticas_almacenamiento()
+        .context("Error al calcular el almacenamiento del servidor")
+}
 
-    // Eliminar del disco
-    let file_path = PathBuf::from(format!("./Privafile/Uploads/{}.st", file_id));
+/// Lista los blobs con problemas de integridad junto a los archivos afectados
+pub async fn list_integrity_issues() -> Result<Vec<IntegrityIssueInfo>> {
+    let db = init_db_manager();
+    let problemas = db
+        .obtener_problemas_integridad()
+        .context("Error al obtener problemas de integridad")?;
 
-    match tokio::fs::remove_file(&file_path).await {
-        Ok(_) => {
-            info!("Archivo {} eliminado del disco", file_id);
-            Ok(())
-        }
-        Err(e) => {
-            warn!(
-                "Archivo {} eliminado de DB pero no del disco: {}",
-                file_id, e
-            );
-            // No es crítico si el archivo no existe en disco
-            Ok(())
-        }
+    let mut infos = Vec::with_capacity(problemas.len());
+    for problema in problemas {
+        let file_ids = db
+            .obtener_files_por_hash(&problema.hash)
+            .context("Error al buscar archivos afectados")?
+            .into_iter()
+            .map(|f| f.id)
+            .collect();
+        infos.push(IntegrityIssueInfo {
+            hash: problema.hash,
+            status: problema.status,
+            detail: problema.detail,
+            detected_at: problema.detected_at,
+            file_ids,
+        });
     }
+    Ok(infos)
 }
 
+// ============================================================================
+// Cuentas
+// ============================================================================
+
 /// Registra un nuevo usuario en el sistema
 ///
 /// # Validaciones
@@ -251,12 +3338,7 @@ pub async fn register_user(username: &str, password: &str) -> Result<String> {
     }
 
     // Hashear la contraseña
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2
-        .hash_password(password.as_bytes(), salt.as_salt())
-        .map_err(|e| anyhow!("Error al hashear la contraseña: {}", e))?
-        .to_string();
+    let password_hash = hash_password(password)?;
 
     // Crear el usuario
     let user_id = Uuid::new_v4().to_string();
@@ -294,22 +3376,81 @@ pub async fn authenticate_user(username: &str, password: &str) -> Result<String>
         .context("Usuario no encontrado")?;
 
     // Verificar contraseña
-    let parsed_hash = PasswordHash::new(&usuario.password)
-        .map_err(|e| anyhow!("Error al parsear hash de contraseña: {}", e))?;
-
-    match Argon2::default().verify_password(password.as_bytes(), &parsed_hash) {
-        Ok(_) => {
-            info!(
-                "Login exitoso para usuario: {} (ID: {})",
-                username, usuario.id
-            );
-            Ok(usuario.id)
-        }
-        Err(_) => {
-            warn!("Intento de login fallido para usuario: {}", username);
-            Err(anyhow!("Credenciales inválidas"))
+    if verify_password(&usuario.password, password)? {
+        if usuario.disabled_at.is_some() {
+            warn!("Intento de login en cuenta deshabilitada: {}", username);
+            return Err(anyhow!("Cuenta deshabilitada"));
         }
+        wait_past_revocation(usuario.sessions_revoked_at).await;
+        info!(
+            "Login exitoso para usuario: {} (ID: {})",
+            username, usuario.id
+        );
+        Ok(usuario.id)
+    } else {
+        warn!("Intento de login fallido para usuario: {}", username);
+        Err(anyhow!("Credenciales inválidas"))
+    }
+}
+
+/// Cambia la contraseña del usuario. Corta las demás sesiones y, si un
+/// administrador había reiniciado la contraseña, levanta la obligación de
+/// cambiarla. Vuelve una vez pasado el segundo del corte, de modo que el
+/// token que emita quien llama siga siendo válido.
+///
+/// # Validaciones
+/// - `current_password` debe coincidir con la contraseña actual
+/// - `new_password` debe tener al menos 8 caracteres y ser distinta
+pub async fn change_password(
+    user_id: &str,
+    current_password: &str,
+    new_password: &str,
+) -> Result<()> {
+    let db = init_db_manager();
+    let usuario = db
+        .buscar_usuario(user_id)
+        .map_err(|_| anyhow!("Usuario '{}' no encontrado", user_id))?;
+    if !verify_password(&usuario.password, current_password)? {
+        warn!("Contraseña actual incorrecta al cambiarla: {}", user_id);
+        return Err(anyhow!("Contraseña actual incorrecta"));
+    }
+    if new_password.len() < 8 {
+        return Err(anyhow!(
+            "Contraseña inválida: debe tener al menos 8 caracteres"
+        ));
+    }
+    if new_password == current_password {
+        return Err(anyhow!(
+            "Contraseña inválida: debe ser distinta de la actual"
+        ));
     }
+
+    let ahora = Utc::now().timestamp();
+    db.actualizar_password_usuario(user_id, &hash_password(new_password)?, false, ahora)
+        .context("Error al actualizar la contraseña")?;
+    info!("Contraseña cambiada por el usuario {}", user_id);
+
+    // El token nuevo se emite al volver: debe quedar fuera del corte
+    wait_past_revocation(Some(ahora)).await;
+    Ok(())
+}
+
+/// Hashea una contraseña con Argon2id y un salt aleatorio
+fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), salt.as_salt())
+        .map_err(|e| anyhow!("Error al hashear la contraseña: {}", e))?
+        .to_string())
+}
+
+/// Comprueba una contraseña contra su hash Argon2
+fn verify_password(hash: &str, password: &str) -> Result<bool> {
+    let parsed_hash = PasswordHash::new(hash)
+        .map_err(|e| anyhow!("Error al parsear hash de contraseña: {}", e))?;
+    Ok(Argon2::default()
+        .verify_password(password.as_bytes(), &parsed_hash)
+        .is_ok())
 }
 
 /// Verifica si un usuario existe por ID
@@ -317,3 +3458,174 @@ pub async fn user_exists(user_id: &str) -> Result<bool> {
     let db = init_db_manager();
     Ok(db.buscar_usuario(user_id).is_ok())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing::{create_test_user, test_env};
+
+    #[tokio::test]
+    async fn enlace_a_archivo_de_equipo() {
+        let _env = test_env().await;
+        let dueno = create_test_user();
+        let miembro = create_test_user();
+        let equipo = create_team(&dueno, "Equipo enlaces").await.unwrap();
+        let team_id = equipo.team.id.clone();
+        set_team_member(&dueno, &team_id, &miembro, TEAM_MEMBER)
+            .await
+            .unwrap();
+
+        let file_id = upload_file(
+            &miembro,
+            "text/plain",
+            Some("equipo.txt"),
+            None,
+            Some(&team_id),
+            &mut &b"contenido del equipo"[..],
+        )
+        .await
+        .unwrap();
+        let link = create_share_link(&miembro, &file_id, None, None, None)
+            .await
+            .unwrap();
+
+        let descarga = open_share_link(&link.token, None).await.unwrap();
+        assert_eq!(descarga.id, file_id);
+        assert_eq!(descarga.filename, "equipo.txt");
+
+        // Al salir del equipo el enlace deja de funcionar
+        remove_team_member(&dueno, &team_id, &miembro)
+            .await
+            .unwrap();
+        let error = open_share_link(&link.token, None).await.unwrap_err();
+        assert_eq!(error.to_string(), "Archivo no encontrado");
+    }
+
+    #[tokio::test]
+    async fn enlace_de_subida_limita_contrasenas() {
+        let _env = test_env().await;
+        let dueno = create_test_user();
+        let carpeta = create_folder(&dueno, "Buzón", None, None).await.unwrap();
+        let link = create_upload_link(
+            &dueno,
+            &carpeta.id,
+            None,
+            None,
+            None,
+            &[],
+            Some("secreta123"),
+        )
+        .await
+        .unwrap();
+
+        for _ in 0..LINK_PASSWORD_MAX_FAILURES {
+            let error = open_upload_link(&link.token, Some("otra-cosa"))
+                .await
+                .unwrap_err();
+            assert!(error.to_string().contains("incorrecta"), "{}", error);
+        }
+        // Agotados los intentos, ni la contraseña correcta se verifica
+        let error = open_upload_link(&link.token, Some("secreta123"))
+            .await
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("Demasiados intentos"),
+            "{}",
+            error
+        );
+
+        // Los intentos de cada tipo de enlace se llevan por separado
+        assert!(
+            check_password_attempts(LinkKind::Share, &link.token, Utc::now().timestamp()).is_ok()
+        );
+    }
+
+    #[test]
+    fn unique_entry_path_numera_repetidos() {
+        let mut usadas = HashSet::new();
+        assert_eq!(unique_entry_path(&mut usadas, "", "a.txt"), "a.txt");
+        assert_eq!(unique_entry_path(&mut usadas, "", "a.txt"), "a (1).txt");
+        assert_eq!(unique_entry_path(&mut usadas, "", "A.TXT"), "A (2).TXT");
+        // Un nombre que ya tiene la forma numerada también se respeta
+        assert_eq!(
+            unique_entry_path(&mut usadas, "", "a (1).txt"),
+            "a (1) (1).txt"
+        );
+
+        assert_eq!(unique_entry_path(&mut usadas, "", "LEEME"), "LEEME");
+        assert_eq!(unique_entry_path(&mut usadas, "", "LEEME"), "LEEME (1)");
+        assert_eq!(unique_entry_path(&mut usadas, "", ".env"), ".env");
+        assert_eq!(unique_entry_path(&mut usadas, "", ".env"), ".env (1)");
+        assert_eq!(
+            unique_entry_path(&mut usadas, "", "datos.tar.gz"),
+            "datos.tar.gz"
+        );
+        assert_eq!(
+            unique_entry_path(&mut usadas, "", "datos.tar.gz"),
+            "datos.tar (1).gz"
+        );
+
+        // Cada carpeta tiene sus propios nombres
+        assert_eq!(
+            unique_entry_path(&mut usadas, "docs/", "a.txt"),
+            "docs/a.txt"
+        );
+        assert_eq!(
+            unique_entry_path(&mut usadas, "docs/", "a.txt"),
+            "docs/a (1).txt"
+        );
+    }
+
+    #[tokio::test]
+    async fn write_archive_genera_un_zip_valido() {
+        let _env = test_env().await;
+        let user_id = create_test_user();
+        let archivos: [(&str, &[u8]); 3] = [
+            ("a.txt", b"primero"),
+            ("a.txt", b"segundo"),
+            ("LEEME", b"tercero"),
+        ];
+        let mut ids = Vec::new();
+        for (nombre, mut contenido) in archivos {
+            ids.push(
+                upload_file(
+                    &user_id,
+                    "text/plain",
+                    Some(nombre),
+                    None,
+                    None,
+                    &mut contenido,
+                )
+                .await
+                .unwrap(),
+            );
+        }
+
+        let (_, entradas) = prepare_archive(&user_id, &ids, None).await.unwrap();
+        let mut zip = Vec::new();
+        write_archive(entradas, &mut zip).await.unwrap();
+
+        let lector = async_zip::base::read::mem::ZipFileReader::new(zip)
+            .await
+            .unwrap();
+        let nombres: Vec<&str> = lector
+            .file()
+            .entries()
+            .iter()
+            .map(|entrada| entrada.filename().as_str().unwrap())
+            .collect();
+        assert_eq!(nombres, ["a.txt", "a (1).txt", "LEEME"]);
+
+        for (i, (_, esperado)) in archivos.into_iter().enumerate() {
+            let mut contenido = Vec::new();
+            lector
+                .reader_with_entry(i)
+                .await
+                .unwrap()
+                .read_to_end_checked(&mut contenido)
+                .await
+                .unwrap();
+            assert_eq!(contenido, esperado);
+        }
+    }
+}
@@ -0,0 +1,1162 @@
+use anyhow::{Context, Result, anyhow, bail};
+use async_compression::Level;
+use async_compression::tokio::bufread::ZstdDecoder;
+use async_compression::tokio::write::ZstdEncoder;
+use blake2::{Blake2b512, Digest};
+use chrono::Utc;
+use fastcdc::v2020::StreamCDC;
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, HashSet};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex, Weak};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+use tracing::{error, info, warn};
+
+use super::integrity::STATUS_CORRUPT;
+use super::{BlobReader, init_storage};
+use crate::core::cryptography::at_rest::{CHUNK_SIZE, init_master_key};
+use crate::core::database::init_db_manager;
+use crate::core::structs::{Blob, BlobChunk, NuevoChunk, NuevoFile};
+use crate::core::utils::{chunking_threshold, compression_level_for, staging_path};
+
+/// Contenido guardado tal cual (solo cifrado)
+pub const CODEC_NONE: &str = "none";
+/// Contenido comprimido con zstd antes de cifrarlo
+pub const CODEC_ZSTD: &str = "zstd";
+/// Contenido repartido en trozos (tabla `blob_chunks`), cada uno guardado
+/// aparte con su propio codec
+pub const CODEC_CHUNKED: &str = "chunked";
+
+/// Prefijo de las claves de los trozos en el storage, para distinguirlas de
+/// las de los blobs completos
+pub const CHUNK_KEY_PREFIX: &str = "chunk-";
+
+/// Tamaños mínimo, medio y máximo de los trozos que corta FastCDC
+const CDC_MIN_SIZE: u32 = 256 * 1024;
+const CDC_AVG_SIZE: u32 = 1024 * 1024;
+const CDC_MAX_SIZE: u32 = 4 * 1024 * 1024;
+
+/// Serializa los cambios de referencias y de uso en DB, para que la cuota se
+/// compruebe y se consuma de forma atómica. No se mantiene durante la E/S
+/// del storage, y nunca se pide un lock de clave mientras se tiene.
+pub(super) static BLOB_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// Locks por clave del storage (el hash de un blob o `chunk-<hash>`). Una
+/// subida y un borrado del mismo contenido se serializan con el lock de su
+/// clave mientras escriben o borran en el storage; contenidos distintos no
+/// se esperan entre sí.
+///
+/// Orden para tomarlos: primero la clave del blob y después las de sus
+/// trozos, ordenadas.
+static KEY_LOCKS: Lazy<StdMutex<HashMap<String, Weak<Mutex<()>>>>> = Lazy::new(Default::default);
+
+/// Toma el lock de una clave del storage
+pub(super) async fn lock_key(key: &str) -> OwnedMutexGuard<()> {
+    let lock = {
+        let mut locks = KEY_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+        // Las claves sin nadie que las use no se conservan
+        locks.retain(|_, lock| lock.strong_count() > 0);
+        match locks.get(key).and_then(Weak::upgrade) {
+            Some(lock) => lock,
+            None => {
+                let lock = Arc::new(Mutex::new(()));
+                locks.insert(key.to_string(), Arc::downgrade(&lock));
+                lock
+            }
+        }
+    };
+    lock.lock_owned().await
+}
+
+/// Cómo se registra en DB el contenido publicado
+#[derive(Clone, Copy)]
+enum Registro {
+    /// Archivo nuevo
+    Archivo,
+    /// Nueva versión de un archivo existente (`nuevo.id`)
+    Version,
+}
+
+/// Contenido ya preparado fuera del lock para publicarlo
+enum Preparado {
+    /// Blob completo, comprimido y cifrado con el codec indicado
+    Completo(&'static str),
+    /// Blob repartido en trozos
+    Troceado(Troceado),
+}
+
+/// Reparto de un archivo en trozos definidos por su contenido
+struct Troceado {
+    /// Trozos en el orden en que aparecen en el archivo
+    partes: Vec<Parte>,
+    /// Trozos que faltaban en el storage, ya comprimidos y cifrados, con su codec
+    preparados: HashMap<String, (PathBuf, &'static str)>,
+}
+
+/// Trozo del archivo: su hash, dónde empieza y cuánto mide
+struct Parte {
+    hash: String,
+    offset: u64,
+    size: u64,
+}
+
+/// Clave del storage de un trozo
+pub fn chunk_key(hash: &str) -> String {
+    format!("{}{}", CHUNK_KEY_PREFIX, hash)
+}
+
+/// Registra un archivo y publica su contenido cifrado en el storage,
+/// indexado por el hash del texto plano.
+///
+/// Si ya existe un blob con el mismo hash solo se suma una referencia y el
+/// temporal se descarta. Si falla la publicación se hace rollback en DB.
+///
+/// Con `cuota` se rechaza el archivo si el uso del dueño más su tamaño la supera;
+/// la comprobación se hace bajo el mismo lock que el registro.
+///
+/// `sha256` (hex) se guarda en el blob para el header `Content-Digest`.
+pub async fn store_file(
+    nuevo: &NuevoFile<'_>,
+    sha256: &str,
+    staged: &Path,
+    cuota: Option<u64>,
+) -> Result<()> {
+    store(nuevo, sha256, staged, cuota, Registro::Archivo).await
+}
+
+/// Igual que `store_file`, pero el contenido pasa a ser la nueva versión
+/// actual del archivo `nuevo.id`; la anterior queda en su historial.
+pub async fn store_file_version(
+    nuevo: &NuevoFile<'_>,
+    sha256: &str,
+    staged: &Path,
+    cuota: Option<u64>,
+) -> Result<()> {
+    store(nuevo, sha256, staged, cuota, Registro::Version).await
+}
+
+async fn store(
+    nuevo: &NuevoFile<'_>,
+    sha256: &str,
+    staged: &Path,
+    cuota: Option<u64>,
+    registro: Registro,
+) -> Result<()> {
+    let cifrado = staged.with_extension("enc");
+    let troceado = should_chunk(nuevo.size);
+
+    // Trocear, comprimir y cifrar fuera del lock, salvo que el contenido ya
+    // esté almacenado y sano
+    let mut preparado = None;
+    if !blob_available(nuevo.hash).await {
+        let resultado = if troceado {
+            let reescribir = blob_corrupt(nuevo.hash);
+            prepare_chunks(staged, nuevo.mime, reescribir)
+                .await
+                .map(Preparado::Troceado)
+        } else {
+            prepare_blob(staged, &cifrado, nuevo.mime)
+                .await
+                .map(Preparado::Completo)
+        };
+        match resultado {
+            Ok(p) => preparado = Some(p),
+            Err(e) => {
+                let _ = tokio::fs::remove_file(staged).await;
+                let _ = tokio::fs::remove_file(&cifrado).await;
+                return Err(e);
+            }
+        }
+    }
+
+    let resultado = publish_file(
+        nuevo,
+        sha256,
+        staged,
+        &cifrado,
+        preparado.as_ref(),
+        cuota,
+        registro,
+    )
+    .await;
+
+    // El texto plano nunca se publica; los cifrados ya fueron movidos si se usaron
+    let _ = tokio::fs::remove_file(staged).await;
+    let _ = tokio::fs::remove_file(&cifrado).await;
+    if let Some(Preparado::Troceado(troceado)) = &preparado {
+        discard_chunks(troceado).await;
+    }
+    resultado
+}
+
+async fn publish_file(
+    nuevo: &NuevoFile<'_>,
+    sha256: &str,
+    staged: &Path,
+    cifrado: &Path,
+    preparado: Option<&Preparado>,
+    cuota: Option<u64>,
+    registro: Registro,
+) -> Result<()> {
+    let _clave = lock_key(nuevo.hash).await;
+    let db = init_db_manager();
+    let storage = init_storage();
+
+    let es_nuevo = {
+        let _guard = BLOB_LOCK.lock().await;
+        check_cuota(nuevo.owner_id, nuevo.size, cuota)?;
+        match registro {
+            Registro::Archivo => db.insertar_file_con_blob(nuevo),
+            Registro::Version => db.insertar_version_file(nuevo),
+        }
+        .context("Error al insertar archivo en la base de datos")?
+    };
+
+    // Los blobs anteriores a los digests se completan con la primera subida
+    if let Err(e) = db.completar_sha256_blob(nuevo.hash, sha256) {
+        warn!(
+            "No se pudo registrar el SHA-256 del blob {}: {}",
+            nuevo.hash, e
+        );
+    }
+
+    // Aunque la referencia ya existiera, el blob podría faltar en el storage
+    // o estar marcado como corrupto; en ese caso se reescribe con este contenido
+    if blob_available(nuevo.hash).await {
+        info!(
+            "Blob {} ya almacenado, archivo {} deduplicado",
+            nuevo.hash, nuevo.id
+        );
+        return Ok(());
+    }
+
+    if !es_nuevo {
+        warn!(
+            "Blob {} referenciado pero ausente o dañado, se vuelve a escribir",
+            nuevo.hash
+        );
+    }
+
+    let troceado = should_chunk(nuevo.size);
+    let anterior = db
+        .buscar_blob(nuevo.hash)
+        .context("Error al consultar el blob")?
+        .codec;
+    let publicado = async {
+        match preparado {
+            Some(Preparado::Troceado(t)) => publish_chunks(nuevo, staged, t).await?,
+            None if troceado => {
+                // Los trozos que falten se preparan al publicarlos
+                let t = Troceado {
+                    partes: plan_chunks(staged).await?,
+                    preparados: HashMap::new(),
+                };
+                publish_chunks(nuevo, staged, &t).await?
+            }
+            otro => {
+                let codec = match otro {
+                    Some(Preparado::Completo(codec)) => codec,
+                    _ => prepare_blob(staged, cifrado, nuevo.mime).await?,
+                };
+                // El codec se registra antes de escribir para que nunca se lea
+                // el contenido nuevo con el codec anterior
+                db.actualizar_codec_blob(nuevo.hash, codec)
+                    .context("Error al registrar el codec del blob")?;
+                storage.put_file(nuevo.hash, cifrado).await?;
+            }
+        }
+        Ok::<(), anyhow::Error>(())
+    }
+    .await;
+
+    if let Err(e) = publicado {
+        // Rollback: quitar el registro (o la versión) y la referencia
+        error!("Error al publicar blob, haciendo rollback en DB");
+        let _guard = BLOB_LOCK.lock().await;
+        let _ = match registro {
+            Registro::Archivo => db.borrar_file_con_blob(nuevo.id).map(|_| ()),
+            Registro::Version => db.deshacer_version_file(nuevo.id).map(|_| ()),
+        };
+        return Err(e).context("Error al guardar archivo en el storage (rollback ejecutado)");
+    }
+
+    if !es_nuevo {
+        let _ = db.marcar_blob_cifrado(nuevo.hash);
+        let _ = db.borrar_problema_integridad(nuevo.hash);
+
+        // Se descarta la representación anterior del contenido
+        if anterior == CODEC_CHUNKED && !troceado {
+            release_chunks(nuevo.hash).await;
+        } else if anterior != CODEC_CHUNKED && troceado {
+            let _ = storage.delete(nuevo.hash).await;
+        }
+    }
+    Ok(())
+}
+
+/// Indica si un contenido de `size` bytes se guarda por trozos
+fn should_chunk(size: i64) -> bool {
+    chunking_threshold().is_some_and(|minimo| size.max(0) as u64 >= minimo)
+}
+
+/// Publica los trozos de `t` que falten en el storage y registra el
+/// manifiesto del blob. Debe llamarse con el lock de la clave del blob
+/// tomado; los de sus trozos se toman aquí hasta registrar el manifiesto.
+///
+/// Si el blob estaba marcado como corrupto se reescriben todos sus trozos,
+/// porque el dañado podría ser cualquiera de ellos.
+async fn publish_chunks(nuevo: &NuevoFile<'_>, staged: &Path, t: &Troceado) -> Result<()> {
+    let db = init_db_manager();
+    let storage = init_storage();
+    let reescribir = blob_corrupt(nuevo.hash);
+
+    let mut claves: Vec<String> = t.partes.iter().map(|p| chunk_key(&p.hash)).collect();
+    claves.sort();
+    claves.dedup();
+    let mut locks = Vec::with_capacity(claves.len());
+    for clave in &claves {
+        locks.push(lock_key(clave).await);
+    }
+
+    let mut escritos = Vec::new();
+    let mut vistos = HashSet::new();
+    for (i, parte) in t.partes.iter().enumerate() {
+        // Un trozo preparado fuera del lock pudo publicarse mientras tanto
+        if !vistos.insert(parte.hash.as_str())
+            || (!reescribir && chunk_available(&parte.hash).await)
+        {
+            continue;
+        }
+
+        let codec = match t.preparados.get(&parte.hash) {
+            Some((cifrado, codec)) => {
+                storage.put_file(&chunk_key(&parte.hash), cifrado).await?;
+                *codec
+            }
+            None => {
+                let cifrado = staged.with_extension(format!("{}.enc", i));
+                let publicado = async {
+                    let codec = prepare_chunk(staged, parte, &cifrado, nuevo.mime).await?;
+                    storage.put_file(&chunk_key(&parte.hash), &cifrado).await?;
+                    Ok::<_, anyhow::Error>(codec)
+                }
+                .await;
+                let _ = tokio::fs::remove_file(&cifrado).await;
+                publicado?
+            }
+        };
+        escritos.push(NuevoChunk {
+            hash: &parte.hash,
+            size: parte.size as i64,
+            codec,
+            refcount: 0,
+        });
+    }
+
+    let manifiesto: Vec<BlobChunk> = t
+        .partes
+        .iter()
+        .enumerate()
+        .map(|(i, parte)| BlobChunk {
+            blob_hash: nuevo.hash.to_string(),
+            seq: i as i32,
+            chunk_hash: parte.hash.clone(),
+            byte_offset: parte.offset as i64,
+            size: parte.size as i64,
+        })
+        .collect();
+    let huerfanos = {
+        let _guard = BLOB_LOCK.lock().await;
+        db.registrar_chunks_blob(nuevo.hash, &escritos, &manifiesto)
+            .context("Error al registrar los trozos del blob")?
+    };
+    // Los huérfanos no son de este manifiesto; sus locks se toman de a uno
+    drop(locks);
+    for hash in huerfanos {
+        delete_chunk(&hash).await;
+    }
+
+    info!(
+        "Blob {} guardado en {} trozo(s), {} nuevo(s)",
+        nuevo.hash,
+        manifiesto.len(),
+        escritos.len()
+    );
+    Ok(())
+}
+
+/// Rechaza `size` bytes más si el uso del dueño superaría `cuota`.
+/// Debe llamarse con `BLOB_LOCK` tomado, junto con el registro que consume
+/// la cuota.
+fn check_cuota(owner_id: &str, size: i64, cuota: Option<u64>) -> Result<()> {
+    let Some(cuota) = cuota else {
+        return Ok(());
+    };
+
+    let usado = init_db_manager()
+        .buscar_uso_dueno(owner_id)
+        .context("Error al consultar el uso del dueño")?
+        .max(0) as u64;
+    if usado + size.max(0) as u64 > cuota {
+        bail!(
+            "El archivo excede la cuota de almacenamiento ({} de {} bytes usados)",
+            usado,
+            cuota
+        );
+    }
+    Ok(())
+}
+
+/// Indica si el blob existe en el storage y no está marcado como corrupto
+async fn blob_available(hash: &str) -> bool {
+    if blob_corrupt(hash) {
+        return false;
+    }
+    match init_db_manager().buscar_blob(hash) {
+        Ok(blob) => blob_stored(&blob).await.unwrap_or(false),
+        // Sin registro solo puede reaprovecharse un objeto completo
+        Err(_) => init_storage().exists(hash).await.unwrap_or(false),
+    }
+}
+
+/// Indica si el scrubber marcó el blob como corrupto
+fn blob_corrupt(hash: &str) -> bool {
+    matches!(
+        init_db_manager().buscar_problema_integridad(hash),
+        Ok(Some(problema)) if problema.status == STATUS_CORRUPT
+    )
+}
+
+/// Indica si el contenido del blob está en el storage: el objeto completo o,
+/// si se guardó por trozos, todos sus trozos
+pub async fn blob_stored(blob: &Blob) -> Result<bool> {
+    let storage = init_storage();
+    if blob.codec != CODEC_CHUNKED {
+        return storage.exists(&blob.hash).await;
+    }
+
+    let manifiesto = init_db_manager().obtener_manifiesto_blob(&blob.hash)?;
+    if manifiesto.is_empty() {
+        return Ok(false);
+    }
+    let trozos: HashSet<String> = manifiesto.into_iter().map(|(_, c)| c.hash).collect();
+    for hash in trozos {
+        if !storage.exists(&chunk_key(&hash)).await? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Indica si el trozo está registrado y existe en el storage
+async fn chunk_available(hash: &str) -> bool {
+    init_db_manager().buscar_chunk(hash).is_ok()
+        && init_storage()
+            .exists(&chunk_key(hash))
+            .await
+            .unwrap_or(false)
+}
+
+/// Abre un blob completo para lectura, descifrándolo si corresponde
+pub async fn open_blob(hash: &str) -> Result<BlobReader> {
+    open_blob_range(hash, 0, u64::MAX).await
+}
+
+/// Abre `len` bytes del contenido original de un blob a partir de `start`.
+///
+/// Sin compresión se leen del storage solo los bloques necesarios. Los blobs
+/// comprimidos con zstd se descomprimen desde el principio, descartando lo
+/// anterior a `start`. Los guardados por trozos solo leen los trozos que se
+/// solapan con el rango.
+pub async fn open_blob_range(hash: &str, start: u64, len: u64) -> Result<BlobReader> {
+    let blob = init_db_manager()
+        .buscar_blob(hash)
+        .with_context(|| format!("Blob {} no encontrado", hash))?;
+
+    if blob.codec == CODEC_CHUNKED {
+        return open_chunked_range(hash, start, len).await;
+    }
+    open_encoded_range(hash, blob.encrypted, &blob.codec, start, len).await
+}
+
+/// Abre `len` bytes del contenido original guardado en `key` con `codec`
+async fn open_encoded_range(
+    key: &str,
+    encrypted: bool,
+    codec: &str,
+    start: u64,
+    len: u64,
+) -> Result<BlobReader> {
+    match codec {
+        CODEC_NONE => open_stored_range(key, encrypted, start, len).await,
+        CODEC_ZSTD => {
+            let almacenado = open_stored_range(key, encrypted, 0, u64::MAX).await?;
+            let mut decoder = ZstdDecoder::new(BufReader::new(almacenado));
+            tokio::io::copy(&mut (&mut decoder).take(start), &mut tokio::io::sink())
+                .await
+                .with_context(|| format!("Error al descomprimir blob {}", key))?;
+            Ok(Box::new(decoder.take(len)))
+        }
+        otro => bail!("Codec '{}' desconocido en el blob {}", otro, key),
+    }
+}
+
+/// Abre un rango de un blob guardado por trozos. El primer trozo se abre
+/// antes de devolver el stream, para que un fallo se informe como error; el
+/// resto se encadena en una tarea aparte.
+async fn open_chunked_range(hash: &str, start: u64, len: u64) -> Result<BlobReader> {
+    let manifiesto = init_db_manager()
+        .obtener_manifiesto_blob(hash)
+        .with_context(|| format!("Error al leer los trozos del blob {}", hash))?;
+    if manifiesto.is_empty() {
+        bail!("Trozos del blob {} no encontrados", hash);
+    }
+
+    let fin = start.saturating_add(len);
+    let mut tramos = manifiesto.into_iter().filter_map(|(parte, chunk)| {
+        let inicio = parte.byte_offset as u64;
+        let final_trozo = inicio + parte.size as u64;
+        if final_trozo <= start || inicio >= fin {
+            return None;
+        }
+        let desde = start.saturating_sub(inicio);
+        Some((
+            chunk.hash,
+            chunk.codec,
+            desde,
+            fin.min(final_trozo) - inicio - desde,
+        ))
+    });
+
+    let Some((chunk, codec, desde, cantidad)) = tramos.next() else {
+        return Ok(Box::new(tokio::io::empty()));
+    };
+    let mut primero = open_encoded_range(&chunk_key(&chunk), true, &codec, desde, cantidad).await?;
+    let resto: Vec<_> = tramos.collect();
+
+    let (mut tx, rx) = tokio::io::duplex(CHUNK_SIZE);
+    let hash = hash.to_string();
+    tokio::spawn(async move {
+        let resultado = async {
+            tokio::io::copy(&mut primero, &mut tx).await?;
+            for (chunk, codec, desde, cantidad) in resto {
+                let mut reader =
+                    open_encoded_range(&chunk_key(&chunk), true, &codec, desde, cantidad).await?;
+                tokio::io::copy(&mut reader, &mut tx).await?;
+            }
+            Ok::<(), anyhow::Error>(())
+        }
+        .await;
+        if let Err(e) = resultado {
+            warn!("Lectura del blob {} interrumpida: {}", hash, e);
+        }
+    });
+
+    Ok(Box::new(rx))
+}
+
+/// Abre `len` bytes del contenido almacenado en `key` (ya descifrado, pero
+/// todavía comprimido si corresponde) a partir de `start`. El contenido
+/// cifrado se descifra en una tarea aparte que alimenta el stream devuelto.
+async fn open_stored_range(key: &str, encrypted: bool, start: u64, len: u64) -> Result<BlobReader> {
+    let storage = init_storage();
+
+    if !encrypted {
+        let reader = storage.stream_from(key, start).await?;
+        return Ok(Box::new(reader.take(len)));
+    }
+
+    let header = {
+        let mut reader = storage.stream(key).await?;
+        init_master_key()
+            .read_header(&mut reader)
+            .await
+            .with_context(|| format!("Error al descifrar blob {}", key))?
+    };
+
+    let chunk = header.chunk_size() as u64;
+    let index = start / chunk;
+    let first_index = u32::try_from(index).map_err(|_| anyhow!("Rango fuera del blob {}", key))?;
+    let mut reader = storage.stream_from(key, header.chunk_offset(index)).await?;
+
+    let (mut tx, rx) = tokio::io::duplex(CHUNK_SIZE);
+    let key = key.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = header
+            .decrypt_chunks(&mut reader, &mut tx, first_index, start % chunk, len)
+            .await
+        {
+            warn!("Lectura del blob {} interrumpida: {}", key, e);
+        }
+    });
+
+    Ok(Box::new(rx))
+}
+
+/// Prepara el contenido de `staged` para publicarlo en `cifrado`: lo
+/// comprime con zstd si `mime` es comprimible y así ocupa menos, y lo cifra.
+///
+/// # Retorna
+/// El codec aplicado (`CODEC_ZSTD` o `CODEC_NONE`)
+async fn prepare_blob(staged: &Path, cifrado: &Path, mime: &str) -> Result<&'static str> {
+    if let Some(nivel) = compression_level_for(mime) {
+        let comprimido = staged.with_extension("zst");
+        let resultado = match compress_file(staged, &comprimido, nivel).await {
+            Ok((original, reducido)) if reducido < original => {
+                info!(
+                    "Contenido comprimido con zstd: {} -> {} bytes",
+                    original, reducido
+                );
+                Some(encrypt_file(&comprimido, cifrado).await)
+            }
+            Ok(_) => None,
+            Err(e) => {
+                warn!(
+                    "No se pudo comprimir {:?}, se guarda sin comprimir: {}",
+                    staged, e
+                );
+                None
+            }
+        };
+        let _ = tokio::fs::remove_file(&comprimido).await;
+
+        if let Some(resultado) = resultado {
+            resultado?;
+            return Ok(CODEC_ZSTD);
+        }
+    }
+
+    encrypt_file(staged, cifrado).await?;
+    Ok(CODEC_NONE)
+}
+
+/// Reparte el contenido de `staged` en trozos con FastCDC y calcula el
+/// Blake2b512 de cada uno
+async fn plan_chunks(staged: &Path) -> Result<Vec<Parte>> {
+    let path = staged.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let archivo =
+            std::fs::File::open(&path).with_context(|| format!("No se pudo abrir {:?}", path))?;
+        let mut partes = Vec::new();
+        for trozo in StreamCDC::new(archivo, CDC_MIN_SIZE, CDC_AVG_SIZE, CDC_MAX_SIZE) {
+            let trozo = trozo.with_context(|| format!("Error al trocear {:?}", path))?;
+            partes.push(Parte {
+                hash: format!("{:x}", Blake2b512::digest(&trozo.data)),
+                offset: trozo.offset,
+                size: trozo.length as u64,
+            });
+        }
+        Ok(partes)
+    })
+    .await?
+}
+
+/// Trocea `staged` y prepara los trozos que todavía no están en el storage
+/// (todos con `reescribir`)
+async fn prepare_chunks(staged: &Path, mime: &str, reescribir: bool) -> Result<Troceado> {
+    let mut troceado = Troceado {
+        partes: plan_chunks(staged).await?,
+        preparados: HashMap::new(),
+    };
+
+    let resultado = async {
+        for (i, parte) in troceado.partes.iter().enumerate() {
+            if troceado.preparados.contains_key(&parte.hash)
+                || (!reescribir && chunk_available(&parte.hash).await)
+            {
+                continue;
+            }
+            let cifrado = staged.with_extension(format!("{}.enc", i));
+            let codec = prepare_chunk(staged, parte, &cifrado, mime).await;
+            let codec = match codec {
+                Ok(codec) => codec,
+                Err(e) => {
+                    let _ = tokio::fs::remove_file(&cifrado).await;
+                    return Err(e);
+                }
+            };
+            troceado
+                .preparados
+                .insert(parte.hash.clone(), (cifrado, codec));
+        }
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = resultado {
+        discard_chunks(&troceado).await;
+        return Err(e);
+    }
+    Ok(troceado)
+}
+
+/// Copia el trozo `parte` de `staged` a un temporal y lo prepara en `cifrado`
+/// como un blob más
+async fn prepare_chunk(
+    staged: &Path,
+    parte: &Parte,
+    cifrado: &Path,
+    mime: &str,
+) -> Result<&'static str> {
+    let temporal = cifrado.with_extension("tmp");
+    let resultado = async {
+        let mut entrada = tokio::fs::File::open(staged)
+            .await
+            .with_context(|| format!("No se pudo abrir {:?}", staged))?;
+        entrada.seek(SeekFrom::Start(parte.offset)).await?;
+        let mut salida = tokio::fs::File::create(&temporal)
+            .await
+            .with_context(|| format!("No se pudo crear el archivo {:?}", temporal))?;
+        tokio::io::copy(&mut entrada.take(parte.size), &mut salida).await?;
+        salida.sync_all().await?;
+
+        prepare_blob(&temporal, cifrado, mime).await
+    }
+    .await;
+
+    let _ = tokio::fs::remove_file(&temporal).await;
+    resultado
+}
+
+/// Borra los trozos preparados que no llegaron a publicarse
+async fn discard_chunks(troceado: &Troceado) {
+    for (cifrado, _) in troceado.preparados.values() {
+        let _ = tokio::fs::remove_file(cifrado).await;
+    }
+}
+
+/// Comprime un archivo local completo en `dst` con zstd al nivel `nivel`
+///
+/// # Retorna
+/// El tamaño original y el comprimido
+async fn compress_file(src: &Path, dst: &Path, nivel: i32) -> Result<(u64, u64)> {
+    let mut entrada = tokio::fs::File::open(src)
+        .await
+        .with_context(|| format!("No se pudo abrir {:?}", src))?;
+    let salida = tokio::fs::File::create(dst)
+        .await
+        .with_context(|| format!("No se pudo crear el archivo {:?}", dst))?;
+    let mut encoder = ZstdEncoder::with_quality(BufWriter::new(salida), Level::Precise(nivel));
+
+    let original = tokio::io::copy(&mut entrada, &mut encoder)
+        .await
+        .with_context(|| format!("Error al comprimir {:?}", src))?;
+    encoder.shutdown().await?;
+
+    let salida = encoder.into_inner().into_inner();
+    salida.sync_all().await?;
+    let reducido = salida.metadata().await?.len();
+    Ok((original, reducido))
+}
+
+/// Cifra un archivo local completo en `dst`
+async fn encrypt_file(src: &Path, dst: &Path) -> Result<()> {
+    let mut entrada = tokio::fs::File::open(src)
+        .await
+        .with_context(|| format!("No se pudo abrir {:?}", src))?;
+    let salida = tokio::fs::File::create(dst)
+        .await
+        .with_context(|| format!("No se pudo crear el archivo {:?}", dst))?;
+    let mut salida = BufWriter::new(salida);
+
+    init_master_key()
+        .encrypt_stream(&mut entrada, &mut salida)
+        .await
+        .with_context(|| format!("Error al cifrar {:?}", src))?;
+    salida.into_inner().sync_all().await?;
+    Ok(())
+}
+
+/// Borra el registro de un archivo con sus versiones y los blobs que se
+/// queden sin referencias.
+pub async fn release_file(file_id: &str) -> Result<()> {
+    let huerfanos = {
+        let _guard = BLOB_LOCK.lock().await;
+        init_db_manager()
+            .borrar_file_con_blob(file_id)
+            .context("Error al eliminar archivo de la base de datos")?
+    };
+
+    for hash in huerfanos {
+        delete_unreferenced(&hash).await;
+    }
+    Ok(())
+}
+
+/// Borra una versión anterior de un archivo y, si era la última referencia,
+/// su blob.
+pub async fn release_version(file_id: &str, version: i32) -> Result<()> {
+    let huerfano = {
+        let _guard = BLOB_LOCK.lock().await;
+        init_db_manager()
+            .borrar_version_file(file_id, version)
+            .context("Error al eliminar la versión de la base de datos")?
+    };
+
+    if let Some(hash) = huerfano {
+        delete_unreferenced(&hash).await;
+    }
+    Ok(())
+}
+
+/// Registra una copia de un archivo que comparte el blob del original: solo
+/// se suma una referencia y el tamaño al uso del dueño, sin escribir nada
+/// en el storage.
+pub async fn copy_file(nuevo: &NuevoFile<'_>, cuota: Option<u64>) -> Result<()> {
+    let _guard = BLOB_LOCK.lock().await;
+    let db = init_db_manager();
+
+    // Bajo el lock el blob no puede desaparecer entre la comprobación y el registro
+    db.buscar_blob(nuevo.hash)
+        .context("Blob del archivo original no encontrado")?;
+    check_cuota(nuevo.owner_id, nuevo.size, cuota)?;
+
+    db.insertar_file_con_blob(nuevo)
+        .context("Error al registrar la copia en la base de datos")?;
+    Ok(())
+}
+
+/// Convierte una versión anterior en la actual. Como la promovida sigue en
+/// el historial, su tamaño vuelve a contar para la cuota.
+pub async fn promote_version(
+    file_id: &str,
+    owner_id: &str,
+    version: i32,
+    cuota: Option<u64>,
+) -> Result<()> {
+    let _guard = BLOB_LOCK.lock().await;
+    let db = init_db_manager();
+
+    let promovida = db
+        .buscar_version_file(file_id, version)
+        .context("Error al buscar la versión")?;
+    check_cuota(owner_id, promovida.size, cuota)?;
+
+    db.promover_version_file(file_id, version, Utc::now().timestamp())
+        .context("Error al promover la versión")?;
+    Ok(())
+}
+
+/// Borra del storage un blob que se quedó sin referencias, junto con los
+/// trozos que solo usaba él. No se borra nada si mientras tanto una subida
+/// volvió a registrar el mismo contenido. Debe llamarse sin `BLOB_LOCK`.
+pub(super) async fn delete_unreferenced(hash: &str) {
+    let _clave = lock_key(hash).await;
+    if init_db_manager().buscar_blob(hash).is_ok() {
+        info!("Blob {} registrado de nuevo, no se elimina", hash);
+        return;
+    }
+    match init_storage().delete(hash).await {
+        Ok(_) => info!("Blob {} eliminado del storage (sin referencias)", hash),
+        Err(e) => warn!("Blob {} sin referencias pero no eliminado: {}", hash, e),
+    }
+    release_chunks(hash).await;
+}
+
+/// Suelta el manifiesto de trozos de un blob y borra los trozos que se
+/// quedan sin referencias. Debe llamarse sin `BLOB_LOCK`.
+pub(super) async fn release_chunks(hash: &str) {
+    let liberados = {
+        let _guard = BLOB_LOCK.lock().await;
+        init_db_manager().liberar_chunks_de_blob(hash)
+    };
+    match liberados {
+        Ok(huerfanos) => {
+            for chunk in huerfanos {
+                delete_chunk(&chunk).await;
+            }
+        }
+        Err(e) => warn!("No se pudieron liberar los trozos del blob {}: {}", hash, e),
+    }
+}
+
+/// Borra del storage un trozo que se quedó sin referencias, salvo que otra
+/// subida lo haya vuelto a registrar. Debe llamarse sin `BLOB_LOCK`.
+pub(super) async fn delete_chunk(hash: &str) {
+    let _clave = lock_key(&chunk_key(hash)).await;
+    if init_db_manager().buscar_chunk(hash).is_ok() {
+        return;
+    }
+    match init_storage().delete(&chunk_key(hash)).await {
+        Ok(_) => info!("Trozo {} eliminado del storage (sin referencias)", hash),
+        Err(e) => warn!("Trozo {} sin referencias pero no eliminado: {}", hash, e),
+    }
+}
+
+/// Mueve los blobs antiguos (`{file_id}.st`) a su clave por hash.
+///
+/// Idempotente: se puede ejecutar en cada arranque.
+pub async fn migrate_legacy_blobs() -> Result<()> {
+    let db = init_db_manager();
+    let storage = init_storage();
+    let files = db
+        .obtener_todos_los_files()
+        .context("Error al listar archivos para migrar blobs")?;
+
+    let mut migrados = 0;
+    for file in files {
+        if !storage.exists(&file.id).await? {
+            continue;
+        }
+
+        let _clave = lock_key(&file.hash).await;
+        if !storage.exists(&file.hash).await? {
+            let mut reader = storage.stream(&file.id).await?;
+            let size = storage.put(&file.hash, &mut reader).await?;
+            let _ = db.actualizar_tamano_blob(&file.hash, size as i64);
+        }
+        storage.delete(&file.id).await?;
+        migrados += 1;
+    }
+
+    if migrados > 0 {
+        info!("{} blob(s) migrados al almacenamiento por hash", migrados);
+    }
+    Ok(())
+}
+
+/// Indica si el contenido guardado en `hash` empieza con una cabecera de
+/// blob cifrado que la clave maestra puede abrir
+async fn already_encrypted(hash: &str) -> bool {
+    match init_storage().stream(hash).await {
+        Ok(mut reader) => init_master_key().read_header(&mut reader).await.is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Cifra los blobs que todavía están en texto plano (anteriores al cifrado en reposo).
+///
+/// Idempotente: se puede ejecutar en cada arranque. Un blob que ya está
+/// cifrado pero sin marcar (por un corte a mitad de la migración) no se
+/// vuelve a cifrar.
+pub async fn encrypt_existing_blobs() -> Result<()> {
+    let db = init_db_manager();
+    let storage = init_storage();
+    let pendientes = db
+        .obtener_blobs_sin_cifrar()
+        .context("Error al listar blobs sin cifrar")?;
+
+    if pendientes.is_empty() {
+        return Ok(());
+    }
+    info!("Cifrando {} blob(s) existentes...", pendientes.len());
+
+    let mut cifrados = 0;
+    for blob in pendientes {
+        let _clave = lock_key(&blob.hash).await;
+        if !storage.exists(&blob.hash).await? {
+            warn!("Blob {} no existe en el storage, no se cifra", blob.hash);
+            continue;
+        }
+
+        // Un arranque anterior pudo cortarse entre publicar el cifrado y
+        // marcarlo: si ya tiene una cabecera válida solo falta el registro
+        if already_encrypted(&blob.hash).await {
+            match db.marcar_blob_cifrado(&blob.hash) {
+                Ok(_) => {
+                    info!("Blob {} ya estaba cifrado, solo se marca", blob.hash);
+                    cifrados += 1;
+                }
+                Err(e) => error!("Error al marcar blob {} como cifrado: {}", blob.hash, e),
+            }
+            continue;
+        }
+
+        let destino = staging_path().join(format!("{}.enc", blob.hash));
+        let resultado = async {
+            let mut reader = storage.stream(&blob.hash).await?;
+            let salida = tokio::fs::File::create(&destino).await?;
+            let mut salida = BufWriter::new(salida);
+            init_master_key()
+                .encrypt_stream(&mut reader, &mut salida)
+                .await?;
+            salida.into_inner().sync_all().await?;
+            storage.put_file(&blob.hash, &destino).await?;
+            db.marcar_blob_cifrado(&blob.hash)?;
+            Ok::<(), anyhow::Error>(())
+        }
+        .await;
+
+        match resultado {
+            Ok(_) => cifrados += 1,
+            Err(e) => {
+                error!("Error al cifrar blob {}: {}", blob.hash, e);
+                let _ = tokio::fs::remove_file(&destino).await;
+            }
+        }
+    }
+
+    info!("{} blob(s) cifrados en reposo", cifrados);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::procedures::upload_file;
+    use crate::core::testing::{TempDir, create_test_user, test_env};
+
+    /// Bytes pseudoaleatorios reproducibles (xorshift64), para que FastCDC
+    /// encuentre cortes como en un archivo real
+    fn contenido(len: usize, semilla: u64) -> Vec<u8> {
+        let mut estado = semilla;
+        (0..len)
+            .map(|_| {
+                estado ^= estado << 13;
+                estado ^= estado >> 7;
+                estado ^= estado << 17;
+                (estado >> 24) as u8
+            })
+            .collect()
+    }
+
+    async fn trocear(dir: &TempDir, nombre: &str, data: &[u8]) -> Vec<Parte> {
+        let path = dir.0.join(nombre);
+        tokio::fs::write(&path, data).await.unwrap();
+        plan_chunks(&path).await.unwrap()
+    }
+
+    async fn leer(reader: BlobReader) -> Vec<u8> {
+        let mut reader = reader;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await.unwrap();
+        data
+    }
+
+    #[tokio::test]
+    async fn plan_chunks_respeta_los_limites() {
+        let dir = TempDir::new();
+        let data = contenido(12 * 1024 * 1024, 1);
+        let partes = trocear(&dir, "a.bin", &data).await;
+        assert!(partes.len() > 1);
+
+        let mut offset = 0;
+        for (i, parte) in partes.iter().enumerate() {
+            assert_eq!(parte.offset, offset, "trozo {} no es contiguo", i);
+            assert!(parte.size <= CDC_MAX_SIZE as u64, "trozo {} muy grande", i);
+            if i + 1 < partes.len() {
+                assert!(parte.size >= CDC_MIN_SIZE as u64, "trozo {} muy chico", i);
+            }
+            let bytes = &data[offset as usize..(offset + parte.size) as usize];
+            assert_eq!(parte.hash, format!("{:x}", Blake2b512::digest(bytes)));
+            offset += parte.size;
+        }
+        assert_eq!(offset, data.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn insertar_al_principio_cambia_pocos_trozos() {
+        let dir = TempDir::new();
+        let original = contenido(12 * 1024 * 1024, 2);
+        let mut editado = original[..1000].to_vec();
+        editado.extend_from_slice(b"bytes insertados cerca del comienzo");
+        editado.extend_from_slice(&original[1000..]);
+
+        let antes = trocear(&dir, "original.bin", &original).await;
+        let despues = trocear(&dir, "editado.bin", &editado).await;
+        let conocidos: HashSet<&str> = antes.iter().map(|p| p.hash.as_str()).collect();
+        let nuevos = despues
+            .iter()
+            .filter(|p| !conocidos.contains(p.hash.as_str()))
+            .count();
+        assert!(despues.len() > 3);
+        assert!(
+            nuevos <= 2,
+            "{} de {} trozos cambiaron",
+            nuevos,
+            despues.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn archivo_troceado_se_reconstruye() {
+        let _env = test_env().await;
+        let user_id = create_test_user();
+        let data = contenido(6 * 1024 * 1024, 3);
+
+        let file_id = upload_file(
+            &user_id,
+            "application/octet-stream",
+            Some("grande.bin"),
+            None,
+            None,
+            &mut &data[..],
+        )
+        .await
+        .unwrap();
+        let db = init_db_manager();
+        let hash = db.buscar_file(&file_id).unwrap().hash;
+        assert_eq!(db.buscar_blob(&hash).unwrap().codec, CODEC_CHUNKED);
+        let manifiesto = db.obtener_manifiesto_blob(&hash).unwrap();
+        assert!(manifiesto.len() > 1);
+
+        assert_eq!(leer(open_blob(&hash).await.unwrap()).await, data);
+
+        // Rangos que cruzan el borde entre trozos, y uno que abarca varios
+        let mut rangos: Vec<(u64, u64)> = manifiesto
+            .iter()
+            .skip(1)
+            .map(|(parte, _)| (parte.byte_offset as u64 - 100, 200))
+            .collect();
+        rangos.push((1, data.len() as u64 - 2));
+        rangos.push((data.len() as u64 - 10, 1000));
+        for (inicio, largo) in rangos {
+            let fin = (inicio + largo).min(data.len() as u64) as usize;
+            let leido = leer(open_blob_range(&hash, inicio, largo).await.unwrap()).await;
+            assert_eq!(
+                leido,
+                &data[inicio as usize..fin],
+                "rango {}+{}",
+                inicio,
+                largo
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn archivo_parecido_reutiliza_trozos() {
+        let _env = test_env().await;
+        let user_id = create_test_user();
+        let original = contenido(6 * 1024 * 1024, 4);
+        let mut editado = b"cabecera nueva".to_vec();
+        editado.extend_from_slice(&original);
+
+        let trozos_guardados = || async {
+            init_storage()
+                .list()
+                .await
+                .unwrap()
+                .into_iter()
+                .filter(|o| o.key.starts_with(CHUNK_KEY_PREFIX))
+                .count()
+        };
+        let subir = |data: Vec<u8>| {
+            let user_id = user_id.clone();
+            async move {
+                upload_file(
+                    &user_id,
+                    "application/octet-stream",
+                    None,
+                    None,
+                    None,
+                    &mut &data[..],
+                )
+                .await
+                .unwrap()
+            }
+        };
+
+        let inicial = trozos_guardados().await;
+        subir(original).await;
+        let con_original = trozos_guardados().await;
+        let id = subir(editado.clone()).await;
+        let con_editado = trozos_guardados().await;
+
+        assert!(con_original - inicial > 2);
+        assert!(con_editado - con_original <= 2);
+        let hash = init_db_manager().buscar_file(&id).unwrap().hash;
+        assert_eq!(leer(open_blob(&hash).await.unwrap()).await, editado);
+    }
+}
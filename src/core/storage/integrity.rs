@@ -0,0 +1,172 @@
+use anyhow::{Context, Result};
+use blake2::{Blake2b512, Digest};
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use sha2::Sha256;
+use std::sync::Mutex as StdMutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::io::AsyncReadExt;
+use tracing::{error, info, warn};
+
+use super::blobs;
+use crate::core::database::init_db_manager;
+use crate::core::structs::{Blob, IntegrityIssue, ScrubReport};
+
+pub const STATUS_CORRUPT: &str = "corrupt";
+pub const STATUS_MISSING: &str = "missing";
+
+static SCRUB_EN_CURSO: AtomicBool = AtomicBool::new(false);
+static ULTIMO_REPORTE: Lazy<StdMutex<Option<ScrubReport>>> = Lazy::new(Default::default);
+
+/// Indica si hay una verificación en curso
+pub fn scrub_running() -> bool {
+    SCRUB_EN_CURSO.load(Ordering::SeqCst)
+}
+
+/// Resumen de la última verificación completa, si hubo alguna
+pub fn last_scrub_report() -> Option<ScrubReport> {
+    ULTIMO_REPORTE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Resultado de verificar un blob
+enum Verificacion {
+    /// Contenido correcto, con su SHA-256 (hex)
+    Ok(String),
+    Problema(&'static str, String),
+}
+
+/// Recalcula el Blake2b512 de un blob y lo compara con su hash y tamaño
+/// registrados. De paso calcula el SHA-256 para completar los blobs que no
+/// lo tienen.
+async fn verify_blob(blob: &Blob) -> Result<Verificacion> {
+    if !blobs::blob_stored(blob).await? {
+        return Ok(Verificacion::Problema(
+            STATUS_MISSING,
+            "El blob no existe en el storage".to_string(),
+        ));
+    }
+
+    let mut reader = match blobs::open_blob(&blob.hash).await {
+        Ok(reader) => reader,
+        Err(e) => return Ok(Verificacion::Problema(STATUS_CORRUPT, e.to_string())),
+    };
+
+    let mut hasher = Blake2b512::new();
+    let mut sha256 = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut total: u64 = 0;
+    loop {
+        let leidos = match reader.read(&mut buffer).await {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => return Ok(Verificacion::Problema(STATUS_CORRUPT, e.to_string())),
+        };
+        hasher.update(&buffer[..leidos]);
+        sha256.update(&buffer[..leidos]);
+        total += leidos as u64;
+    }
+
+    let hash = format!("{:x}", hasher.finalize());
+    if hash != blob.hash {
+        return Ok(Verificacion::Problema(
+            STATUS_CORRUPT,
+            format!("El hash no coincide ({} bytes leídos)", total),
+        ));
+    }
+    if blob.size > 0 && total != blob.size as u64 {
+        return Ok(Verificacion::Problema(
+            STATUS_CORRUPT,
+            format!("Tamaño {} distinto del registrado ({})", total, blob.size),
+        ));
+    }
+
+    Ok(Verificacion::Ok(format!("{:x}", sha256.finalize())))
+}
+
+/// Recorre todos los blobs registrados y verifica su contenido.
+///
+/// Los blobs dañados o ausentes se registran en `integrity_issues`; los que
+/// vuelven a verificar correctamente se quitan de esa tabla.
+pub async fn scrub_blobs() -> Result<ScrubReport> {
+    if SCRUB_EN_CURSO.swap(true, Ordering::SeqCst) {
+        anyhow::bail!("Ya hay una verificación de integridad en curso");
+    }
+
+    let resultado = run_scrub().await;
+    SCRUB_EN_CURSO.store(false, Ordering::SeqCst);
+
+    let reporte = resultado?;
+    *ULTIMO_REPORTE.lock().unwrap_or_else(|e| e.into_inner()) = Some(reporte.clone());
+    Ok(reporte)
+}
+
+async fn run_scrub() -> Result<ScrubReport> {
+    let db = init_db_manager();
+    let mut reporte = ScrubReport {
+        started_at: Utc::now().timestamp(),
+        ..Default::default()
+    };
+
+    let blobs = db
+        .obtener_todos_los_blobs()
+        .context("Error al listar blobs para verificar")?;
+    info!("Verificando integridad de {} blob(s)...", blobs.len());
+
+    for blob in blobs {
+        let verificacion = match verify_blob(&blob).await {
+            Ok(v) => v,
+            Err(e) => {
+                error!("No se pudo verificar el blob {}: {}", blob.hash, e);
+                continue;
+            }
+        };
+
+        // El blob pudo eliminarse mientras se verificaba
+        if db.buscar_blob(&blob.hash).is_err() {
+            continue;
+        }
+        reporte.checked += 1;
+
+        match verificacion {
+            Verificacion::Ok(sha256) => {
+                reporte.ok += 1;
+                if db.borrar_problema_integridad(&blob.hash)? > 0 {
+                    info!("Blob {} vuelve a verificar correctamente", blob.hash);
+                }
+                if blob.sha256.is_none() {
+                    db.completar_sha256_blob(&blob.hash, &sha256)?;
+                }
+            }
+            Verificacion::Problema(status, detail) => {
+                if status == STATUS_MISSING {
+                    reporte.missing += 1;
+                } else {
+                    reporte.corrupt += 1;
+                }
+                warn!("Blob {} marcado como {}: {}", blob.hash, status, detail);
+
+                // Conservar la fecha de la primera detección
+                let detected_at = db
+                    .buscar_problema_integridad(&blob.hash)?
+                    .map(|p| p.detected_at)
+                    .unwrap_or_else(|| Utc::now().timestamp());
+                db.registrar_problema_integridad(&IntegrityIssue {
+                    hash: blob.hash.clone(),
+                    status: status.to_string(),
+                    detail,
+                    detected_at,
+                })?;
+            }
+        }
+    }
+
+    reporte.finished_at = Utc::now().timestamp();
+    info!(
+        "Verificación de integridad terminada: {} verificados, {} correctos, {} corruptos, {} ausentes",
+        reporte.checked, reporte.ok, reporte.corrupt, reporte.missing
+    );
+    Ok(reporte)
+}
@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tokio::fs;
+use tokio::io::{AsyncRead, AsyncSeekExt};
+use uuid::Uuid;
+
+use super::{BlobReader, StorageBackend, StoredObject, validate_key};
+
+/// Almacenamiento en el sistema de archivos local, con raíz en `uploads_path`
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LocalStorage { root: root.into() }
+    }
+
+    fn path_for(&self, key: &str) -> Result<PathBuf> {
+        validate_key(key)?;
+        Ok(self.root.join(format!("{}.st", key)))
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LocalStorage {
+    async fn put(&self, key: &str, reader: &mut (dyn AsyncRead + Unpin + Send)) -> Result<u64> {
+        let path = self.path_for(key)?;
+
+        // Escribir en un temporal y renombrar para que nunca se vea un blob a medias
+        let temp = self.root.join(format!(".{}.tmp", Uuid::new_v4()));
+        let result = async {
+            let mut file = fs::File::create(&temp).await?;
+            let written = tokio::io::copy(reader, &mut file).await?;
+            file.sync_all().await?;
+            fs::rename(&temp, &path).await?;
+            Ok::<u64, std::io::Error>(written)
+        }
+        .await;
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temp).await;
+        }
+        result.with_context(|| format!("Error al escribir blob {:?}", path))
+    }
+
+    async fn put_file(&self, key: &str, staged: &Path) -> Result<()> {
+        let path = self.path_for(key)?;
+        fs::rename(staged, &path)
+            .await
+            .with_context(|| format!("Error al mover {:?} a {:?}", staged, path))
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let path = self.path_for(key)?;
+        fs::read(&path)
+            .await
+            .with_context(|| format!("Error al leer archivo desde disco: {:?}", path))
+    }
+
+    async fn stream(&self, key: &str) -> Result<BlobReader> {
+        let path = self.path_for(key)?;
+        let file = fs::File::open(&path)
+            .await
+            .with_context(|| format!("Error al abrir archivo desde disco: {:?}", path))?;
+        Ok(Box::new(file))
+    }
+
+    async fn stream_from(&self, key: &str, offset: u64) -> Result<BlobReader> {
+        let path = self.path_for(key)?;
+        let mut file = fs::File::open(&path)
+            .await
+            .with_context(|| format!("Error al abrir archivo desde disco: {:?}", path))?;
+        file.seek(SeekFrom::Start(offset)).await?;
+        Ok(Box::new(file))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let path = self.path_for(key)?;
+        match fs::remove_file(&path).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).with_context(|| format!("Error al eliminar {:?}", path)),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        let path = self.path_for(key)?;
+        Ok(fs::try_exists(&path).await?)
+    }
+
+    async fn list(&self) -> Result<Vec<StoredObject>> {
+        let mut objetos = Vec::new();
+        let mut entradas = fs::read_dir(&self.root)
+            .await
+            .with_context(|| format!("Error al listar {:?}", self.root))?;
+
+        while let Some(entrada) = entradas.next_entry().await? {
+            let nombre = entrada.file_name().to_string_lossy().to_string();
+            let Some(key) = nombre.strip_suffix(".st") else {
+                continue;
+            };
+            let metadata = entrada.metadata().await?;
+            if !metadata.is_file() || validate_key(key).is_err() {
+                continue;
+            }
+
+            let modified_at = metadata
+                .modified()
+                .ok()
+                .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or(0);
+            objetos.push(StoredObject {
+                key: key.to_string(),
+                modified_at,
+            });
+        }
+
+        Ok(objetos)
+    }
+}
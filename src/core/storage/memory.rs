@@ -0,0 +1,81 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::io::Cursor;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::RwLock;
+
+use super::{BlobReader, StorageBackend, StoredObject, validate_key};
+
+/// Almacenamiento volátil en memoria, solo disponible en las pruebas
+#[derive(Default)]
+pub struct MemoryStorage {
+    blobs: RwLock<HashMap<String, (Vec<u8>, i64)>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl StorageBackend for MemoryStorage {
+    async fn put(&self, key: &str, reader: &mut (dyn AsyncRead + Unpin + Send)) -> Result<u64> {
+        validate_key(key)?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await?;
+        let written = data.len() as u64;
+        self.blobs
+            .write()
+            .await
+            .insert(key.to_string(), (data, Utc::now().timestamp()));
+        Ok(written)
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        validate_key(key)?;
+        self.blobs
+            .read()
+            .await
+            .get(key)
+            .map(|(data, _)| data.clone())
+            .ok_or_else(|| anyhow!("Blob {} no encontrado en memoria", key))
+    }
+
+    async fn stream(&self, key: &str) -> Result<BlobReader> {
+        let data = self.get(key).await?;
+        Ok(Box::new(Cursor::new(data)))
+    }
+
+    async fn stream_from(&self, key: &str, offset: u64) -> Result<BlobReader> {
+        let mut cursor = Cursor::new(self.get(key).await?);
+        cursor.set_position(offset);
+        Ok(Box::new(cursor))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        validate_key(key)?;
+        self.blobs.write().await.remove(key);
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        validate_key(key)?;
+        Ok(self.blobs.read().await.contains_key(key))
+    }
+
+    async fn list(&self) -> Result<Vec<StoredObject>> {
+        Ok(self
+            .blobs
+            .read()
+            .await
+            .iter()
+            .map(|(key, (_, modified_at))| StoredObject {
+                key: key.clone(),
+                modified_at: *modified_at,
+            })
+            .collect())
+    }
+}
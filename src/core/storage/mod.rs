@@ -0,0 +1,198 @@
+// src/core/storage/mod.rs
+// ── Backends de almacenamiento ───────────────────────────────────────
+pub mod blobs;
+pub mod integrity;
+mod local;
+#[cfg(test)]
+mod memory;
+pub mod reconcile;
+
+pub use local::LocalStorage;
+#[cfg(test)]
+pub use memory::MemoryStorage;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use once_cell::sync::OnceCell;
+use std::path::Path;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tracing::{info, warn};
+
+use crate::core::utils::{storage_backend, uploads_path};
+
+/// Stream de lectura devuelto por los backends
+pub type BlobReader = Box<dyn AsyncRead + Unpin + Send>;
+
+/// Operaciones mínimas que necesita Privafile de un almacenamiento de blobs.
+///
+/// Las claves son nombres planos (sin separadores de ruta); cada backend
+/// decide cómo mapearlas a su medio físico.
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Guarda el contenido de `reader` bajo `key`, reemplazando lo que hubiera.
+    /// Devuelve la cantidad de bytes escritos.
+    async fn put(&self, key: &str, reader: &mut (dyn AsyncRead + Unpin + Send)) -> Result<u64>;
+
+    /// Publica un archivo temporal ya escrito en disco bajo `key`.
+    ///
+    /// La implementación por defecto lo copia con `put` y borra el temporal;
+    /// los backends locales pueden sobreescribirla con un rename atómico.
+    async fn put_file(&self, key: &str, staged: &Path) -> Result<()> {
+        let mut file = tokio::fs::File::open(staged).await?;
+        self.put(key, &mut file).await?;
+        tokio::fs::remove_file(staged).await?;
+        Ok(())
+    }
+
+    /// Lee el contenido completo de `key`
+    async fn get(&self, key: &str) -> Result<Vec<u8>>;
+
+    /// Abre `key` para lectura en streaming
+    async fn stream(&self, key: &str) -> Result<BlobReader>;
+
+    /// Abre `key` para lectura en streaming a partir del byte `offset`.
+    ///
+    /// La implementación por defecto descarta los bytes previos; los backends
+    /// con acceso aleatorio deberían sobreescribirla.
+    async fn stream_from(&self, key: &str, offset: u64) -> Result<BlobReader> {
+        let mut reader = self.stream(key).await?;
+        tokio::io::copy(&mut (&mut reader).take(offset), &mut tokio::io::sink()).await?;
+        Ok(reader)
+    }
+
+    /// Elimina `key`. No falla si no existe.
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// Indica si `key` existe
+    async fn exists(&self, key: &str) -> Result<bool>;
+
+    /// Lista todas las claves almacenadas
+    async fn list(&self) -> Result<Vec<StoredObject>>;
+}
+
+/// Entrada devuelta por `StorageBackend::list`
+#[derive(Debug, Clone)]
+pub struct StoredObject {
+    pub key: String,
+    /// Última modificación (timestamp unix)
+    pub modified_at: i64,
+}
+
+/// Valida que una clave no pueda escapar del almacenamiento
+pub(crate) fn validate_key(key: &str) -> Result<()> {
+    if key.is_empty() || key.contains("..") || key.contains('/') || key.contains('\\') {
+        anyhow::bail!("Clave de almacenamiento inválida: {:?}", key);
+    }
+    Ok(())
+}
+
+pub static STORAGE: OnceCell<Box<dyn StorageBackend>> = OnceCell::new();
+
+/// Devuelve el backend global, creándolo según `Config.storage_backend`.
+pub fn init_storage() -> &'static dyn StorageBackend {
+    STORAGE
+        .get_or_init(|| {
+            let backend = storage_backend();
+            if backend != "local" {
+                warn!(
+                    "Backend de almacenamiento desconocido '{}', usando 'local'",
+                    backend
+                );
+            }
+            info!("Usando almacenamiento local en {}", uploads_path());
+            Box::new(LocalStorage::new(uploads_path()))
+        })
+        .as_ref()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::testing::TempDir;
+
+    async fn leer(reader: BlobReader) -> Vec<u8> {
+        let mut reader = reader;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await.unwrap();
+        data
+    }
+
+    /// Contrato común que deben cumplir todos los backends
+    async fn contrato(storage: &dyn StorageBackend, staging: &Path) {
+        assert!(!storage.exists("abc").await.unwrap());
+        assert!(storage.get("abc").await.is_err());
+
+        let escritos = storage.put("abc", &mut &b"hola mundo"[..]).await.unwrap();
+        assert_eq!(escritos, 10);
+        assert!(storage.exists("abc").await.unwrap());
+        assert_eq!(storage.get("abc").await.unwrap(), b"hola mundo");
+        assert_eq!(
+            leer(storage.stream("abc").await.unwrap()).await,
+            b"hola mundo"
+        );
+        assert_eq!(
+            leer(storage.stream_from("abc", 5).await.unwrap()).await,
+            b"mundo"
+        );
+        assert!(
+            leer(storage.stream_from("abc", 10).await.unwrap())
+                .await
+                .is_empty()
+        );
+
+        // `put` reemplaza el contenido anterior
+        storage.put("abc", &mut &b"otro"[..]).await.unwrap();
+        assert_eq!(storage.get("abc").await.unwrap(), b"otro");
+
+        // `put_file` publica el temporal y lo consume
+        let staged = staging.join("staged.tmp");
+        std::fs::write(&staged, b"desde disco").unwrap();
+        storage.put_file("def", &staged).await.unwrap();
+        assert!(!staged.exists());
+        assert_eq!(storage.get("def").await.unwrap(), b"desde disco");
+
+        let mut claves: Vec<String> = storage
+            .list()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|o| o.key)
+            .collect();
+        claves.sort();
+        assert_eq!(claves, ["abc", "def"]);
+
+        storage.delete("abc").await.unwrap();
+        assert!(!storage.exists("abc").await.unwrap());
+        // Borrar una clave inexistente no es un error
+        storage.delete("abc").await.unwrap();
+
+        for clave in ["", "..", "../x", "a/b", "a\\b"] {
+            assert!(storage.put(clave, &mut &b"x"[..]).await.is_err());
+            assert!(storage.get(clave).await.is_err());
+            assert!(storage.delete(clave).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn memory_cumple_el_contrato() {
+        let staging = TempDir::new();
+        contrato(&MemoryStorage::new(), &staging.0).await;
+    }
+
+    #[tokio::test]
+    async fn local_cumple_el_contrato() {
+        let root = TempDir::new();
+        let staging = root.0.join("staging");
+        std::fs::create_dir_all(&staging).unwrap();
+        contrato(&LocalStorage::new(&root.0), &staging).await;
+    }
+
+    #[test]
+    fn validate_key_rechaza_rutas() {
+        assert!(validate_key("0123abcd").is_ok());
+        assert!(validate_key("chunk-0123abcd").is_ok());
+        for clave in ["", "..", "a..b", "a/b", "/abs", "a\\b"] {
+            assert!(validate_key(clave).is_err(), "{:?}", clave);
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::UNIX_EPOCH;
+use tracing::{error, info, warn};
+
+use super::blobs::{self, BLOB_LOCK, CHUNK_KEY_PREFIX, CODEC_CHUNKED, chunk_key, lock_key};
+use super::init_storage;
+use super::integrity::STATUS_MISSING;
+use crate::core::database::init_db_manager;
+use crate::core::structs::{
+    Blob, DanglingFile, IntegrityIssue, ReconcileReport, RefcountFix, UsageFix,
+};
+use crate::core::utils::{orphan_grace_hours, staging_path};
+
+static RECONCILIACION_EN_CURSO: AtomicBool = AtomicBool::new(false);
+
+/// Compara la DB con el contenido del storage y repara las diferencias.
+///
+/// - Blobs en el storage sin registro: se borran si son más antiguos que
+///   `orphan_grace_hours`.
+/// - Registros de blobs con un refcount distinto de los archivos que los
+///   referencian: se corrige el contador (y se borran si no queda ninguno).
+/// - Manifiestos de trozos de blobs ya borrados y trozos con un refcount
+///   distinto de los manifiestos que los usan: igual que con los blobs.
+/// - Archivos cuyo blob no tiene registro o no existe en el storage: se
+///   marcan como `missing` en `integrity_issues` (y se desmarcan cuando el
+///   contenido vuelve a aparecer).
+/// - Uso de almacenamiento de los usuarios desalineado: se recalcula.
+/// - Temporales de staging abandonados: se borran.
+///
+/// Con `dry_run` solo se genera el reporte, sin modificar nada.
+pub async fn reconcile_storage(dry_run: bool) -> Result<ReconcileReport> {
+    if RECONCILIACION_EN_CURSO.swap(true, Ordering::SeqCst) {
+        anyhow::bail!("Ya hay una reconciliación en curso");
+    }
+
+    let resultado = run_reconcile(dry_run).await;
+    RECONCILIACION_EN_CURSO.store(false, Ordering::SeqCst);
+    resultado
+}
+
+async fn run_reconcile(dry_run: bool) -> Result<ReconcileReport> {
+    let db = init_db_manager();
+    let storage = init_storage();
+    let ahora = Utc::now().timestamp();
+    let limite = ahora - orphan_grace_hours().max(0) * 3600;
+    let mut reporte = ReconcileReport {
+        dry_run,
+        started_at: ahora,
+        ..Default::default()
+    };
+
+    // El listado del storage va primero: un blob publicado después de leer
+    // la DB no debe aparecer como huérfano
+    let objetos = storage
+        .list()
+        .await
+        .context("Error al listar el contenido del storage")?;
+    let registrados: HashMap<String, Blob> = db
+        .obtener_todos_los_blobs()
+        .context("Error al listar blobs")?
+        .into_iter()
+        .map(|b| (b.hash.clone(), b))
+        .collect();
+    let trozos: HashSet<String> = db
+        .obtener_todos_los_chunks()
+        .context("Error al listar trozos")?
+        .into_iter()
+        .map(|c| chunk_key(&c.hash))
+        .collect();
+
+    // 1. Objetos del storage sin registro
+    let mut presentes = HashSet::new();
+    for objeto in objetos {
+        presentes.insert(objeto.key.clone());
+        if registrados.contains_key(&objeto.key) || trozos.contains(&objeto.key) {
+            continue;
+        }
+        if objeto.modified_at > limite {
+            reporte.recent_orphans += 1;
+            continue;
+        }
+
+        if !dry_run {
+            let _clave = lock_key(&objeto.key).await;
+            // Pudo registrarse mientras tanto
+            let registrado = match objeto.key.strip_prefix(CHUNK_KEY_PREFIX) {
+                Some(chunk) => db.buscar_chunk(chunk).is_ok(),
+                None => db.buscar_blob(&objeto.key).is_ok(),
+            };
+            if registrado {
+                continue;
+            }
+            if let Err(e) = storage.delete(&objeto.key).await {
+                error!("No se pudo borrar el blob huérfano {}: {}", objeto.key, e);
+                continue;
+            }
+        }
+        warn!("Blob huérfano sin registro: {}", objeto.key);
+        reporte.orphan_blobs.push(objeto.key);
+    }
+
+    // 2. Refcounts desalineados y registros sin referencias
+    let referencias: HashMap<String, i64> = db
+        .contar_referencias_blobs()
+        .context("Error al contar referencias de blobs")?
+        .into_iter()
+        .collect();
+    for blob in db.obtener_todos_los_blobs()? {
+        let actual = referencias.get(&blob.hash).copied().unwrap_or(0) as i32;
+        if actual == blob.refcount {
+            continue;
+        }
+
+        if !dry_run {
+            let recalculado = {
+                let _guard = BLOB_LOCK.lock().await;
+                db.recalcular_refcount_blob(&blob.hash)
+            };
+            match recalculado {
+                Ok(true) => blobs::delete_unreferenced(&blob.hash).await,
+                Ok(false) => {}
+                Err(e) => {
+                    error!("No se pudo corregir el refcount de {}: {}", blob.hash, e);
+                    continue;
+                }
+            }
+        }
+
+        if actual == 0 {
+            warn!(
+                "Registro de blob {} sin archivos que lo referencien",
+                blob.hash
+            );
+            reporte.unreferenced_blobs.push(blob.hash);
+        } else {
+            warn!(
+                "Refcount de {} desalineado: {} registrado, {} real",
+                blob.hash, blob.refcount, actual
+            );
+            reporte.refcount_fixes.push(RefcountFix {
+                hash: blob.hash,
+                recorded: blob.refcount,
+                actual,
+            });
+        }
+    }
+
+    // 3. Manifiestos de blobs sin registro y refcounts de los trozos
+    for hash in db
+        .obtener_manifiestos_huerfanos()
+        .context("Error al listar manifiestos de trozos")?
+    {
+        if !dry_run {
+            let _clave = lock_key(&hash).await;
+            // Pudo registrarse mientras tanto
+            if db.buscar_blob(&hash).is_ok() {
+                continue;
+            }
+            blobs::release_chunks(&hash).await;
+        }
+        warn!("Manifiesto de trozos sin blob: {}", hash);
+        reporte.orphan_manifests.push(hash);
+    }
+
+    let referencias: HashMap<String, i64> = db
+        .contar_referencias_chunks()
+        .context("Error al contar referencias de trozos")?
+        .into_iter()
+        .collect();
+    for chunk in db.obtener_todos_los_chunks()? {
+        let actual = referencias.get(&chunk.hash).copied().unwrap_or(0) as i32;
+        if actual == chunk.refcount {
+            continue;
+        }
+
+        if !dry_run {
+            let recalculado = {
+                let _guard = BLOB_LOCK.lock().await;
+                db.recalcular_refcount_chunk(&chunk.hash)
+            };
+            match recalculado {
+                Ok(true) => blobs::delete_chunk(&chunk.hash).await,
+                Ok(false) => {}
+                Err(e) => {
+                    error!(
+                        "No se pudo corregir el refcount del trozo {}: {}",
+                        chunk.hash, e
+                    );
+                    continue;
+                }
+            }
+        }
+
+        if actual == 0 {
+            warn!("Trozo {} sin manifiestos que lo referencien", chunk.hash);
+            reporte.unreferenced_chunks.push(chunk.hash);
+        } else {
+            warn!(
+                "Refcount del trozo {} desalineado: {} registrado, {} real",
+                chunk.hash, chunk.refcount, actual
+            );
+            reporte.chunk_refcount_fixes.push(RefcountFix {
+                hash: chunk.hash,
+                recorded: chunk.refcount,
+                actual,
+            });
+        }
+    }
+
+    // 4. Archivos cuyo contenido no está disponible
+    for file in db
+        .obtener_todos_los_files()
+        .context("Error al listar archivos")?
+    {
+        let consultado;
+        let blob = match registrados.get(&file.hash) {
+            Some(blob) => Some(blob),
+            None => {
+                consultado = db.buscar_blob(&file.hash).ok();
+                consultado.as_ref()
+            }
+        };
+        let almacenado = match blob {
+            None => false,
+            Some(blob) if blob.codec == CODEC_CHUNKED => blobs::blob_stored(blob).await?,
+            Some(_) => presentes.contains(&file.hash) || storage.exists(&file.hash).await?,
+        };
+
+        let reason = if blob.is_none() {
+            "El blob no tiene registro en la base de datos"
+        } else if !almacenado {
+            "El blob no existe en el storage"
+        } else {
+            // El contenido volvió a estar disponible
+            if !dry_run
+                && matches!(
+                    db.buscar_problema_integridad(&file.hash)?,
+                    Some(problema) if problema.status == STATUS_MISSING
+                )
+            {
+                db.borrar_problema_integridad(&file.hash)?;
+                info!("Blob {} vuelve a estar disponible", file.hash);
+            }
+            continue;
+        };
+
+        warn!("Archivo {} sin contenido: {}", file.id, reason);
+        if !dry_run && db.buscar_problema_integridad(&file.hash)?.is_none() {
+            db.registrar_problema_integridad(&IntegrityIssue {
+                hash: file.hash.clone(),
+                status: STATUS_MISSING.to_string(),
+                detail: reason.to_string(),
+                detected_at: ahora,
+            })?;
+        }
+        reporte.dangling_files.push(DanglingFile {
+            file_id: file.id,
+            hash: file.hash,
+            reason: reason.to_string(),
+        });
+    }
+
+    // 5. Uso de almacenamiento de cada usuario y cada equipo
+    for (user_id, recorded, actual) in db
+        .calcular_uso_usuarios()
+        .context("Error al calcular el uso de los usuarios")?
+    {
+        if recorded == actual {
+            continue;
+        }
+
+        let actual = if dry_run {
+            actual
+        } else {
+            let _guard = BLOB_LOCK.lock().await;
+            db.recalcular_uso_usuario(&user_id)?
+        };
+        warn!(
+            "Uso de {} desalineado: {} registrado, {} real",
+            user_id, recorded, actual
+        );
+        reporte.usage_fixes.push(UsageFix {
+            user_id,
+            recorded,
+            actual,
+        });
+    }
+
+    // 6. Temporales de staging abandonados
+    reporte.stale_temp_files = purge_stale_temp_files(limite, dry_run).await?;
+
+    reporte.finished_at = Utc::now().timestamp();
+    info!(
+        "Reconciliación {}terminada: {} huérfano(s), {} sin referencias, {} refcount(s) corregidos, {} manifiesto(s) huérfanos, {} trozo(s) sin referencias, {} refcount(s) de trozos corregidos, {} archivo(s) sin contenido, {} uso(s) corregidos, {} temporal(es)",
+        if dry_run { "(simulada) " } else { "" },
+        reporte.orphan_blobs.len(),
+        reporte.unreferenced_blobs.len(),
+        reporte.refcount_fixes.len(),
+        reporte.orphan_manifests.len(),
+        reporte.unreferenced_chunks.len(),
+        reporte.chunk_refcount_fixes.len(),
+        reporte.dangling_files.len(),
+        reporte.usage_fixes.len(),
+        reporte.stale_temp_files.len()
+    );
+    Ok(reporte)
+}
+
+/// Temporales de subida (`.tmp`, `.zst`, `.enc`) anteriores a `limite` y partes de
+/// subidas reanudables cuya sesión ya no existe
+async fn purge_stale_temp_files(limite: i64, dry_run: bool) -> Result<Vec<String>> {
+    let db = init_db_manager();
+    let mut borrados = Vec::new();
+    let mut entradas = tokio::fs::read_dir(staging_path())
+        .await
+        .context("Error al listar el directorio de staging")?;
+
+    while let Some(entrada) = entradas.next_entry().await? {
+        let path = entrada.path();
+        let metadata = entrada.metadata().await?;
+        if !metadata.is_file() {
+            continue;
+        }
+
+        let modificado = metadata
+            .modified()
+            .ok()
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        if modificado > limite {
+            continue;
+        }
+
+        let stem = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default();
+        let abandonado = match path.extension().and_then(|e| e.to_str()) {
+            Some("tmp") | Some("zst") | Some("enc") => true,
+            Some("part") => db.buscar_tus_upload(stem).is_err(),
+            _ => false,
+        };
+        if !abandonado {
+            continue;
+        }
+
+        if !dry_run && let Err(e) = tokio::fs::remove_file(&path).await {
+            warn!("No se pudo borrar el temporal {:?}: {}", path, e);
+            continue;
+        }
+        borrados.push(entrada.file_name().to_string_lossy().to_string());
+    }
+
+    Ok(borrados)
+}
@@ -1,7 +1,10 @@
 use diesel::prelude::{Insertable, Queryable};
 use serde::{Deserialize, Serialize};
 
-use crate::core::database::schema::{files, usuarios};
+use crate::core::database::schema::{
+    blob_chunks, blobs, chunks, file_tags, file_versions, files, folders, grants, integrity_issues,
+    share_links, team_members, teams, tus_uploads, upload_link_files, upload_links, usuarios,
+};
 
 #[derive(Queryable, Debug)]
 pub struct Usuario {
@@ -9,6 +12,17 @@ pub struct Usuario {
     pub username: String,
     pub password: String,
     pub b64_pubkey: Option<String>,
+    pub used_bytes: i64,
+    /// Cuota propia en bytes; `None` usa la cuota por defecto
+    pub quota_bytes: Option<i64>,
+    /// `user` o `admin`
+    pub role: String,
+    /// Momento en que se deshabilitó la cuenta; `None` = habilitada
+    pub disabled_at: Option<i64>,
+    /// La contraseña la fijó un administrador y hay que cambiarla
+    pub password_reset_required: bool,
+    /// Los tokens emitidos antes de este momento dejan de valer
+    pub sessions_revoked_at: Option<i64>,
 }
 
 #[derive(Insertable)]
@@ -26,6 +40,20 @@ pub struct File {
     pub mime: String,
     pub hash: String,
     pub owner_id: String,
+    /// Nombre original con el que se subió
+    pub filename: String,
+    pub size: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub last_accessed_at: Option<i64>,
+    /// Carpeta contenedora; `None` = raíz del usuario
+    pub folder_id: Option<String>,
+    /// Momento en que se envió a la papelera; `None` = archivo activo
+    pub deleted_at: Option<i64>,
+    /// Número de la versión actual del contenido
+    pub version: i32,
+    /// Momento en que se subió la versión actual
+    pub version_created_at: i64,
 }
 
 #[derive(Insertable)]
@@ -35,6 +63,340 @@ pub struct NuevoFile<'a> {
     pub mime: &'a str,
     pub hash: &'a str,
     pub owner_id: &'a str,
+    pub filename: &'a str,
+    pub size: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub folder_id: Option<&'a str>,
+    pub version_created_at: i64,
+}
+
+/// Versión anterior del contenido de un archivo
+#[derive(Queryable, Insertable, Debug, Clone)]
+#[diesel(table_name = file_versions)]
+pub struct FileVersion {
+    pub file_id: String,
+    pub version: i32,
+    pub hash: String,
+    pub mime: String,
+    pub size: i64,
+    /// Momento en que se subió esta versión
+    pub created_at: i64,
+}
+
+/// Etiqueta asignada a un archivo
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = file_tags)]
+pub struct NuevoFileTag<'a> {
+    pub file_id: &'a str,
+    pub tag: &'a str,
+}
+
+#[derive(Queryable, Debug, Clone)]
+pub struct Folder {
+    pub id: String,
+    pub owner_id: String,
+    /// Carpeta padre; `None` = raíz del usuario
+    pub parent_id: Option<String>,
+    pub name: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = folders)]
+pub struct NuevoFolder<'a> {
+    pub id: &'a str,
+    pub owner_id: &'a str,
+    pub parent_id: Option<&'a str>,
+    pub name: &'a str,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Queryable, Debug)]
+pub struct Blob {
+    pub hash: String,
+    pub size: i64,
+    pub refcount: i32,
+    pub encrypted: bool,
+    /// Compresión aplicada antes de cifrar (`none` o `zstd`), o `chunked`
+    /// si el contenido está repartido en trozos
+    pub codec: String,
+    /// SHA-256 (hex) del contenido original; `None` en blobs anteriores a
+    /// los digests hasta que el scrubber lo calcula
+    pub sha256: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = blobs)]
+pub struct NuevoBlob<'a> {
+    pub hash: &'a str,
+    pub size: i64,
+    pub refcount: i32,
+    pub encrypted: bool,
+}
+
+/// Trozo de contenido (FastCDC) compartido entre blobs guardados por trozos
+#[derive(Queryable, Debug, Clone)]
+pub struct Chunk {
+    pub hash: String,
+    pub size: i64,
+    /// Compresión aplicada al trozo antes de cifrarlo (`none` o `zstd`)
+    pub codec: String,
+    pub refcount: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = chunks)]
+pub struct NuevoChunk<'a> {
+    pub hash: &'a str,
+    pub size: i64,
+    pub codec: &'a str,
+    pub refcount: i32,
+}
+
+/// Posición de un trozo dentro del contenido de un blob
+#[derive(Queryable, Insertable, Debug, Clone)]
+#[diesel(table_name = blob_chunks)]
+pub struct BlobChunk {
+    pub blob_hash: String,
+    pub seq: i32,
+    pub chunk_hash: String,
+    pub byte_offset: i64,
+    pub size: i64,
+}
+
+/// Blob marcado como dañado (`corrupt`) o ausente (`missing`) por el scrubber
+#[derive(Queryable, Insertable, Debug, Clone)]
+#[diesel(table_name = integrity_issues)]
+pub struct IntegrityIssue {
+    pub hash: String,
+    pub status: String,
+    pub detail: String,
+    pub detected_at: i64,
+}
+
+/// Permiso de otro usuario sobre un archivo o una carpeta (y su contenido)
+#[derive(Queryable, Debug, Clone)]
+pub struct Grant {
+    pub id: String,
+    pub owner_id: String,
+    pub grantee_id: String,
+    pub file_id: Option<String>,
+    pub folder_id: Option<String>,
+    /// `viewer` (solo lectura) o `editor`
+    pub role: String,
+    pub created_at: i64,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = grants)]
+pub struct NuevoGrant<'a> {
+    pub id: &'a str,
+    pub owner_id: &'a str,
+    pub grantee_id: &'a str,
+    pub file_id: Option<&'a str>,
+    pub folder_id: Option<&'a str>,
+    pub role: &'a str,
+    pub created_at: i64,
+}
+
+/// Equipo con un espacio de archivos común. Sus archivos y carpetas usan
+/// el id del equipo como `owner_id`.
+#[derive(Queryable, Debug, Clone)]
+pub struct Team {
+    pub id: String,
+    pub name: String,
+    pub used_bytes: i64,
+    /// Cuota propia del equipo; `None` usa la cuota por defecto
+    pub quota_bytes: Option<i64>,
+    pub created_at: i64,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = teams)]
+pub struct NuevoTeam<'a> {
+    pub id: &'a str,
+    pub name: &'a str,
+    pub created_at: i64,
+}
+
+/// Pertenencia de un usuario a un equipo
+#[derive(Queryable, Insertable, Debug, Clone)]
+#[diesel(table_name = team_members)]
+pub struct TeamMember {
+    pub team_id: String,
+    pub user_id: String,
+    /// `owner`, `admin`, `member` o `viewer`
+    pub role: String,
+    pub created_at: i64,
+}
+
+/// Enlace público para descargar un archivo sin cuenta
+#[derive(Queryable, Debug, Clone)]
+pub struct ShareLink {
+    pub token: String,
+    pub file_id: String,
+    pub owner_id: String,
+    /// Hash Argon2 de la contraseña; `None` si el enlace no la pide
+    pub password_hash: Option<String>,
+    pub expires_at: Option<i64>,
+    pub max_downloads: Option<i32>,
+    pub download_count: i32,
+    pub created_at: i64,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = share_links)]
+pub struct NuevoShareLink<'a> {
+    pub token: &'a str,
+    pub file_id: &'a str,
+    pub owner_id: &'a str,
+    pub password_hash: Option<&'a str>,
+    pub expires_at: Option<i64>,
+    pub max_downloads: Option<i32>,
+    pub created_at: i64,
+}
+
+/// Enlace para que personas sin cuenta suban archivos a una carpeta
+#[derive(Queryable, Debug, Clone)]
+pub struct UploadLink {
+    pub token: String,
+    pub folder_id: String,
+    pub owner_id: String,
+    /// Hash Argon2 de la contraseña; `None` si el enlace no la pide
+    pub password_hash: Option<String>,
+    pub expires_at: Option<i64>,
+    pub max_files: Option<i32>,
+    pub max_file_size: Option<i64>,
+    /// Tipos MIME aceptados separados por comas; `None` acepta cualquiera
+    pub allowed_mime_types: Option<String>,
+    pub upload_count: i32,
+    pub created_at: i64,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = upload_links)]
+pub struct NuevoUploadLink<'a> {
+    pub token: &'a str,
+    pub folder_id: &'a str,
+    pub owner_id: &'a str,
+    pub password_hash: Option<&'a str>,
+    pub expires_at: Option<i64>,
+    pub max_files: Option<i32>,
+    pub max_file_size: Option<i64>,
+    pub allowed_mime_types: Option<&'a str>,
+    pub created_at: i64,
+}
+
+/// Archivo recibido por un enlace de subida
+#[derive(Queryable, Insertable, Debug, Clone)]
+#[diesel(table_name = upload_link_files)]
+pub struct UploadLinkFile {
+    pub file_id: String,
+    pub token: String,
+    /// Nombre que dio quien lo subió, si dio alguno
+    pub uploader_name: Option<String>,
+    pub created_at: i64,
+}
+
+/// Subida reanudable (protocolo tus) en curso o recién completada
+#[derive(Queryable, Debug, Clone)]
+pub struct TusUpload {
+    pub id: String,
+    pub owner_id: String,
+    pub mime: String,
+    pub upload_length: i64,
+    pub upload_offset: i64,
+    pub file_id: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub filename: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = tus_uploads)]
+pub struct NuevoTusUpload<'a> {
+    pub id: &'a str,
+    pub owner_id: &'a str,
+    pub mime: &'a str,
+    pub upload_length: i64,
+    pub upload_offset: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub filename: &'a str,
+}
+
+/// Contenido de una carpeta (o de la raíz)
+pub struct FolderListing {
+    pub folder: Option<Folder>,
+    /// Carpetas desde la raíz hasta la listada (sin incluirla)
+    pub path: Vec<Folder>,
+    pub folders: Vec<Folder>,
+    pub files: Vec<File>,
+}
+
+/// Archivo o carpeta compartidos con un usuario, con el permiso que se lo da
+pub struct SharedEntry {
+    pub grant: Grant,
+    pub file: Option<File>,
+    pub folder: Option<Folder>,
+}
+
+/// Archivo recibido por un enlace de subida junto a quién lo subió
+pub struct UploadLinkEntry {
+    pub upload: UploadLinkFile,
+    pub file: File,
+}
+
+/// Equipo visto por uno de sus miembros: su rol y el uso del espacio común
+pub struct TeamMembership {
+    pub team: Team,
+    pub role: String,
+    pub usage: StorageUsage,
+}
+
+/// Datos de un archivo ya autorizado para descarga
+#[derive(Debug, Clone)]
+pub struct FileDownload {
+    pub id: String,
+    pub mime: String,
+    pub hash: String,
+    pub filename: String,
+    pub size: u64,
+    /// Fecha de la última modificación del contenido (`Last-Modified`)
+    pub modified_at: i64,
+    /// SHA-256 (hex) del contenido, si ya se calculó
+    pub sha256: Option<String>,
+}
+
+/// Archivo incluido en una descarga ZIP, con su ruta dentro del archivo
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub path: String,
+    pub file: FileDownload,
+}
+
+/// Cambio ya validado de una operación por lotes
+#[derive(Debug, Clone)]
+pub enum CambioLote {
+    Papelera {
+        file_id: String,
+    },
+    Mover {
+        file_id: String,
+        folder_id: Option<String>,
+    },
+    Renombrar {
+        file_id: String,
+        filename: String,
+    },
+    Etiquetar {
+        file_id: String,
+        agregar: Vec<String>,
+        quitar: Vec<String>,
+    },
 }
 
 // ============================================================================
@@ -66,6 +428,424 @@ pub struct FileInfo {
     pub id: String,
     pub mime: String,
     pub hash: String,
+    pub filename: String,
+    pub size: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub last_accessed_at: Option<i64>,
+    pub folder_id: Option<String>,
+    pub deleted_at: Option<i64>,
+    pub version: i32,
+}
+
+#[derive(Serialize)]
+pub struct FileResponse {
+    pub success: bool,
+    pub message: String,
+    pub file: Option<FileInfo>,
+}
+
+#[derive(Serialize)]
+pub struct VersionInfo {
+    pub version: i32,
+    pub hash: String,
+    pub mime: String,
+    pub size: i64,
+    pub created_at: i64,
+    /// Indica si es el contenido que se sirve al descargar el archivo
+    pub current: bool,
+}
+
+#[derive(Serialize)]
+pub struct VersionListResponse {
+    pub success: bool,
+    pub message: String,
+    /// Versiones de la más reciente (la actual) a la más antigua
+    pub versions: Vec<VersionInfo>,
+}
+
+#[derive(Serialize)]
+pub struct FolderInfo {
+    pub id: String,
+    pub parent_id: Option<String>,
+    pub name: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Serialize)]
+pub struct FolderResponse {
+    pub success: bool,
+    pub message: String,
+    pub folder: Option<FolderInfo>,
+}
+
+#[derive(Serialize)]
+pub struct FolderContentsResponse {
+    pub success: bool,
+    pub message: String,
+    /// Carpeta listada; `None` para la raíz
+    pub folder: Option<FolderInfo>,
+    /// Carpetas desde la raíz hasta la listada (sin incluirla)
+    pub path: Vec<FolderInfo>,
+    pub folders: Vec<FolderInfo>,
+    pub files: Vec<FileInfo>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateFolderRequest {
+    pub name: String,
+    /// Carpeta padre; `null` o ausente = raíz
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    /// Equipo en cuyo espacio se crea la carpeta; solo se usa en la raíz,
+    /// dentro de una carpeta el espacio es el de la carpeta padre
+    #[serde(default)]
+    pub team_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RenameRequest {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct MoveRequest {
+    /// Carpeta destino; `null` = raíz
+    pub folder_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CopyRequest {
+    /// Carpeta destino; `null` o ausente = raíz
+    #[serde(default)]
+    pub folder_id: Option<String>,
+    /// Nombre de la copia; sin él se conserva el del original
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct GrantRequest {
+    /// Usuario con quien se comparte
+    pub user_id: String,
+    /// `viewer` o `editor`
+    pub role: String,
+    /// Archivo compartido (exclusivo con `folder_id`)
+    #[serde(default)]
+    pub file_id: Option<String>,
+    /// Carpeta compartida con todo su contenido (exclusivo con `file_id`)
+    #[serde(default)]
+    pub folder_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct GrantInfo {
+    pub id: String,
+    pub owner_id: String,
+    pub grantee_id: String,
+    pub file_id: Option<String>,
+    pub folder_id: Option<String>,
+    pub role: String,
+    pub created_at: i64,
+}
+
+#[derive(Serialize)]
+pub struct GrantResponse {
+    pub success: bool,
+    pub message: String,
+    pub grant: Option<GrantInfo>,
+}
+
+#[derive(Serialize)]
+pub struct GrantListResponse {
+    pub success: bool,
+    pub message: String,
+    pub grants: Vec<GrantInfo>,
+}
+
+/// Elemento compartido con el usuario: el permiso y el archivo o la carpeta
+#[derive(Serialize)]
+pub struct SharedItem {
+    pub grant: GrantInfo,
+    pub file: Option<FileInfo>,
+    pub folder: Option<FolderInfo>,
+}
+
+#[derive(Serialize)]
+pub struct SharedWithMeResponse {
+    pub success: bool,
+    pub message: String,
+    pub items: Vec<SharedItem>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateTeamRequest {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct TeamMemberRequest {
+    pub user_id: String,
+    /// `owner`, `admin`, `member` o `viewer`
+    pub role: String,
+}
+
+#[derive(Serialize)]
+pub struct TeamInfo {
+    pub id: String,
+    pub name: String,
+    /// Rol del usuario que consulta
+    pub role: String,
+    pub usage: StorageUsage,
+    pub created_at: i64,
+}
+
+#[derive(Serialize)]
+pub struct TeamMemberInfo {
+    pub user_id: String,
+    pub role: String,
+    pub created_at: i64,
+}
+
+#[derive(Serialize)]
+pub struct TeamResponse {
+    pub success: bool,
+    pub message: String,
+    pub team: Option<TeamInfo>,
+}
+
+#[derive(Serialize)]
+pub struct TeamListResponse {
+    pub success: bool,
+    pub message: String,
+    pub teams: Vec<TeamInfo>,
+}
+
+#[derive(Serialize)]
+pub struct TeamMemberListResponse {
+    pub success: bool,
+    pub message: String,
+    pub members: Vec<TeamMemberInfo>,
+}
+
+#[derive(Deserialize)]
+pub struct SignedUrlRequest {
+    /// Segundos de validez; sin valor se usan 5 minutos
+    #[serde(default)]
+    pub expires_in_seconds: Option<i64>,
+    /// Ata la URL a la IP del cliente que la pide
+    #[serde(default)]
+    pub bind_ip: bool,
+    /// Sirve el archivo con `Content-Disposition: inline`
+    #[serde(default)]
+    pub inline: bool,
+}
+
+#[derive(Serialize)]
+pub struct SignedUrlResponse {
+    pub success: bool,
+    pub message: String,
+    /// Ruta relativa al servidor, con la firma en el query string
+    pub url: Option<String>,
+    pub expires_at: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateShareRequest {
+    /// Horas hasta que el enlace expira; sin valor no expira
+    #[serde(default)]
+    pub expires_in_hours: Option<i64>,
+    /// Descargas permitidas; sin valor no hay límite
+    #[serde(default)]
+    pub max_downloads: Option<i32>,
+    /// Contraseña que se pedirá al descargar
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ShareLinkInfo {
+    pub token: String,
+    pub file_id: String,
+    /// Ruta pública de descarga del enlace
+    pub url: String,
+    pub has_password: bool,
+    pub expires_at: Option<i64>,
+    pub max_downloads: Option<i32>,
+    pub download_count: i32,
+    pub created_at: i64,
+}
+
+#[derive(Serialize)]
+pub struct ShareLinkResponse {
+    pub success: bool,
+    pub message: String,
+    pub link: Option<ShareLinkInfo>,
+}
+
+#[derive(Serialize)]
+pub struct ShareLinkListResponse {
+    pub success: bool,
+    pub message: String,
+    pub links: Vec<ShareLinkInfo>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateUploadLinkRequest {
+    /// Horas hasta que el enlace expira; sin valor no expira
+    #[serde(default)]
+    pub expires_in_hours: Option<i64>,
+    /// Archivos que se pueden subir; sin valor no hay límite
+    #[serde(default)]
+    pub max_files: Option<i32>,
+    /// Tamaño máximo de cada archivo; sin valor rige `max_upload_size_mb`
+    #[serde(default)]
+    pub max_file_size: Option<i64>,
+    /// Tipos MIME aceptados (`image/png` o `image/*`); vacío acepta cualquiera
+    #[serde(default)]
+    pub allowed_mime_types: Vec<String>,
+    /// Contraseña que se pedirá al subir
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct UploadLinkInfo {
+    pub token: String,
+    pub folder_id: String,
+    /// Ruta pública de subida del enlace
+    pub url: String,
+    pub has_password: bool,
+    pub expires_at: Option<i64>,
+    pub max_files: Option<i32>,
+    pub max_file_size: Option<i64>,
+    pub allowed_mime_types: Vec<String>,
+    pub upload_count: i32,
+    pub created_at: i64,
+}
+
+#[derive(Serialize)]
+pub struct UploadLinkResponse {
+    pub success: bool,
+    pub message: String,
+    pub link: Option<UploadLinkInfo>,
+}
+
+#[derive(Serialize)]
+pub struct UploadLinkListResponse {
+    pub success: bool,
+    pub message: String,
+    pub links: Vec<UploadLinkInfo>,
+}
+
+/// Lo que ve quien sube por un enlace: solo las condiciones de la subida,
+/// nunca la carpeta ni su contenido
+#[derive(Serialize)]
+pub struct PublicUploadLinkInfo {
+    pub has_password: bool,
+    pub expires_at: Option<i64>,
+    /// Archivos que todavía se pueden subir; `None` = sin límite
+    pub remaining_files: Option<i32>,
+    /// Tamaño máximo de cada archivo en bytes
+    pub max_file_size: u64,
+    pub allowed_mime_types: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct PublicUploadLinkResponse {
+    pub success: bool,
+    pub message: String,
+    pub link: Option<PublicUploadLinkInfo>,
+}
+
+#[derive(Serialize)]
+pub struct UploadLinkFileInfo {
+    pub file: FileInfo,
+    /// Nombre que dio quien subió el archivo
+    pub uploader_name: Option<String>,
+    pub uploaded_at: i64,
+}
+
+#[derive(Serialize)]
+pub struct UploadLinkFilesResponse {
+    pub success: bool,
+    pub message: String,
+    pub uploads: Vec<UploadLinkFileInfo>,
+}
+
+#[derive(Deserialize)]
+pub struct ArchiveRequest {
+    /// Archivos a incluir; excluyente con `folder_id`
+    #[serde(default)]
+    pub file_ids: Vec<String>,
+    /// Carpeta a descargar con todas sus subcarpetas
+    pub folder_id: Option<String>,
+}
+
+/// Operación de `/api/files/batch`, identificada por el campo `op`
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation {
+    /// Envía el archivo a la papelera
+    Delete {
+        file_id: String,
+    },
+    /// Mueve el archivo a otra carpeta; `null` = raíz
+    Move {
+        file_id: String,
+        #[serde(default)]
+        folder_id: Option<String>,
+    },
+    Rename {
+        file_id: String,
+        name: String,
+    },
+    /// Agrega y quita etiquetas del archivo
+    Tag {
+        file_id: String,
+        #[serde(default)]
+        add: Vec<String>,
+        #[serde(default)]
+        remove: Vec<String>,
+    },
+}
+
+impl BatchOperation {
+    pub fn file_id(&self) -> &str {
+        match self {
+            BatchOperation::Delete { file_id }
+            | BatchOperation::Move { file_id, .. }
+            | BatchOperation::Rename { file_id, .. }
+            | BatchOperation::Tag { file_id, .. } => file_id,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BatchRequest {
+    pub operations: Vec<BatchOperation>,
+}
+
+/// Resultado de una operación del lote, en el mismo orden que la petición
+#[derive(Serialize)]
+pub struct BatchItemResult {
+    pub index: usize,
+    pub file_id: String,
+    pub success: bool,
+    /// Status HTTP que habría devuelto la operación por separado
+    pub status: u16,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct BatchResponse {
+    /// `true` solo si todas las operaciones se aplicaron
+    pub success: bool,
+    pub message: String,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<BatchItemResult>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -74,9 +854,183 @@ pub struct LoginCredentials {
     pub(crate) password: String,
 }
 
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    pub(crate) current_password: String,
+    pub(crate) new_password: String,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct AuthResponse {
     pub(crate) sucess: bool,
     pub(crate) message: String,
     pub(crate) token: String,
 }
+
+#[derive(Serialize, Clone, Default)]
+pub struct ScrubReport {
+    pub started_at: i64,
+    pub finished_at: i64,
+    pub checked: u64,
+    pub ok: u64,
+    pub corrupt: u64,
+    pub missing: u64,
+}
+
+#[derive(Serialize)]
+pub struct IntegrityIssueInfo {
+    pub hash: String,
+    pub status: String,
+    pub detail: String,
+    pub detected_at: i64,
+    pub file_ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct IntegrityResponse {
+    pub success: bool,
+    pub message: String,
+    pub scrub_running: bool,
+    pub last_report: Option<ScrubReport>,
+    pub issues: Vec<IntegrityIssueInfo>,
+}
+
+#[derive(Serialize, Default)]
+pub struct StorageUsage {
+    pub used_bytes: u64,
+    /// `None` si el usuario no tiene límite
+    pub quota_bytes: Option<u64>,
+    pub available_bytes: Option<u64>,
+    /// Indica si la cuota es propia del usuario y no la de por defecto
+    pub custom_quota: bool,
+}
+
+#[derive(Serialize)]
+pub struct UsageResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(flatten)]
+    pub usage: StorageUsage,
+}
+
+#[derive(Deserialize)]
+pub struct QuotaUpdate {
+    /// Cuota en bytes; `null` vuelve a la cuota por defecto
+    pub quota_bytes: Option<u64>,
+}
+
+/// Cuenta vista por un administrador
+#[derive(Serialize)]
+pub struct AdminUserInfo {
+    pub id: String,
+    pub username: String,
+    /// `user` o `admin`
+    pub role: String,
+    pub disabled: bool,
+    pub disabled_at: Option<i64>,
+    pub password_reset_required: bool,
+    pub usage: StorageUsage,
+}
+
+#[derive(Serialize)]
+pub struct AdminUserResponse {
+    pub success: bool,
+    pub message: String,
+    pub user: Option<AdminUserInfo>,
+}
+
+#[derive(Serialize)]
+pub struct AdminUserListResponse {
+    pub success: bool,
+    pub message: String,
+    pub users: Vec<AdminUserInfo>,
+}
+
+#[derive(Deserialize)]
+pub struct UserRoleUpdate {
+    /// `user` o `admin`
+    pub role: String,
+}
+
+#[derive(Serialize)]
+pub struct PasswordResetResponse {
+    pub success: bool,
+    pub message: String,
+    /// Contraseña temporal para entregar al usuario; solo se muestra una vez
+    pub temporary_password: Option<String>,
+}
+
+/// Totales de almacenamiento de todo el servidor
+#[derive(Serialize, Default)]
+pub struct ServerStorage {
+    pub users: u64,
+    pub disabled_users: u64,
+    pub teams: u64,
+    /// Archivos activos, sin contar la papelera
+    pub files: u64,
+    pub trashed_files: u64,
+    pub file_versions: u64,
+    /// Suma del uso de usuarios y equipos, contando duplicados
+    pub used_bytes: u64,
+    pub blobs: u64,
+    /// Tamaño de los blobs únicos que ocupan espacio real en el storage
+    pub blob_bytes: u64,
+}
+
+#[derive(Serialize)]
+pub struct ServerStorageResponse {
+    pub success: bool,
+    pub message: String,
+    pub storage: ServerStorage,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct ReconcileReport {
+    pub dry_run: bool,
+    pub started_at: i64,
+    pub finished_at: i64,
+    /// Blobs en el storage sin registro en la DB (ya fuera del período de gracia)
+    pub orphan_blobs: Vec<String>,
+    /// Blobs sin registro que todavía están dentro del período de gracia
+    pub recent_orphans: u64,
+    /// Registros de blobs sin ningún archivo que los referencie
+    pub unreferenced_blobs: Vec<String>,
+    pub refcount_fixes: Vec<RefcountFix>,
+    /// Manifiestos de trozos cuyo blob ya no tiene registro
+    pub orphan_manifests: Vec<String>,
+    /// Trozos sin ningún manifiesto que los referencie
+    pub unreferenced_chunks: Vec<String>,
+    pub chunk_refcount_fixes: Vec<RefcountFix>,
+    pub dangling_files: Vec<DanglingFile>,
+    pub usage_fixes: Vec<UsageFix>,
+    /// Temporales abandonados en el directorio de staging
+    pub stale_temp_files: Vec<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct RefcountFix {
+    pub hash: String,
+    pub recorded: i32,
+    pub actual: i32,
+}
+
+#[derive(Serialize, Clone)]
+pub struct UsageFix {
+    pub user_id: String,
+    pub recorded: i64,
+    pub actual: i64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct DanglingFile {
+    pub file_id: String,
+    pub hash: String,
+    pub reason: String,
+}
+
+#[derive(Serialize)]
+pub struct ReconcileResponse {
+    pub success: bool,
+    pub message: String,
+    pub report: Option<ReconcileReport>,
+}
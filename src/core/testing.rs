@@ -0,0 +1,79 @@
+// src/core/testing.rs
+// ── Entorno compartido por las pruebas ───────────────────────────────
+use once_cell::sync::Lazy;
+use std::path::PathBuf;
+use tokio::sync::{Mutex, MutexGuard};
+use uuid::Uuid;
+
+use crate::core::database::{init_db_manager, run_migrations};
+use crate::core::storage::{MemoryStorage, STORAGE};
+use crate::core::structs::NuevoUsuario;
+use crate::core::utils::{CONFIG, Config, staging_path};
+
+/// Directorio temporal propio de cada prueba, borrado al salir
+pub(crate) struct TempDir(pub PathBuf);
+
+impl TempDir {
+    pub(crate) fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("privafile-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Las pruebas que usan la DB global se ejecutan de a una, para que SQLite
+/// no devuelva `database is locked`
+static ENTORNO: Lazy<Mutex<()>> = Lazy::new(|| {
+    let base = std::env::temp_dir().join(format!("privafile-test-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&base).unwrap();
+    let ruta = |nombre: &str| base.join(nombre).to_string_lossy().into_owned();
+
+    // Los archivos de 1 MiB o más se guardan por trozos
+    let config: Config = toml::from_str(&format!(
+        "uploads_path = {:?}\n\
+         http_port = 0\n\
+         database_url = {:?}\n\
+         paseto_keys_path = {:?}\n\
+         master_key_path = {:?}\n\
+         chunking_threshold_mb = 1\n",
+        ruta("Uploads"),
+        ruta("Privafile.db"),
+        ruta("paseto.key"),
+        ruta("master.key"),
+    ))
+    .unwrap();
+    assert!(CONFIG.set(config).is_ok(), "CONFIG ya inicializado");
+    std::fs::create_dir_all(staging_path()).unwrap();
+    assert!(
+        STORAGE.set(Box::new(MemoryStorage::new())).is_ok(),
+        "STORAGE ya inicializado"
+    );
+    run_migrations();
+    Mutex::new(())
+});
+
+/// Prepara (una sola vez) la configuración, la DB y el storage en memoria
+/// globales, y bloquea el entorno hasta que se suelta el guard
+pub(crate) async fn test_env() -> MutexGuard<'static, ()> {
+    ENTORNO.lock().await
+}
+
+/// Registra un usuario con un nombre único y devuelve su ID
+pub(crate) fn create_test_user() -> String {
+    let user_id = Uuid::new_v4().to_string();
+    init_db_manager()
+        .insertar_usuario(&NuevoUsuario {
+            id: &user_id,
+            username: &format!("test-{}", user_id),
+            password: "sin-login",
+            b64_pubkey: None,
+        })
+        .unwrap();
+    user_id
+}
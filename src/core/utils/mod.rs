@@ -5,7 +5,7 @@ use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use tokio::{
     fs::{self, File},
-    io::AsyncWriteExt,
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
 };
 use tracing::{error, info};
 #[derive(Debug, Deserialize, Serialize)]
@@ -14,6 +14,114 @@ pub struct Config {
     pub http_port: u16,
     pub database_url: String,
     pub paseto_keys_path: String,
+    /// Tamaño máximo de subida en MiB
+    #[serde(default = "default_max_upload_size_mb")]
+    pub max_upload_size_mb: u64,
+    /// Backend de almacenamiento: por ahora solo "local" (en `uploads_path`)
+    #[serde(default = "default_storage_backend")]
+    pub storage_backend: String,
+    /// Clave maestra que envuelve las claves de cifrado de cada blob
+    #[serde(default = "default_master_key_path")]
+    pub master_key_path: String,
+    /// Horas sin actividad tras las que se descarta una subida reanudable
+    #[serde(default = "default_tus_expiration_hours")]
+    pub tus_expiration_hours: i64,
+    /// Cada cuántas horas se verifica la integridad de los blobs (0 = nunca)
+    #[serde(default = "default_scrub_interval_hours")]
+    pub scrub_interval_hours: u64,
+    /// Cuota de almacenamiento por usuario en MiB (0 = sin límite); se puede
+    /// sobrescribir por usuario
+    #[serde(default = "default_quota_mb")]
+    pub default_quota_mb: u64,
+    /// Cada cuántas horas se reconcilia la DB con el storage (0 = nunca)
+    #[serde(default = "default_reconcile_interval_hours")]
+    pub reconcile_interval_hours: u64,
+    /// Antigüedad mínima (horas) de un blob sin registro antes de borrarlo
+    #[serde(default = "default_orphan_grace_hours")]
+    pub orphan_grace_hours: i64,
+    /// Días que un archivo permanece en la papelera antes de eliminarse
+    /// definitivamente (0 = no se purga automáticamente)
+    #[serde(default = "default_trash_retention_days")]
+    pub trash_retention_days: i64,
+    /// Nivel de compresión zstd (1-22) para los tipos comprimibles (0 = sin
+    /// compresión)
+    #[serde(default = "default_compression_level")]
+    pub compression_level: i32,
+    /// Tipos MIME que se comprimen antes de guardarse; `tipo/*` cubre todos
+    /// los subtipos
+    #[serde(default = "default_compressible_mime_types")]
+    pub compressible_mime_types: Vec<String>,
+    /// Tamaño mínimo en MiB a partir del cual un archivo se guarda repartido
+    /// en trozos por contenido, compartidos con versiones y archivos parecidos
+    /// (0 = desactivado)
+    #[serde(default = "default_chunking_threshold_mb")]
+    pub chunking_threshold_mb: u64,
+    /// IDs de usuario que reciben el rol de administrador al arrancar, para
+    /// crear el primer administrador. Quitar un ID de la lista no le quita
+    /// el rol; eso se hace desde `/api/admin/users/<id>/role` o con
+    /// `privafile admin revoke <username>`.
+    #[serde(default)]
+    pub admin_user_ids: Vec<String>,
+}
+
+fn default_max_upload_size_mb() -> u64 {
+    100
+}
+
+fn default_storage_backend() -> String {
+    "local".to_string()
+}
+
+fn default_master_key_path() -> String {
+    "./Privafile/master.key".to_string()
+}
+
+fn default_tus_expiration_hours() -> i64 {
+    24
+}
+
+fn default_scrub_interval_hours() -> u64 {
+    24
+}
+
+fn default_quota_mb() -> u64 {
+    0
+}
+
+fn default_reconcile_interval_hours() -> u64 {
+    24
+}
+
+fn default_orphan_grace_hours() -> i64 {
+    24
+}
+
+fn default_trash_retention_days() -> i64 {
+    30
+}
+
+fn default_compression_level() -> i32 {
+    3
+}
+
+fn default_chunking_threshold_mb() -> u64 {
+    0
+}
+
+fn default_compressible_mime_types() -> Vec<String> {
+    [
+        "text/*",
+        "application/json",
+        "application/x-ndjson",
+        "application/xml",
+        "application/javascript",
+        "application/sql",
+        "application/x-yaml",
+        "image/svg+xml",
+    ]
+    .into_iter()
+    .map(str::to_string)
+    .collect()
 }
 
 pub static CONFIG: OnceCell<Config> = OnceCell::new();
@@ -29,6 +137,19 @@ pub async fn create_toml_file() -> anyhow::Result<()> {
             http_port: 5830,
             database_url: "./Privafile/Privafile.db".to_string(),
             paseto_keys_path: "./Privafile/paseto.key".to_string(),
+            max_upload_size_mb: default_max_upload_size_mb(),
+            storage_backend: default_storage_backend(),
+            master_key_path: default_master_key_path(),
+            tus_expiration_hours: default_tus_expiration_hours(),
+            scrub_interval_hours: default_scrub_interval_hours(),
+            default_quota_mb: default_quota_mb(),
+            reconcile_interval_hours: default_reconcile_interval_hours(),
+            orphan_grace_hours: default_orphan_grace_hours(),
+            trash_retention_days: default_trash_retention_days(),
+            compression_level: default_compression_level(),
+            compressible_mime_types: default_compressible_mime_types(),
+            chunking_threshold_mb: default_chunking_threshold_mb(),
+            admin_user_ids: vec![],
         };
         let toml_string = toml::to_string_pretty(&default_config)?;
         fs::write(&config_path, toml_string)
@@ -121,6 +242,15 @@ pub async fn check_temp_perms() -> anyhow::Result<()> {
         info!("Directorio creado en {:?}", path);
     }
 
+    // Directorio para subidas en curso
+    let staging = staging_path();
+    if !staging.exists() {
+        fs::create_dir_all(&staging)
+            .await
+            .unwrap_or_else(|e| panic!("No se pudo crear el directorio {:?}: {:?}", staging, e));
+        info!("Directorio temporal creado en {:?}", staging);
+    }
+
     // Verificar permisos
     match fs::metadata(&path).await {
         Ok(metadata) => {
@@ -168,6 +298,145 @@ pub fn paseto_keys_path() -> String {
             "./Privafile/Paseto_privafile.key".to_string()
         })
 }
+pub fn master_key_path() -> String {
+    CONFIG
+        .get()
+        .map(|c| c.master_key_path.clone())
+        .unwrap_or_else(|| {
+            error!("Se intentó obtener la clave maestra del servidor, pero CONFIG no está inicializado. Usando default");
+            default_master_key_path()
+        })
+}
+
+pub fn uploads_path() -> String {
+    CONFIG
+        .get()
+        .map(|c| c.uploads_path.clone())
+        .unwrap_or_else(|| {
+            error!("Se intentó obtener la ruta de subidas, pero CONFIG no está inicializado. Usando default");
+            "./Privafile/Uploads".to_string()
+        })
+}
+
+/// Directorio donde se escriben las subidas antes de publicarlas en el storage
+pub fn staging_path() -> PathBuf {
+    PathBuf::from(uploads_path()).join("tmp")
+}
+
+pub fn storage_backend() -> String {
+    CONFIG
+        .get()
+        .map(|c| c.storage_backend.clone())
+        .unwrap_or_else(default_storage_backend)
+}
+
+pub fn tus_expiration_hours() -> i64 {
+    CONFIG
+        .get()
+        .map(|c| c.tus_expiration_hours)
+        .unwrap_or_else(default_tus_expiration_hours)
+}
+
+pub fn scrub_interval_hours() -> u64 {
+    CONFIG
+        .get()
+        .map(|c| c.scrub_interval_hours)
+        .unwrap_or_else(default_scrub_interval_hours)
+}
+
+pub fn reconcile_interval_hours() -> u64 {
+    CONFIG
+        .get()
+        .map(|c| c.reconcile_interval_hours)
+        .unwrap_or_else(default_reconcile_interval_hours)
+}
+
+pub fn orphan_grace_hours() -> i64 {
+    CONFIG
+        .get()
+        .map(|c| c.orphan_grace_hours)
+        .unwrap_or_else(default_orphan_grace_hours)
+}
+
+pub fn trash_retention_days() -> i64 {
+    CONFIG
+        .get()
+        .map(|c| c.trash_retention_days)
+        .unwrap_or_else(default_trash_retention_days)
+}
+
+/// Nivel de compresión zstd para un archivo de tipo `mime`, o `None` si ese
+/// tipo no se comprime
+pub fn compression_level_for(mime: &str) -> Option<i32> {
+    let mime = mime
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let (nivel, comprimible) = match CONFIG.get() {
+        Some(c) => (
+            c.compression_level,
+            mime_matches(&c.compressible_mime_types, &mime),
+        ),
+        None => (
+            default_compression_level(),
+            mime_matches(&default_compressible_mime_types(), &mime),
+        ),
+    };
+    (nivel > 0 && comprimible).then_some(nivel.min(22))
+}
+
+fn mime_matches(patrones: &[String], mime: &str) -> bool {
+    patrones.iter().any(|patron| {
+        let patron = patron.trim().to_ascii_lowercase();
+        match patron.strip_suffix("/*") {
+            Some(tipo) => mime.split('/').next() == Some(tipo),
+            None => patron == mime,
+        }
+    })
+}
+
+/// Tamaño en bytes a partir del cual los archivos se guardan por trozos, o
+/// `None` si el almacenamiento por trozos está desactivado
+pub fn chunking_threshold() -> Option<u64> {
+    let mb = CONFIG
+        .get()
+        .map(|c| c.chunking_threshold_mb)
+        .unwrap_or_else(default_chunking_threshold_mb);
+    (mb > 0).then(|| mb * 1024 * 1024)
+}
+
+/// IDs de usuario que se promueven a administradores al arrancar
+pub fn admin_user_ids() -> Vec<String> {
+    CONFIG
+        .get()
+        .map(|c| c.admin_user_ids.clone())
+        .unwrap_or_default()
+}
+
+/// Cuota por defecto en bytes, o `None` si no hay límite
+pub fn default_quota() -> Option<u64> {
+    let mb = CONFIG
+        .get()
+        .map(|c| c.default_quota_mb)
+        .unwrap_or_else(default_quota_mb);
+    (mb > 0).then(|| mb * 1024 * 1024)
+}
+
+/// Tamaño máximo de subida en bytes
+pub fn max_upload_size() -> u64 {
+    CONFIG
+        .get()
+        .map(|c| c.max_upload_size_mb)
+        .unwrap_or_else(|| {
+            error!("Se intentó obtener el tamaño máximo de subida, pero CONFIG no está inicializado. Usando default (100 MiB)");
+            default_max_upload_size_mb()
+        })
+        * 1024
+        * 1024
+}
+
 pub async fn write_file(path: impl AsRef<Path>, datos: &[u8]) -> Result<()> {
     let mut archivo = File::create(&path)
         .await
@@ -183,3 +452,49 @@ pub async fn write_file(path: impl AsRef<Path>, datos: &[u8]) -> Result<()> {
     archivo.flush().await?;
     Ok(())
 }
+
+/// Copia un stream a disco en bloques de `CHUNK_SIZE`, llamando a `on_chunk`
+/// con cada bloque antes de escribirlo.
+///
+/// Falla si el stream supera `limit` bytes. Devuelve el total de bytes escritos.
+pub async fn write_stream<R, F>(
+    path: impl AsRef<Path>,
+    reader: &mut R,
+    limit: u64,
+    mut on_chunk: F,
+) -> Result<u64>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    F: FnMut(&[u8]),
+{
+    let mut archivo = File::create(&path)
+        .await
+        .with_context(|| format!("No se pudo crear el archivo {:?}", path.as_ref()))?;
+
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut total: u64 = 0;
+    loop {
+        let leidos = reader
+            .read(&mut buffer)
+            .await
+            .context("Error al leer datos del stream")?;
+        if leidos == 0 {
+            break;
+        }
+
+        total += leidos as u64;
+        if total > limit {
+            anyhow::bail!(
+                "El archivo excede el tamaño máximo permitido ({} bytes)",
+                limit
+            );
+        }
+
+        on_chunk(&buffer[..leidos]);
+        archivo.write_all(&buffer[..leidos]).await?;
+    }
+
+    archivo.flush().await?;
+    archivo.sync_all().await?;
+    Ok(total)
+}
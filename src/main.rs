@@ -1,13 +1,18 @@
-use anyhow;
-
 // External crates
-use tokio;
 use tracing::info;
 use tracing_subscriber::EnvFilter;
 
 // Internal crates
 use privafile::{
-    core::{check_temp_perms, load_config, run_migrations},
+    core::{
+        check_temp_perms,
+        jobs::spawn_background_jobs,
+        load_config,
+        procedures::{bootstrap_admins, set_admin_by_username},
+        run_migrations,
+        storage::blobs::{encrypt_existing_blobs, migrate_legacy_blobs},
+        storage::reconcile::reconcile_storage,
+    },
     servers::http::start_server,
 };
 
@@ -22,6 +27,42 @@ async fn main() -> anyhow::Result<()> {
     load_config().await?;
     check_temp_perms().await?;
     run_migrations();
+    bootstrap_admins()?;
+    migrate_legacy_blobs().await?;
+    encrypt_existing_blobs().await?;
+
+    // `privafile reconcile [--dry-run]`: reconcilia DB y storage y termina
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("reconcile") {
+        let dry_run = args.iter().any(|a| a == "--dry-run");
+        let reporte = reconcile_storage(dry_run).await?;
+        println!("{}", serde_json::to_string_pretty(&reporte)?);
+        return Ok(());
+    }
+
+    // `privafile admin grant|revoke <username>`: da o quita el rol de
+    // administrador, p. ej. para crear el primero, y termina
+    if args.first().map(String::as_str) == Some("admin") {
+        let (admin, username) = match (args.get(1).map(String::as_str), args.get(2)) {
+            (Some("grant"), Some(username)) => (true, username),
+            (Some("revoke"), Some(username)) => (false, username),
+            _ => anyhow::bail!("Uso: privafile admin grant|revoke <username>"),
+        };
+        let user_id = set_admin_by_username(username, admin)?;
+        println!(
+            "{} ({}) {}",
+            username,
+            user_id,
+            if admin {
+                "ahora es administrador"
+            } else {
+                "ya no es administrador"
+            }
+        );
+        return Ok(());
+    }
+
+    spawn_background_jobs();
     info!("Iniciando servidor...");
     start_server().launch().await?;
 
@@ -0,0 +1,614 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::DateTime;
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::{self, Responder, Response};
+use tokio::io::AsyncReadExt;
+use uuid::Uuid;
+
+use crate::core::procedures::open_file_range;
+use crate::core::storage::BlobReader;
+use crate::core::structs::FileDownload;
+
+/// Máximo de rangos aceptados en un mismo `Range`; si se piden más se
+/// responde el archivo completo
+const MAX_RANGES: usize = 16;
+
+// ============================================================================
+// Headers de la petición
+// ============================================================================
+
+/// Headers condicionales y de rango de una descarga
+pub struct DownloadHeaders {
+    range: Option<String>,
+    if_range: Option<String>,
+    if_none_match: Option<String>,
+    if_modified_since: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for DownloadHeaders {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let headers = request.headers();
+        request::Outcome::Success(DownloadHeaders {
+            range: headers.get_one("Range").map(str::to_string),
+            if_range: headers.get_one("If-Range").map(str::to_string),
+            if_none_match: headers.get_one("If-None-Match").map(str::to_string),
+            if_modified_since: headers.get_one("If-Modified-Since").map(str::to_string),
+        })
+    }
+}
+
+/// ETag fuerte de un archivo, derivado de su hash de contenido
+pub fn etag_for(file: &FileDownload) -> String {
+    format!("\"{}\"", file.hash)
+}
+
+/// Fecha en formato HTTP (IMF-fixdate) a partir de un timestamp Unix
+fn http_date(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+/// Timestamp Unix de una fecha HTTP; `None` si no es válida
+fn parse_http_date(valor: &str) -> Option<i64> {
+    DateTime::parse_from_rfc2822(valor.trim())
+        .ok()
+        .map(|fecha| fecha.timestamp())
+}
+
+/// `Repr-Digest`/`Content-Digest` (RFC 9530) a partir del SHA-256 en hex
+fn sha256_digest(file: &FileDownload) -> Option<String> {
+    let bytes = hex::decode(file.sha256.as_deref()?).ok()?;
+    Some(format!("sha-256=:{}:", STANDARD.encode(bytes)))
+}
+
+/// Validadores comunes a todas las respuestas de descarga
+fn cache_headers(file: &FileDownload, etag: &str) -> Vec<Header<'static>> {
+    let mut headers = vec![
+        Header::new("ETag", etag.to_string()),
+        Header::new("Last-Modified", http_date(file.modified_at)),
+        // El contenido es privado y debe revalidarse antes de reutilizarse
+        Header::new("Cache-Control", "private, no-cache"),
+    ];
+    if let Some(digest) = sha256_digest(file) {
+        headers.push(Header::new("Repr-Digest", digest));
+    }
+    headers
+}
+
+/// `Content-Disposition` con el nombre original (RFC 6266): un `filename`
+/// ASCII de respaldo y `filename*` con el nombre completo en UTF-8
+fn content_disposition(filename: &str, inline: bool) -> String {
+    let tipo = if inline { "inline" } else { "attachment" };
+
+    let respaldo: String = filename
+        .chars()
+        .map(|c| {
+            if c == ' ' || (c.is_ascii_graphic() && c != '"' && c != '\\' && c != '%') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    // attr-char de RFC 8187; el resto se codifica con %XX
+    let mut codificado = String::with_capacity(filename.len());
+    for byte in filename.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            codificado.push(byte as char);
+        } else {
+            codificado.push_str(&format!("%{:02X}", byte));
+        }
+    }
+
+    format!(
+        "{}; filename=\"{}\"; filename*=UTF-8''{}",
+        tipo, respaldo, codificado
+    )
+}
+
+// ============================================================================
+// Parseo de Range (RFC 9110 §14)
+// ============================================================================
+
+#[derive(Debug, PartialEq)]
+enum RangeRequest {
+    /// Sin rango válido: se responde el archivo completo
+    Full,
+    /// Rangos satisfacibles, como (inicio, fin) inclusivos
+    Partial(Vec<(u64, u64)>),
+    /// Ningún rango se solapa con el archivo
+    Unsatisfiable,
+}
+
+fn parse_range(header: &str, size: u64) -> RangeRequest {
+    let Some(specs) = header.trim().strip_prefix("bytes=") else {
+        return RangeRequest::Full;
+    };
+
+    let specs: Vec<&str> = specs
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+    if specs.is_empty() {
+        return RangeRequest::Full;
+    }
+
+    let mut ranges = Vec::new();
+    for spec in specs {
+        let Some((inicio, fin)) = spec.split_once('-') else {
+            return RangeRequest::Full;
+        };
+
+        let rango = match (inicio.trim(), fin.trim()) {
+            // "-N": los últimos N bytes
+            ("", sufijo) => match sufijo.parse::<u64>() {
+                Ok(0) => None,
+                Ok(n) => (size > 0).then(|| (size - n.min(size), size - 1)),
+                Err(_) => return RangeRequest::Full,
+            },
+            // "A-" o "A-B"
+            (inicio, fin) => {
+                let Ok(inicio) = inicio.parse::<u64>() else {
+                    return RangeRequest::Full;
+                };
+                let fin = if fin.is_empty() {
+                    u64::MAX
+                } else {
+                    match fin.parse::<u64>() {
+                        Ok(fin) if fin >= inicio => fin,
+                        _ => return RangeRequest::Full,
+                    }
+                };
+                (inicio < size).then(|| (inicio, fin.min(size - 1)))
+            }
+        };
+
+        if let Some(rango) = rango {
+            ranges.push(rango);
+        }
+    }
+
+    if ranges.is_empty() {
+        RangeRequest::Unsatisfiable
+    } else if ranges.len() > MAX_RANGES {
+        RangeRequest::Full
+    } else {
+        RangeRequest::Partial(ranges)
+    }
+}
+
+/// `If-Range` solo aplica si coincide exactamente con el ETag fuerte actual
+/// o con la fecha exacta de `Last-Modified`; si no, se ignora el rango.
+fn if_range_matches(if_range: Option<&str>, etag: &str, modified_at: i64) -> bool {
+    match if_range {
+        None => true,
+        Some(valor) if valor.trim().starts_with('"') => valor.trim() == etag,
+        Some(valor) => parse_http_date(valor) == Some(modified_at),
+    }
+}
+
+// ============================================================================
+// Peticiones condicionales (RFC 9110 §13)
+// ============================================================================
+
+/// `If-None-Match` usa comparación débil: se ignora el prefijo `W/`
+fn if_none_match_matches(if_none_match: &str, etag: &str) -> bool {
+    if if_none_match.trim() == "*" {
+        return true;
+    }
+    if_none_match
+        .split(',')
+        .map(|candidato| candidato.trim())
+        .map(|candidato| candidato.strip_prefix("W/").unwrap_or(candidato))
+        .any(|candidato| candidato == etag)
+}
+
+/// Indica si el cliente ya tiene la versión actual. `If-Modified-Since`
+/// solo se evalúa cuando no hay `If-None-Match`.
+fn not_modified(headers: &DownloadHeaders, etag: &str, modified_at: i64) -> bool {
+    match (&headers.if_none_match, &headers.if_modified_since) {
+        (Some(if_none_match), _) => if_none_match_matches(if_none_match, etag),
+        (None, Some(fecha)) => parse_http_date(fecha).is_some_and(|fecha| modified_at <= fecha),
+        (None, None) => false,
+    }
+}
+
+// ============================================================================
+// Respuesta
+// ============================================================================
+
+/// Respuesta de descarga con soporte de rangos (200, 206, 304 o 416).
+/// Sin `body` solo se envían los headers (`HEAD` y 304).
+pub struct RangedDownload {
+    status: Status,
+    content_type: ContentType,
+    length: u64,
+    headers: Vec<Header<'static>>,
+    body: Option<BlobReader>,
+}
+
+impl<'r> Responder<'r, 'static> for RangedDownload {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .status(self.status)
+            .raw_header("Accept-Ranges", "bytes");
+        for header in self.headers {
+            response.header(header);
+        }
+        if self.status == Status::NotModified {
+            return response.ok();
+        }
+
+        response.header(self.content_type);
+        match self.body {
+            Some(body) => {
+                response
+                    .raw_header("Content-Length", self.length.to_string())
+                    .streamed_body(body);
+            }
+            // En un HEAD Rocket descarta el cuerpo pero conserva su tamaño
+            // como Content-Length
+            None => {
+                response.sized_body(self.length as usize, std::io::Cursor::new(Vec::new()));
+            }
+        }
+        response.ok()
+    }
+}
+
+/// Descarga de un ZIP generado al vuelo. Su tamaño no se conoce de antemano,
+/// así que se envía sin `Content-Length` y sin soporte de rangos.
+pub struct ArchiveDownload {
+    filename: String,
+    body: BlobReader,
+}
+
+impl ArchiveDownload {
+    pub fn new(filename: String, body: BlobReader) -> Self {
+        ArchiveDownload { filename, body }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ArchiveDownload {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .status(Status::Ok)
+            .header(ContentType::ZIP)
+            .raw_header(
+                "Content-Disposition",
+                content_disposition(&self.filename, false),
+            )
+            .raw_header("Cache-Control", "no-store")
+            .streamed_body(self.body)
+            .ok()
+    }
+}
+
+/// Rango que se sirve según `Range` e `If-Range`
+fn requested_range(file: &FileDownload, headers: &DownloadHeaders, etag: &str) -> RangeRequest {
+    match &headers.range {
+        Some(range) if if_range_matches(headers.if_range.as_deref(), etag, file.modified_at) => {
+            parse_range(range, file.size)
+        }
+        _ => RangeRequest::Full,
+    }
+}
+
+/// Indica si la respuesta a la petición entrega el comienzo del archivo: la
+/// descarga completa o un rango que empieza en el primer byte. Un 304, un
+/// 416 o un rango que continúa una descarga ya empezada no la entregan.
+pub fn starts_download(file: &FileDownload, headers: &DownloadHeaders) -> bool {
+    let etag = etag_for(file);
+    if not_modified(headers, &etag, file.modified_at) {
+        return false;
+    }
+    match requested_range(file, headers, &etag) {
+        RangeRequest::Full => true,
+        RangeRequest::Partial(ranges) => ranges.iter().any(|&(inicio, _)| inicio == 0),
+        RangeRequest::Unsatisfiable => false,
+    }
+}
+
+/// Construye la respuesta a un `HEAD`: los mismos headers que la descarga
+/// completa sin abrir el contenido
+pub fn serve_head(file: &FileDownload, inline: bool) -> RangedDownload {
+    let etag = etag_for(file);
+    let mut headers = cache_headers(file, &etag);
+    headers.push(Header::new(
+        "Content-Disposition",
+        content_disposition(&file.filename, inline),
+    ));
+    if let Some(digest) = sha256_digest(file) {
+        headers.push(Header::new("Content-Digest", digest));
+    }
+
+    RangedDownload {
+        status: Status::Ok,
+        content_type: ContentType::parse_flexible(&file.mime).unwrap_or(ContentType::Binary),
+        length: file.size,
+        headers,
+        body: None,
+    }
+}
+
+/// Construye la respuesta de descarga de `file` según los headers
+/// condicionales y de rango. Con `inline` el navegador puede mostrar el
+/// archivo en lugar de guardarlo.
+pub async fn serve_download(
+    file: &FileDownload,
+    headers: &DownloadHeaders,
+    inline: bool,
+) -> anyhow::Result<RangedDownload> {
+    let content_type = ContentType::parse_flexible(&file.mime).unwrap_or(ContentType::Binary);
+    let etag = etag_for(file);
+    let size = file.size;
+    let mut validadores = cache_headers(file, &etag);
+
+    if not_modified(headers, &etag, file.modified_at) {
+        return Ok(RangedDownload {
+            status: Status::NotModified,
+            content_type,
+            length: 0,
+            headers: validadores,
+            body: None,
+        });
+    }
+
+    let rango = requested_range(file, headers, &etag);
+
+    let disposition_header = Header::new(
+        "Content-Disposition",
+        content_disposition(&file.filename, inline),
+    );
+    match rango {
+        RangeRequest::Full => {
+            // Sin codificación de contenido el digest del cuerpo es el de la representación
+            validadores.push(disposition_header);
+            if let Some(digest) = sha256_digest(file) {
+                validadores.push(Header::new("Content-Digest", digest));
+            }
+            Ok(RangedDownload {
+                status: Status::Ok,
+                content_type,
+                length: size,
+                headers: validadores,
+                body: Some(open_file_range(file, 0, size).await?),
+            })
+        }
+        RangeRequest::Unsatisfiable => {
+            validadores.push(Header::new("Content-Range", format!("bytes */{}", size)));
+            Ok(RangedDownload {
+                status: Status::RangeNotSatisfiable,
+                content_type,
+                length: 0,
+                headers: validadores,
+                body: Some(Box::new(tokio::io::empty())),
+            })
+        }
+        RangeRequest::Partial(ranges) if ranges.len() == 1 => {
+            let (inicio, fin) = ranges[0];
+            validadores.push(disposition_header);
+            validadores.push(Header::new(
+                "Content-Range",
+                format!("bytes {}-{}/{}", inicio, fin, size),
+            ));
+            Ok(RangedDownload {
+                status: Status::PartialContent,
+                content_type,
+                length: fin - inicio + 1,
+                headers: validadores,
+                body: Some(open_file_range(file, inicio, fin - inicio + 1).await?),
+            })
+        }
+        RangeRequest::Partial(ranges) => {
+            // multipart/byteranges: cada parte con su propio Content-Range
+            let boundary = Uuid::new_v4().simple().to_string();
+            let mut length = 0;
+            let mut body: BlobReader = Box::new(tokio::io::empty());
+
+            for (inicio, fin) in ranges {
+                let cabecera = format!(
+                    "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+                    boundary, file.mime, inicio, fin, size
+                );
+                let parte = open_file_range(file, inicio, fin - inicio + 1).await?;
+                length += cabecera.len() as u64 + (fin - inicio + 1);
+                body = Box::new(body.chain(std::io::Cursor::new(cabecera)).chain(parte));
+            }
+
+            let cierre = format!("\r\n--{}--\r\n", boundary);
+            length += cierre.len() as u64;
+            body = Box::new(body.chain(std::io::Cursor::new(cierre)));
+
+            validadores.push(disposition_header);
+            Ok(RangedDownload {
+                status: Status::PartialContent,
+                content_type: ContentType::new("multipart", "byteranges")
+                    .with_params(("boundary", boundary)),
+                length,
+                headers: validadores,
+                body: Some(body),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RangeRequest::{Full, Partial, Unsatisfiable};
+
+    const ETAG: &str = "\"abc123\"";
+    const MODIFICADO: i64 = 1_700_000_000;
+
+    fn headers(if_none_match: Option<&str>, if_modified_since: Option<&str>) -> DownloadHeaders {
+        DownloadHeaders {
+            range: None,
+            if_range: None,
+            if_none_match: if_none_match.map(str::to_string),
+            if_modified_since: if_modified_since.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn parse_range_simple_y_multiple() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Partial(vec![(0, 99)]));
+        assert_eq!(parse_range(" bytes=10-10 ", 1000), Partial(vec![(10, 10)]));
+        assert_eq!(
+            parse_range("bytes=0-9, 20-29", 1000),
+            Partial(vec![(0, 9), (20, 29)])
+        );
+        // El fin se recorta al tamaño del archivo
+        assert_eq!(
+            parse_range("bytes=900-5000", 1000),
+            Partial(vec![(900, 999)])
+        );
+    }
+
+    #[test]
+    fn parse_range_sufijos() {
+        assert_eq!(parse_range("bytes=-100", 1000), Partial(vec![(900, 999)]));
+        // Un sufijo mayor que el archivo lo cubre entero
+        assert_eq!(parse_range("bytes=-5000", 1000), Partial(vec![(0, 999)]));
+        assert_eq!(parse_range("bytes=-0", 1000), Unsatisfiable);
+    }
+
+    #[test]
+    fn parse_range_abierto() {
+        assert_eq!(parse_range("bytes=500-", 1000), Partial(vec![(500, 999)]));
+        assert_eq!(parse_range("bytes=999-", 1000), Partial(vec![(999, 999)]));
+        // "A-" a partir del final no es satisfacible
+        assert_eq!(parse_range("bytes=1000-", 1000), Unsatisfiable);
+        assert_eq!(parse_range("bytes=5000-6000", 1000), Unsatisfiable);
+        // Los rangos fuera del archivo se descartan si hay otros válidos
+        assert_eq!(parse_range("bytes=5000-, 0-0", 1000), Partial(vec![(0, 0)]));
+    }
+
+    #[test]
+    fn parse_range_invalido_sirve_completo() {
+        for header in [
+            "items=0-10",
+            "bytes=",
+            "bytes=abc",
+            "bytes=10-5",
+            "bytes=a-10",
+            "bytes=0-10, x",
+        ] {
+            assert_eq!(parse_range(header, 1000), Full, "{:?}", header);
+        }
+    }
+
+    #[test]
+    fn parse_range_demasiados_rangos() {
+        let limite = (0..MAX_RANGES)
+            .map(|i| format!("{}-{}", i * 10, i * 10 + 1))
+            .collect::<Vec<_>>()
+            .join(",");
+        assert!(matches!(
+            parse_range(&format!("bytes={}", limite), 1000),
+            Partial(rangos) if rangos.len() == MAX_RANGES
+        ));
+
+        let exceso = format!("bytes={},900-901", limite);
+        assert_eq!(parse_range(&exceso, 1000), Full);
+    }
+
+    #[test]
+    fn parse_range_archivo_vacio() {
+        assert_eq!(parse_range("bytes=0-", 0), Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-0", 0), Unsatisfiable);
+        assert_eq!(parse_range("bytes=-10", 0), Unsatisfiable);
+    }
+
+    #[test]
+    fn if_range_compara_etag_fuerte_y_fecha() {
+        assert!(if_range_matches(None, ETAG, MODIFICADO));
+        assert!(if_range_matches(Some(ETAG), ETAG, MODIFICADO));
+        assert!(!if_range_matches(Some("\"otro\""), ETAG, MODIFICADO));
+        // Un ETag débil nunca habilita el rango
+        assert!(!if_range_matches(Some("W/\"abc123\""), ETAG, MODIFICADO));
+
+        let fecha = http_date(MODIFICADO);
+        assert_eq!(fecha, "Tue, 14 Nov 2023 22:13:20 GMT");
+        assert!(if_range_matches(Some(&fecha), ETAG, MODIFICADO));
+        assert!(!if_range_matches(Some(&fecha), ETAG, MODIFICADO + 1));
+        assert!(!if_range_matches(Some("no es una fecha"), ETAG, MODIFICADO));
+    }
+
+    #[test]
+    fn not_modified_con_if_none_match() {
+        assert!(not_modified(&headers(Some(ETAG), None), ETAG, MODIFICADO));
+        // Comparación débil: `W/` se ignora
+        assert!(not_modified(
+            &headers(Some("W/\"abc123\""), None),
+            ETAG,
+            MODIFICADO
+        ));
+        assert!(not_modified(
+            &headers(Some("\"otro\", \"abc123\""), None),
+            ETAG,
+            MODIFICADO
+        ));
+        assert!(not_modified(&headers(Some("*"), None), ETAG, MODIFICADO));
+        assert!(!not_modified(
+            &headers(Some("\"otro\""), None),
+            ETAG,
+            MODIFICADO
+        ));
+    }
+
+    #[test]
+    fn not_modified_con_if_modified_since() {
+        let fecha = http_date(MODIFICADO);
+        assert!(not_modified(&headers(None, Some(&fecha)), ETAG, MODIFICADO));
+        assert!(!not_modified(
+            &headers(None, Some(&fecha)),
+            ETAG,
+            MODIFICADO + 1
+        ));
+        assert!(!not_modified(
+            &headers(None, Some("basura")),
+            ETAG,
+            MODIFICADO
+        ));
+        assert!(!not_modified(&headers(None, None), ETAG, MODIFICADO));
+
+        // Con `If-None-Match` presente la fecha no se evalúa
+        assert!(!not_modified(
+            &headers(Some("\"otro\""), Some(&fecha)),
+            ETAG,
+            MODIFICADO
+        ));
+    }
+
+    #[test]
+    fn content_disposition_ascii() {
+        assert_eq!(
+            content_disposition("informe final.pdf", false),
+            "attachment; filename=\"informe final.pdf\"; filename*=UTF-8''informe%20final.pdf"
+        );
+        assert!(content_disposition("a.txt", true).starts_with("inline; "));
+    }
+
+    #[test]
+    fn content_disposition_codifica_rfc8187() {
+        assert_eq!(
+            content_disposition("año \"2024\" 100%.txt", false),
+            "attachment; filename=\"a_o _2024_ 100_.txt\"; \
+             filename*=UTF-8''a%C3%B1o%20%222024%22%20100%25.txt"
+        );
+        assert_eq!(
+            content_disposition("a\\b;c.txt", false),
+            "attachment; filename=\"a_b;c.txt\"; filename*=UTF-8''a%5Cb%3Bc.txt"
+        );
+    }
+}
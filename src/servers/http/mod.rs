@@ -3,6 +3,7 @@ use rocket_cors::{AllowedOrigins, CorsOptions};
 use tracing::info;
 // Internal crates
 use crate::core::{cryptography::authentication::PasetoManager, http_port, paseto_keys_path};
+mod download;
 mod routes;
 
 pub fn start_server() -> rocket::Rocket<rocket::Build> {
@@ -22,11 +23,36 @@ pub fn start_server() -> rocket::Rocket<rocket::Build> {
             rocket::http::Method::Post,
             rocket::http::Method::Put,
             rocket::http::Method::Delete,
+            rocket::http::Method::Patch,
+            rocket::http::Method::Head,
+            rocket::http::Method::Options,
         ]
         .into_iter()
         .map(From::from)
         .collect(),
         allowed_headers: rocket_cors::AllowedHeaders::all(),
+        expose_headers: [
+            "Accept-Ranges",
+            "Content-Range",
+            "Content-Length",
+            "Content-Disposition",
+            "ETag",
+            "Last-Modified",
+            "Content-Digest",
+            "Repr-Digest",
+            "Location",
+            "Tus-Resumable",
+            "Tus-Version",
+            "Tus-Extension",
+            "Tus-Max-Size",
+            "Upload-Offset",
+            "Upload-Length",
+            "Upload-Expires",
+            "Privafile-File-Id",
+        ]
+        .iter()
+        .map(|h| h.to_string())
+        .collect(),
         allow_credentials: true,
         ..Default::default()
     }
@@ -40,9 +66,76 @@ pub fn start_server() -> rocket::Rocket<rocket::Build> {
                 routes::upload_file_route,
                 routes::list_files_route,
                 routes::download_file_route,
+                routes::head_file_route,
+                routes::create_signed_url_route,
+                routes::signed_download_route,
+                routes::signed_head_route,
+                routes::download_archive_route,
+                routes::batch_route,
+                routes::rename_file_route,
+                routes::copy_file_route,
+                routes::duplicate_file_route,
                 routes::delete_file_route,
+                routes::usage_route,
+                routes::move_file_route,
+                routes::create_folder_route,
+                routes::folder_contents_route,
+                routes::rename_folder_route,
+                routes::move_folder_route,
+                routes::delete_folder_route,
+                routes::list_trash_route,
+                routes::restore_file_route,
+                routes::purge_file_route,
+                routes::empty_trash_route,
+                routes::upload_version_route,
+                routes::list_versions_route,
+                routes::download_version_route,
+                routes::promote_version_route,
+                routes::prune_versions_route,
+                routes::create_share_route,
+                routes::list_shares_route,
+                routes::revoke_share_route,
+                routes::public_download_route,
+                routes::public_head_route,
+                routes::create_upload_link_route,
+                routes::list_upload_links_route,
+                routes::revoke_upload_link_route,
+                routes::upload_link_files_route,
+                routes::public_upload_link_route,
+                routes::public_upload_route,
+                routes::create_grant_route,
+                routes::list_grants_route,
+                routes::revoke_grant_route,
+                routes::shared_with_me_route,
+                routes::create_team_route,
+                routes::list_teams_route,
+                routes::get_team_route,
+                routes::delete_team_route,
+                routes::list_team_members_route,
+                routes::set_team_member_route,
+                routes::remove_team_member_route,
+                routes::team_drive_route,
                 routes::login,
                 routes::register,
+                routes::change_password_route,
+                routes::tus_options,
+                routes::tus_create,
+                routes::tus_head,
+                routes::tus_patch,
+                routes::tus_delete,
+                routes::integrity_report_route,
+                routes::start_scrub_route,
+                routes::reconcile_route,
+                routes::set_quota_route,
+                routes::set_team_quota_route,
+                routes::list_users_route,
+                routes::get_user_route,
+                routes::set_user_role_route,
+                routes::disable_user_route,
+                routes::enable_user_route,
+                routes::reset_password_route,
+                routes::delete_user_route,
+                routes::server_storage_route,
             ],
         )
         .attach(cors)
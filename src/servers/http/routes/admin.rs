@@ -0,0 +1,620 @@
+use rocket::outcome::Outcome;
+use rocket::request::{self, FromRequest, Request};
+use rocket::serde::json::Json;
+use rocket::{delete, get, http::Status, post, put, response::status::Custom};
+use tracing::{Level, error, info, span, warn};
+
+use super::files::AuthenticatedUser;
+use crate::core::procedures::{
+    delete_user, get_user, is_admin_user, list_integrity_issues, list_users, reset_user_password,
+    server_storage, set_team_quota, set_user_disabled, set_user_quota, set_user_role,
+};
+use crate::core::storage::integrity::{last_scrub_report, scrub_blobs, scrub_running};
+use crate::core::storage::reconcile::reconcile_storage;
+use crate::core::structs::{
+    AdminUserListResponse, AdminUserResponse, DeleteResponse, IntegrityResponse,
+    PasswordResetResponse, QuotaUpdate, ReconcileResponse, ServerStorage, ServerStorageResponse,
+    StorageUsage, UsageResponse, UserRoleUpdate,
+};
+
+// ============================================================================
+// Admin Guard
+// ============================================================================
+
+/// Guard para endpoints de administración: sesión válida de un usuario con
+/// el rol `admin`
+pub struct AdminUser {
+    pub user_id: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminUser {
+    type Error = String;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let user = match request.guard::<AuthenticatedUser>().await {
+            Outcome::Success(user) => user,
+            Outcome::Error(e) => return Outcome::Error(e),
+            Outcome::Forward(f) => return Outcome::Forward(f),
+        };
+
+        if is_admin_user(&user.user_id).await {
+            Outcome::Success(AdminUser {
+                user_id: user.user_id,
+            })
+        } else {
+            warn!("Acceso de administración denegado a {}", user.user_id);
+            Outcome::Error((
+                Status::Forbidden,
+                "Se requieren permisos de administrador".to_string(),
+            ))
+        }
+    }
+}
+
+/// Traduce el mensaje de error de un procedure de administración de
+/// usuarios a un status HTTP
+fn admin_error_status(error_msg: &str) -> Status {
+    if error_msg.contains("no encontrad") {
+        Status::NotFound
+    } else if error_msg.contains("inválid") {
+        Status::BadRequest
+    } else if error_msg.contains("al menos un dueño") {
+        Status::Conflict
+    } else {
+        Status::InternalServerError
+    }
+}
+
+/// Respuesta de error común a las rutas que devuelven una cuenta
+fn user_error(e: anyhow::Error) -> Custom<Json<AdminUserResponse>> {
+    let error_msg = e.to_string();
+    let status = admin_error_status(&error_msg);
+    if status == Status::InternalServerError {
+        error!("Error al administrar usuario: {}", e);
+    }
+
+    Custom(
+        status,
+        Json(AdminUserResponse {
+            success: false,
+            message: error_msg,
+            user: None,
+        }),
+    )
+}
+
+// ============================================================================
+// Routes
+// ============================================================================
+
+/// Estado de integridad de los blobs almacenados
+///
+/// Endpoint: GET /api/admin/integrity
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token de un administrador>
+/// ```
+///
+/// Response: blobs corruptos o ausentes con los archivos afectados y el
+/// resumen de la última verificación
+#[get("/api/admin/integrity")]
+pub async fn integrity_report_route(
+    admin: AdminUser,
+) -> Result<Json<IntegrityResponse>, Custom<Json<IntegrityResponse>>> {
+    let span = span!(Level::INFO, "integrity_report_route");
+    let _enter = span.enter();
+
+    match list_integrity_issues().await {
+        Ok(issues) => {
+            info!("Admin {} consultó el estado de integridad", admin.user_id);
+            Ok(Json(IntegrityResponse {
+                success: true,
+                message: format!("{} blob(s) con problemas", issues.len()),
+                scrub_running: scrub_running(),
+                last_report: last_scrub_report(),
+                issues,
+            }))
+        }
+        Err(e) => {
+            error!("Error al obtener problemas de integridad: {}", e);
+            Err(Custom(
+                Status::InternalServerError,
+                Json(IntegrityResponse {
+                    success: false,
+                    message: format!("Error al obtener problemas de integridad: {}", e),
+                    scrub_running: scrub_running(),
+                    last_report: None,
+                    issues: vec![],
+                }),
+            ))
+        }
+    }
+}
+
+/// Lanza una verificación de integridad en segundo plano
+///
+/// Endpoint: POST /api/admin/integrity/scrub
+///
+/// Response: 202 si se lanzó, 409 si ya hay una en curso
+#[post("/api/admin/integrity/scrub")]
+pub async fn start_scrub_route(admin: AdminUser) -> Custom<Json<IntegrityResponse>> {
+    let span = span!(Level::INFO, "start_scrub_route");
+    let _enter = span.enter();
+
+    if scrub_running() {
+        return Custom(
+            Status::Conflict,
+            Json(IntegrityResponse {
+                success: false,
+                message: "Ya hay una verificación de integridad en curso".to_string(),
+                scrub_running: true,
+                last_report: last_scrub_report(),
+                issues: vec![],
+            }),
+        );
+    }
+
+    info!(
+        "Admin {} lanzó una verificación de integridad",
+        admin.user_id
+    );
+    tokio::spawn(async {
+        if let Err(e) = scrub_blobs().await {
+            error!("Error en la verificación de integridad: {}", e);
+        }
+    });
+
+    Custom(
+        Status::Accepted,
+        Json(IntegrityResponse {
+            success: true,
+            message: "Verificación de integridad iniciada".to_string(),
+            scrub_running: true,
+            last_report: last_scrub_report(),
+            issues: vec![],
+        }),
+    )
+}
+
+/// Reconcilia la base de datos con el contenido del storage
+///
+/// Endpoint: POST /api/admin/reconcile?dry_run=true
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token de un administrador>
+/// ```
+///
+/// Response: reporte de blobs huérfanos, refcounts corregidos, archivos sin
+/// contenido y temporales abandonados. Con `dry_run=true` no se modifica nada.
+#[post("/api/admin/reconcile?<dry_run>")]
+pub async fn reconcile_route(
+    admin: AdminUser,
+    dry_run: Option<bool>,
+) -> Result<Json<ReconcileResponse>, Custom<Json<ReconcileResponse>>> {
+    let span = span!(Level::INFO, "reconcile_route");
+    let _enter = span.enter();
+
+    let dry_run = dry_run.unwrap_or(false);
+    info!(
+        "Admin {} lanzó una reconciliación{}",
+        admin.user_id,
+        if dry_run { " (simulada)" } else { "" }
+    );
+
+    match reconcile_storage(dry_run).await {
+        Ok(report) => Ok(Json(ReconcileResponse {
+            success: true,
+            message: "Reconciliación completada".to_string(),
+            report: Some(report),
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = if error_msg.contains("en curso") {
+                Status::Conflict
+            } else {
+                error!("Error en la reconciliación: {}", e);
+                Status::InternalServerError
+            };
+            Err(Custom(
+                status,
+                Json(ReconcileResponse {
+                    success: false,
+                    message: error_msg,
+                    report: None,
+                }),
+            ))
+        }
+    }
+}
+
+/// Fija la cuota de almacenamiento de un usuario
+///
+/// Endpoint: PUT /api/admin/users/<user_id>/quota
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token de un administrador>
+/// Content-Type: application/json
+/// ```
+///
+/// Body: `{"quota_bytes": 1073741824}`, o `{"quota_bytes": null}` para volver
+/// a la cuota por defecto
+#[put("/api/admin/users/<user_id>/quota", data = "<update>")]
+pub async fn set_quota_route(
+    admin: AdminUser,
+    user_id: String,
+    update: Json<QuotaUpdate>,
+) -> Result<Json<UsageResponse>, Custom<Json<UsageResponse>>> {
+    let span = span!(Level::INFO, "set_quota_route");
+    let _enter = span.enter();
+
+    match set_user_quota(&user_id, update.quota_bytes).await {
+        Ok(usage) => {
+            info!("Admin {} cambió la cuota de {}", admin.user_id, user_id);
+            Ok(Json(UsageResponse {
+                success: true,
+                message: "Cuota actualizada".to_string(),
+                usage,
+            }))
+        }
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = if error_msg.contains("no encontrado") {
+                Status::NotFound
+            } else if error_msg.contains("inválida") {
+                Status::BadRequest
+            } else {
+                error!("Error al actualizar la cuota de {}: {}", user_id, e);
+                Status::InternalServerError
+            };
+
+            Err(Custom(
+                status,
+                Json(UsageResponse {
+                    success: false,
+                    message: error_msg,
+                    usage: StorageUsage::default(),
+                }),
+            ))
+        }
+    }
+}
+
+/// Fija la cuota de almacenamiento del espacio de un equipo
+///
+/// Endpoint: PUT /api/admin/teams/<team_id>/quota
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token de un administrador>
+/// Content-Type: application/json
+/// ```
+///
+/// Body: `{"quota_bytes": 10737418240}`, o `{"quota_bytes": null}` para
+/// volver a la cuota por defecto
+#[put("/api/admin/teams/<team_id>/quota", data = "<update>")]
+pub async fn set_team_quota_route(
+    admin: AdminUser,
+    team_id: String,
+    update: Json<QuotaUpdate>,
+) -> Result<Json<UsageResponse>, Custom<Json<UsageResponse>>> {
+    let span = span!(Level::INFO, "set_team_quota_route");
+    let _enter = span.enter();
+
+    match set_team_quota(&team_id, update.quota_bytes).await {
+        Ok(usage) => {
+            info!(
+                "Admin {} cambió la cuota del equipo {}",
+                admin.user_id, team_id
+            );
+            Ok(Json(UsageResponse {
+                success: true,
+                message: "Cuota actualizada".to_string(),
+                usage,
+            }))
+        }
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = if error_msg.contains("no encontrado") {
+                Status::NotFound
+            } else if error_msg.contains("inválida") {
+                Status::BadRequest
+            } else {
+                error!("Error al actualizar la cuota del equipo {}: {}", team_id, e);
+                Status::InternalServerError
+            };
+
+            Err(Custom(
+                status,
+                Json(UsageResponse {
+                    success: false,
+                    message: error_msg,
+                    usage: StorageUsage::default(),
+                }),
+            ))
+        }
+    }
+}
+
+/// Lista todas las cuentas con su rol, estado y uso de almacenamiento
+///
+/// Endpoint: GET /api/admin/users
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token de un administrador>
+/// ```
+#[get("/api/admin/users")]
+pub async fn list_users_route(
+    admin: AdminUser,
+) -> Result<Json<AdminUserListResponse>, Custom<Json<AdminUserListResponse>>> {
+    let span = span!(Level::INFO, "list_users_route");
+    let _enter = span.enter();
+
+    match list_users().await {
+        Ok(users) => {
+            info!("Admin {} listó los usuarios", admin.user_id);
+            Ok(Json(AdminUserListResponse {
+                success: true,
+                message: format!("{} usuario(s)", users.len()),
+                users,
+            }))
+        }
+        Err(e) => {
+            error!("Error al listar usuarios: {}", e);
+            Err(Custom(
+                Status::InternalServerError,
+                Json(AdminUserListResponse {
+                    success: false,
+                    message: e.to_string(),
+                    users: vec![],
+                }),
+            ))
+        }
+    }
+}
+
+/// Consulta una cuenta
+///
+/// Endpoint: GET /api/admin/users/<user_id>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token de un administrador>
+/// ```
+#[get("/api/admin/users/<user_id>")]
+pub async fn get_user_route(
+    _admin: AdminUser,
+    user_id: String,
+) -> Result<Json<AdminUserResponse>, Custom<Json<AdminUserResponse>>> {
+    let user = get_user(&user_id).await.map_err(user_error)?;
+
+    Ok(Json(AdminUserResponse {
+        success: true,
+        message: format!("Usuario '{}'", user.username),
+        user: Some(user),
+    }))
+}
+
+/// Cambia el rol de una cuenta
+///
+/// Endpoint: PUT /api/admin/users/<user_id>/role
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token de un administrador>
+/// Content-Type: application/json
+/// ```
+///
+/// Body: `{"role": "admin"}` o `{"role": "user"}`. Un administrador no puede
+/// quitarse el rol a sí mismo.
+#[put("/api/admin/users/<user_id>/role", data = "<update>")]
+pub async fn set_user_role_route(
+    admin: AdminUser,
+    user_id: String,
+    update: Json<UserRoleUpdate>,
+) -> Result<Json<AdminUserResponse>, Custom<Json<AdminUserResponse>>> {
+    let span = span!(Level::INFO, "set_user_role_route");
+    let _enter = span.enter();
+
+    let user = set_user_role(&admin.user_id, &user_id, &update.role)
+        .await
+        .map_err(user_error)?;
+
+    Ok(Json(AdminUserResponse {
+        success: true,
+        message: format!("Rol '{}' asignado", user.role),
+        user: Some(user),
+    }))
+}
+
+/// Deshabilita una cuenta: corta sus sesiones y no puede volver a entrar
+///
+/// Endpoint: POST /api/admin/users/<user_id>/disable
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token de un administrador>
+/// ```
+///
+/// Sus archivos se conservan, pero sus enlaces públicos y URLs firmadas
+/// dejan de funcionar mientras siga deshabilitada.
+#[post("/api/admin/users/<user_id>/disable")]
+pub async fn disable_user_route(
+    admin: AdminUser,
+    user_id: String,
+) -> Result<Json<AdminUserResponse>, Custom<Json<AdminUserResponse>>> {
+    let span = span!(Level::INFO, "disable_user_route");
+    let _enter = span.enter();
+
+    let user = set_user_disabled(&admin.user_id, &user_id, true)
+        .await
+        .map_err(user_error)?;
+
+    Ok(Json(AdminUserResponse {
+        success: true,
+        message: "Cuenta deshabilitada".to_string(),
+        user: Some(user),
+    }))
+}
+
+/// Vuelve a habilitar una cuenta
+///
+/// Endpoint: POST /api/admin/users/<user_id>/enable
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token de un administrador>
+/// ```
+///
+/// Los tokens emitidos antes de deshabilitarla siguen sin valer.
+#[post("/api/admin/users/<user_id>/enable")]
+pub async fn enable_user_route(
+    admin: AdminUser,
+    user_id: String,
+) -> Result<Json<AdminUserResponse>, Custom<Json<AdminUserResponse>>> {
+    let span = span!(Level::INFO, "enable_user_route");
+    let _enter = span.enter();
+
+    let user = set_user_disabled(&admin.user_id, &user_id, false)
+        .await
+        .map_err(user_error)?;
+
+    Ok(Json(AdminUserResponse {
+        success: true,
+        message: "Cuenta habilitada".to_string(),
+        user: Some(user),
+    }))
+}
+
+/// Reinicia la contraseña de una cuenta con una temporal
+///
+/// Endpoint: POST /api/admin/users/<user_id>/password-reset
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token de un administrador>
+/// ```
+///
+/// Response: la contraseña temporal, que solo se muestra esta vez. Las
+/// sesiones del usuario se cortan y, al entrar con ella, solo puede usar
+/// `PUT /api/auth/password` hasta cambiarla.
+#[post("/api/admin/users/<user_id>/password-reset")]
+pub async fn reset_password_route(
+    admin: AdminUser,
+    user_id: String,
+) -> Result<Json<PasswordResetResponse>, Custom<Json<PasswordResetResponse>>> {
+    let span = span!(Level::INFO, "reset_password_route");
+    let _enter = span.enter();
+
+    match reset_user_password(&admin.user_id, &user_id).await {
+        Ok(temporal) => Ok(Json(PasswordResetResponse {
+            success: true,
+            message: "Contraseña reiniciada; el usuario deberá cambiarla al entrar".to_string(),
+            temporary_password: Some(temporal),
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = admin_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al reiniciar la contraseña de {}: {}", user_id, e);
+            }
+
+            Err(Custom(
+                status,
+                Json(PasswordResetResponse {
+                    success: false,
+                    message: error_msg,
+                    temporary_password: None,
+                }),
+            ))
+        }
+    }
+}
+
+/// Elimina una cuenta con todos sus archivos
+///
+/// Endpoint: DELETE /api/admin/users/<user_id>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token de un administrador>
+/// ```
+///
+/// Borra sus archivos (también los de la papelera), carpetas, enlaces y
+/// permisos. Los archivos que subió a equipos son del equipo y se
+/// conservan. Responde 409 si es el único dueño de algún equipo.
+#[delete("/api/admin/users/<user_id>")]
+pub async fn delete_user_route(
+    admin: AdminUser,
+    user_id: String,
+) -> Result<Json<DeleteResponse>, Custom<Json<DeleteResponse>>> {
+    let span = span!(Level::INFO, "delete_user_route");
+    let _enter = span.enter();
+
+    match delete_user(&admin.user_id, &user_id).await {
+        Ok(archivos) => Ok(Json(DeleteResponse {
+            success: true,
+            message: format!("Usuario eliminado ({} archivo(s))", archivos),
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = admin_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al eliminar el usuario {}: {}", user_id, e);
+            }
+
+            Err(Custom(
+                status,
+                Json(DeleteResponse {
+                    success: false,
+                    message: error_msg,
+                }),
+            ))
+        }
+    }
+}
+
+/// Uso de almacenamiento de todo el servidor
+///
+/// Endpoint: GET /api/admin/storage
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token de un administrador>
+/// ```
+///
+/// Response: cuentas, equipos y archivos, la suma del uso de todos los
+/// espacios y el tamaño real de los blobs tras deduplicar
+#[get("/api/admin/storage")]
+pub async fn server_storage_route(
+    admin: AdminUser,
+) -> Result<Json<ServerStorageResponse>, Custom<Json<ServerStorageResponse>>> {
+    let span = span!(Level::INFO, "server_storage_route");
+    let _enter = span.enter();
+
+    match server_storage().await {
+        Ok(storage) => {
+            info!("Admin {} consultó el almacenamiento", admin.user_id);
+            Ok(Json(ServerStorageResponse {
+                success: true,
+                message: "Almacenamiento del servidor".to_string(),
+                storage,
+            }))
+        }
+        Err(e) => {
+            error!("Error al calcular el almacenamiento del servidor: {}", e);
+            Err(Custom(
+                Status::InternalServerError,
+                Json(ServerStorageResponse {
+                    success: false,
+                    message: e.to_string(),
+                    storage: ServerStorage::default(),
+                }),
+            ))
+        }
+    }
+}
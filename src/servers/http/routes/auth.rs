@@ -1,12 +1,13 @@
+use super::files::SessionUser;
 use crate::core::paseto_keys_path;
-use crate::core::procedures::{authenticate_user, register_user};
+use crate::core::procedures::{authenticate_user, change_password, register_user};
 use crate::core::{
     cryptography::authentication::PasetoManager,
-    structs::{AuthResponse, LoginCredentials},
+    structs::{AuthResponse, ChangePasswordRequest, LoginCredentials},
 };
 use anyhow::Result;
 use rocket::response::status;
-use rocket::{post, serde::json::Json};
+use rocket::{State, post, put, serde::json::Json};
 
 #[post("/api/auth/register", data = "<credentials>")]
 pub async fn register(
@@ -59,3 +60,55 @@ pub async fn login(
         token,
     }))
 }
+
+/// Ruta para cambiar la contraseña del usuario autenticado
+///
+/// Endpoint: PUT /api/auth/password
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// Content-Type: application/json
+/// ```
+///
+/// Body: `{"current_password": "...", "new_password": "..."}`
+///
+/// Es la única ruta disponible mientras haya que cambiar una contraseña
+/// reiniciada por un administrador. Corta las demás sesiones y devuelve un
+/// token nuevo.
+#[put("/api/auth/password", data = "<request>")]
+pub async fn change_password_route(
+    session: SessionUser,
+    paseto: &State<PasetoManager>,
+    request: Json<ChangePasswordRequest>,
+) -> Result<Json<AuthResponse>, status::Custom<String>> {
+    let request = request.into_inner();
+
+    change_password(
+        &session.user_id,
+        &request.current_password,
+        &request.new_password,
+    )
+    .await
+    .map_err(|e| {
+        let error_msg = e.to_string();
+        let status = if error_msg.contains("incorrecta") {
+            rocket::http::Status::Unauthorized
+        } else if error_msg.contains("inválida") {
+            rocket::http::Status::BadRequest
+        } else {
+            rocket::http::Status::InternalServerError
+        };
+        status::Custom(status, error_msg)
+    })?;
+
+    let token = paseto
+        .create_token(&session.user_id, 72)
+        .map_err(|e| status::Custom(rocket::http::Status::InternalServerError, e.to_string()))?;
+
+    Ok(Json(AuthResponse {
+        sucess: true,
+        message: "Password changed".to_string(),
+        token,
+    }))
+}
@@ -2,14 +2,25 @@ use rocket::data::ToByteUnit;
 use rocket::outcome::Outcome;
 use rocket::request::{self, FromRequest, Request};
 use rocket::serde::json::Json;
-use rocket::{Data, State, delete, get, http::Status, post, response::status::Custom};
+use rocket::{Data, State, delete, get, head, http::Status, post, put, response::status::Custom};
 use tracing::{Level, error, info, span, warn};
 
 use crate::core::File;
 use crate::core::cryptography::authentication::PasetoManager;
-use crate::core::init_db_manager;
-use crate::core::procedures::{delete_file, download_file, list_user_files, upload_file};
-use crate::core::structs::{DeleteResponse, FileInfo, FileListResponse, UploadResponse};
+use crate::core::procedures::{
+    apply_batch, copy_file, delete_file, download_file, duplicate_file, list_user_files, move_file,
+    open_archive, prepare_archive, rename_file, session_user, stat_file, storage_usage,
+    upload_file,
+};
+use crate::core::structs::{
+    ArchiveRequest, BatchItemResult, BatchRequest, BatchResponse, CopyRequest, DeleteResponse,
+    FileInfo, FileListResponse, FileResponse, MoveRequest, RenameRequest, StorageUsage,
+    UploadResponse, UsageResponse,
+};
+use crate::core::{init_db_manager, max_upload_size};
+use crate::servers::http::download::{
+    ArchiveDownload, DownloadHeaders, RangedDownload, serve_download, serve_head,
+};
 
 impl From<File> for FileInfo {
     fn from(file: File) -> Self {
@@ -17,6 +28,14 @@ impl From<File> for FileInfo {
             id: file.id,
             mime: file.mime,
             hash: file.hash,
+            filename: file.filename,
+            size: file.size,
+            created_at: file.created_at,
+            updated_at: file.updated_at,
+            last_accessed_at: file.last_accessed_at,
+            folder_id: file.folder_id,
+            deleted_at: file.deleted_at,
+            version: file.version,
         }
     }
 }
@@ -25,13 +44,17 @@ impl From<File> for FileInfo {
 // Authentication Guard
 // ============================================================================
 
-/// Guard para extraer y validar el token PASETO del header Authorization
-pub struct AuthenticatedUser {
+/// Guard de sesión: token PASETO válido en el header Authorization de una
+/// cuenta habilitada cuyas sesiones no se cortaron después de emitirlo.
+/// No exige el cambio de contraseña pendiente; solo lo usa la ruta que
+/// cambia la contraseña, el resto usa `AuthenticatedUser`.
+pub struct SessionUser {
     pub user_id: String,
+    pub password_reset_required: bool,
 }
 
 #[rocket::async_trait]
-impl<'r> FromRequest<'r> for AuthenticatedUser {
+impl<'r> FromRequest<'r> for SessionUser {
     type Error = String;
 
     async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
@@ -45,8 +68,8 @@ impl<'r> FromRequest<'r> for AuthenticatedUser {
             }
         };
 
-        let token = if auth_header.starts_with("Bearer ") {
-            &auth_header[7..]
+        let token = if let Some(token) = auth_header.strip_prefix("Bearer ") {
+            token
         } else {
             return Outcome::Error((
                 Status::Unauthorized,
@@ -64,37 +87,93 @@ impl<'r> FromRequest<'r> for AuthenticatedUser {
             }
         };
 
-        match paseto_manager.verify_token(token) {
-            Ok(claims) => Outcome::Success(AuthenticatedUser {
-                user_id: claims.sub,
-            }),
+        let claims = match paseto_manager.verify_token(token) {
+            Ok(claims) => claims,
             Err(e) => {
                 warn!("Token verification failed: {}", e);
-                Outcome::Error((Status::Unauthorized, format!("Invalid token: {}", e)))
+                return Outcome::Error((Status::Unauthorized, format!("Invalid token: {}", e)));
+            }
+        };
+
+        match session_user(&claims.sub, claims.iat).await {
+            Ok(usuario) => Outcome::Success(SessionUser {
+                user_id: usuario.id,
+                password_reset_required: usuario.password_reset_required,
+            }),
+            Err(e) => {
+                let error_msg = e.to_string();
+                warn!("Sesión rechazada para {}: {}", claims.sub, error_msg);
+                let status = if error_msg.contains("deshabilitada") {
+                    Status::Forbidden
+                } else {
+                    Status::Unauthorized
+                };
+                Outcome::Error((status, error_msg))
             }
         }
     }
 }
 
+/// Guard para extraer y validar el token PASETO del header Authorization.
+/// Responde 403 mientras el usuario tenga pendiente cambiar la contraseña
+/// que le fijó un administrador.
+pub struct AuthenticatedUser {
+    pub user_id: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthenticatedUser {
+    type Error = String;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let session = match request.guard::<SessionUser>().await {
+            Outcome::Success(session) => session,
+            Outcome::Error(e) => return Outcome::Error(e),
+            Outcome::Forward(f) => return Outcome::Forward(f),
+        };
+
+        if session.password_reset_required {
+            return Outcome::Error((
+                Status::Forbidden,
+                "Debes cambiar tu contraseña antes de continuar".to_string(),
+            ));
+        }
+        Outcome::Success(AuthenticatedUser {
+            user_id: session.user_id,
+        })
+    }
+}
+
 // ============================================================================
 // Routes
 // ============================================================================
 
 /// Ruta para subir archivos con autenticación PASETO
 ///
-/// Endpoint: POST /api/files/upload?mime=application/pdf
+/// Endpoint: POST /api/files/upload?mime=application/pdf&filename=informe.pdf&folder_id=<optional>&team_id=<optional>
 ///
 /// Headers:
-/// ```
+/// ```text
 /// Content-Type: application/octet-stream
 /// Authorization: Bearer <paseto-token>
 /// ```
 ///
-/// Body: archivo binario raw
-#[post("/api/files/upload?<mime>", data = "<data>")]
+/// Body: archivo binario raw (se procesa en streaming, límite `max_upload_size_mb`)
+///
+/// `filename` es opcional; sin él el archivo se nombra con su ID. Sin
+/// `folder_id` el archivo se guarda en la raíz, la del usuario o, con
+/// `team_id`, la del espacio del equipo. En el espacio de un equipo el
+/// archivo ocupa la cuota del equipo.
+#[post(
+    "/api/files/upload?<mime>&<filename>&<folder_id>&<team_id>",
+    data = "<data>"
+)]
 pub async fn upload_file_route(
     user: AuthenticatedUser,
     mime: String,
+    filename: Option<String>,
+    folder_id: Option<String>,
+    team_id: Option<String>,
     data: Data<'_>,
 ) -> Result<Json<UploadResponse>, Custom<Json<UploadResponse>>> {
     let span = span!(Level::INFO, "upload_file_route");
@@ -127,36 +206,21 @@ pub async fn upload_file_route(
         ));
     }
 
-    // Leer el archivo (límite de 100 MiB)
-    let mut stream = data.open(ToByteUnit::mebibytes(100));
-    let mut file_content = Vec::new();
-
-    if let Err(e) = tokio::io::AsyncReadExt::read_to_end(&mut stream, &mut file_content).await {
-        error!("Error al leer datos del request: {}", e);
-        return Err(Custom(
-            Status::BadRequest,
-            Json(UploadResponse {
-                success: false,
-                message: format!("Error al leer datos del archivo: {}", e),
-                file_id: None,
-            }),
-        ));
-    }
-
-    if file_content.is_empty() {
-        error!("Archivo vacío");
-        return Err(Custom(
-            Status::BadRequest,
-            Json(UploadResponse {
-                success: false,
-                message: "El archivo no puede estar vacío".to_string(),
-                file_id: None,
-            }),
-        ));
-    }
+    // Abrir el stream con un byte extra para detectar si se excede el límite
+    let limit = max_upload_size();
+    let mut stream = data.open(ToByteUnit::bytes(limit + 1));
 
-    // Usar el procedure para subir el archivo
-    match upload_file(&user.user_id, &mime, file_content).await {
+    // Usar el procedure para subir el archivo (se escribe en disco por bloques)
+    match upload_file(
+        &user.user_id,
+        &mime,
+        filename.as_deref(),
+        folder_id.as_deref(),
+        team_id.as_deref(),
+        &mut stream,
+    )
+    .await
+    {
         Ok(file_id) => {
             info!("Archivo subido exitosamente: {}", file_id);
             Ok(Json(UploadResponse {
@@ -166,12 +230,32 @@ pub async fn upload_file_route(
             }))
         }
         Err(e) => {
+            let error_msg = e.to_string();
+
+            // Determinar el status code apropiado
+            let status = if error_msg.contains("cuota") {
+                Status::InsufficientStorage
+            } else if error_msg.contains("excede") {
+                Status::PayloadTooLarge
+            } else if error_msg.contains("no encontrad") {
+                Status::NotFound
+            } else if error_msg.contains("Permiso insuficiente") {
+                Status::Forbidden
+            } else if error_msg.contains("vacío")
+                || error_msg.contains("leer datos")
+                || error_msg.contains("inválido")
+            {
+                Status::BadRequest
+            } else {
+                Status::InternalServerError
+            };
+
             error!("Error al subir archivo: {}", e);
             Err(Custom(
-                Status::InternalServerError,
+                status,
                 Json(UploadResponse {
                     success: false,
-                    message: format!("Error al subir archivo: {}", e),
+                    message: format!("Error al subir archivo: {}", error_msg),
                     file_id: None,
                 }),
             ))
@@ -181,41 +265,43 @@ pub async fn upload_file_route(
 
 /// Ruta para listar los archivos del usuario autenticado
 ///
-/// Endpoint: GET /api/files/list?mime=<optional>&limit=<optional>
+/// Endpoint: GET /api/files/list?mime=<optional>&tag=<optional>&limit=<optional>
 ///
 /// Headers:
-/// ```
+/// ```text
 /// Authorization: Bearer <paseto-token>
 /// ```
 ///
 /// Query params (opcionales):
 /// - mime: Filtrar por tipo MIME (ej: "application/pdf")
+/// - tag: Filtrar por etiqueta
 /// - limit: Límite de resultados (1-1000)
-#[get("/api/files/list?<mime>&<limit>")]
+#[get("/api/files/list?<mime>&<tag>&<limit>")]
 pub async fn list_files_route(
     user: AuthenticatedUser,
     mime: Option<String>,
+    tag: Option<String>,
     limit: Option<i64>,
 ) -> Result<Json<FileListResponse>, Custom<Json<FileListResponse>>> {
     let span = span!(Level::INFO, "list_files_route");
     let _enter = span.enter();
 
     // Validar límite si se proporciona
-    if let Some(lim) = limit {
-        if lim <= 0 || lim > 1000 {
-            return Err(Custom(
-                Status::BadRequest,
-                Json(FileListResponse {
-                    success: false,
-                    message: "El límite debe estar entre 1 y 1000".to_string(),
-                    files: vec![],
-                }),
-            ));
-        }
+    if let Some(lim) = limit
+        && (lim <= 0 || lim > 1000)
+    {
+        return Err(Custom(
+            Status::BadRequest,
+            Json(FileListResponse {
+                success: false,
+                message: "El límite debe estar entre 1 y 1000".to_string(),
+                files: vec![],
+            }),
+        ));
     }
 
     // Usar el procedure para listar archivos
-    match list_user_files(&user.user_id, mime.as_deref(), limit).await {
+    match list_user_files(&user.user_id, mime.as_deref(), tag.as_deref(), limit).await {
         Ok(files) => {
             let file_count = files.len();
             let file_infos: Vec<FileInfo> = files.into_iter().map(FileInfo::from).collect();
@@ -226,6 +312,14 @@ pub async fn list_files_route(
                 files: file_infos,
             }))
         }
+        Err(e) if e.to_string().contains("inválid") => Err(Custom(
+            Status::BadRequest,
+            Json(FileListResponse {
+                success: false,
+                message: e.to_string(),
+                files: vec![],
+            }),
+        )),
         Err(e) => {
             error!("Error al obtener archivos: {}", e);
             Err(Custom(
@@ -240,59 +334,254 @@ pub async fn list_files_route(
     }
 }
 
-/// Ruta para descargar un archivo específico
+/// Status HTTP de una operación fallida del lote, con el mismo criterio que
+/// las rutas individuales
+fn batch_error_status(error_msg: &str) -> Status {
+    if error_msg.contains("no encontrad") {
+        Status::NotFound
+    } else if error_msg.contains("inválid") {
+        Status::BadRequest
+    } else if error_msg.contains("Permiso insuficiente") {
+        Status::Forbidden
+    } else {
+        Status::InternalServerError
+    }
+}
+
+/// Ruta para aplicar varias operaciones sobre archivos en una petición
 ///
-/// Endpoint: GET /api/files/download/<file_id>
+/// Endpoint: POST /api/files/batch
 ///
 /// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// Content-Type: application/json
+/// ```
+///
+/// Body:
+/// ```json
+/// {"operations": [
+///   {"op": "delete", "file_id": "<id>"},
+///   {"op": "move", "file_id": "<id>", "folder_id": "<id o null>"},
+///   {"op": "rename", "file_id": "<id>", "name": "informe.pdf"},
+///   {"op": "tag", "file_id": "<id>", "add": ["trabajo"], "remove": ["viejo"]}
+/// ]}
 /// ```
+///
+/// Response: 200 con el resultado de cada operación (`status` y `message`
+/// propios) aunque alguna falle; `success` es `true` solo si todas se
+/// aplicaron. Las operaciones válidas se aplican en una misma transacción.
+///
+/// Sobre archivos compartidos como `editor` se permiten `delete`, `rename`
+/// y `tag`; `move` solo en el espacio propio o de un equipo.
+#[post("/api/files/batch", data = "<request>")]
+pub async fn batch_route(
+    user: AuthenticatedUser,
+    request: Json<BatchRequest>,
+) -> Result<Json<BatchResponse>, Custom<Json<BatchResponse>>> {
+    let span = span!(Level::INFO, "batch_route");
+    let _enter = span.enter();
+
+    let resultados = apply_batch(&user.user_id, &request.operations)
+        .await
+        .map_err(|e| {
+            let error_msg = e.to_string();
+            let status = batch_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al aplicar lote: {}", e);
+            }
+            Custom(
+                status,
+                Json(BatchResponse {
+                    success: false,
+                    message: error_msg,
+                    succeeded: 0,
+                    failed: request.operations.len(),
+                    results: vec![],
+                }),
+            )
+        })?;
+
+    let results: Vec<BatchItemResult> = resultados
+        .into_iter()
+        .zip(&request.operations)
+        .enumerate()
+        .map(|(index, (resultado, operacion))| {
+            let file_id = operacion.file_id().to_string();
+            match resultado {
+                Ok(()) => BatchItemResult {
+                    index,
+                    file_id,
+                    success: true,
+                    status: Status::Ok.code,
+                    message: "Operación aplicada".to_string(),
+                },
+                Err(e) => {
+                    let error_msg = e.to_string();
+                    let status = batch_error_status(&error_msg);
+                    if status == Status::InternalServerError {
+                        error!("Error en operación {} del lote: {:#}", index, e);
+                    }
+                    BatchItemResult {
+                        index,
+                        file_id,
+                        success: false,
+                        status: status.code,
+                        message: error_msg,
+                    }
+                }
+            }
+        })
+        .collect();
+
+    let succeeded = results.iter().filter(|r| r.success).count();
+    let failed = results.len() - succeeded;
+    Ok(Json(BatchResponse {
+        success: failed == 0,
+        message: format!(
+            "{} operación(es) aplicadas, {} fallida(s)",
+            succeeded, failed
+        ),
+        succeeded,
+        failed,
+        results,
+    }))
+}
+
+/// Traduce el error de una descarga a un status HTTP con el mensaje
+fn download_error(e: anyhow::Error) -> Custom<String> {
+    let error_msg = e.to_string();
+
+    // Determinar el status code apropiado
+    let status = if error_msg.contains("no encontrado") {
+        Status::NotFound
+    } else if error_msg.contains("inválido") {
+        Status::BadRequest
+    } else {
+        Status::InternalServerError
+    };
+
+    Custom(status, error_msg)
+}
+
+/// Ruta para descargar un archivo específico
+///
+/// Endpoint: GET /api/files/download/<file_id>?inline=<optional>
+///
+/// Headers:
+/// ```text
 /// Authorization: Bearer <paseto-token>
+/// Range: bytes=0-1023          (opcional, admite varios rangos)
+/// If-Range: "<etag>" o fecha   (opcional)
+/// If-None-Match: "<etag>"      (opcional)
+/// If-Modified-Since: <fecha>   (opcional)
 /// ```
 ///
-/// Response: El archivo binario con headers apropiados
-#[get("/api/files/download/<file_id>")]
+/// Response: El archivo binario (200), los rangos pedidos (206, multipart si
+/// son varios), 304 si el cliente ya tiene el contenido actual o 416 si
+/// ningún rango es satisfacible. `Content-Disposition` lleva el nombre
+/// original, como `attachment` salvo que se pida `inline=true`.
+///
+/// Todas las respuestas incluyen `ETag`, `Last-Modified` y `Repr-Digest`;
+/// la completa también `Content-Digest` (SHA-256, RFC 9530).
+#[get("/api/files/download/<file_id>?<inline>")]
 pub async fn download_file_route(
     user: AuthenticatedUser,
     file_id: String,
-) -> Result<(Status, (rocket::http::ContentType, Vec<u8>)), Custom<String>> {
+    inline: Option<bool>,
+    headers: DownloadHeaders,
+) -> Result<RangedDownload, Custom<String>> {
     let span = span!(Level::INFO, "download_file_route");
     let _enter = span.enter();
 
-    // Usar el procedure para descargar el archivo
-    match download_file(&user.user_id, &file_id).await {
-        Ok((mime_type, file_content)) => {
-            let content_type = rocket::http::ContentType::parse_flexible(&mime_type)
-                .unwrap_or(rocket::http::ContentType::Binary);
+    // Usar el procedure para validar y obtener los metadatos del archivo
+    let resultado = match download_file(&user.user_id, &file_id).await {
+        Ok(file) => serve_download(&file, &headers, inline.unwrap_or(false)).await,
+        Err(e) => Err(e),
+    };
 
-            Ok((Status::Ok, (content_type, file_content)))
-        }
-        Err(e) => {
-            let error_msg = e.to_string();
+    resultado.map_err(download_error)
+}
 
-            // Determinar el status code apropiado
-            let status = if error_msg.contains("no encontrado") {
-                Status::NotFound
-            } else if error_msg.contains("inválido") {
-                Status::BadRequest
-            } else {
-                Status::InternalServerError
-            };
+/// Ruta para consultar los metadatos de descarga de un archivo
+///
+/// Endpoint: HEAD /api/files/download/<file_id>?inline=<optional>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// Response: los headers de la descarga completa sin cuerpo. No registra un
+/// acceso al archivo.
+#[head("/api/files/download/<file_id>?<inline>")]
+pub async fn head_file_route(
+    user: AuthenticatedUser,
+    file_id: String,
+    inline: Option<bool>,
+) -> Result<RangedDownload, Custom<String>> {
+    stat_file(&user.user_id, &file_id)
+        .await
+        .map(|file| serve_head(&file, inline.unwrap_or(false)))
+        .map_err(download_error)
+}
 
-            Err(Custom(status, error_msg))
-        }
-    }
+/// Ruta para descargar varios archivos o una carpeta en un ZIP
+///
+/// Endpoint: POST /api/files/archive
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// Content-Type: application/json
+/// ```
+///
+/// Body: `{"file_ids": ["<id>", ...]}` o `{"folder_id": "<id>"}`
+///
+/// Response: ZIP64 generado mientras se descarga, con los nombres originales
+/// (los repetidos llevan un sufijo ` (n)`). Una carpeta conserva su
+/// estructura de subcarpetas.
+#[post("/api/files/archive", data = "<request>")]
+pub async fn download_archive_route(
+    user: AuthenticatedUser,
+    request: Json<ArchiveRequest>,
+) -> Result<ArchiveDownload, Custom<String>> {
+    let span = span!(Level::INFO, "download_archive_route");
+    let _enter = span.enter();
+
+    let (nombre, entradas) = prepare_archive(
+        &user.user_id,
+        &request.file_ids,
+        request.folder_id.as_deref(),
+    )
+    .await
+    .map_err(|e| {
+        let error_msg = e.to_string();
+        let status = if error_msg.contains("no encontrad") {
+            Status::NotFound
+        } else if error_msg.contains("inválid") {
+            Status::BadRequest
+        } else {
+            error!("Error al preparar ZIP: {}", e);
+            Status::InternalServerError
+        };
+        Custom(status, error_msg)
+    })?;
+
+    Ok(ArchiveDownload::new(nombre, open_archive(entradas)))
 }
 
-/// Ruta para eliminar un archivo
+/// Ruta para eliminar un archivo (lo envía a la papelera)
 ///
 /// Endpoint: DELETE /api/files/delete/<file_id>
 ///
 /// Headers:
-/// ```
+/// ```text
 /// Authorization: Bearer <paseto-token>
 /// ```
 ///
-/// Response: JSON indicando éxito o error
+/// Response: JSON indicando éxito o error. El archivo se puede restaurar
+/// desde `/api/trash` hasta que se purgue.
 #[delete("/api/files/delete/<file_id>")]
 pub async fn delete_file_route(
     user: AuthenticatedUser,
@@ -304,10 +593,10 @@ pub async fn delete_file_route(
     // Usar el procedure para eliminar el archivo
     match delete_file(&user.user_id, &file_id).await {
         Ok(_) => {
-            info!("Archivo {} eliminado exitosamente", file_id);
+            info!("Archivo {} enviado a la papelera", file_id);
             Ok(Json(DeleteResponse {
                 success: true,
-                message: format!("Archivo {} eliminado exitosamente", file_id),
+                message: format!("Archivo {} enviado a la papelera", file_id),
             }))
         }
         Err(e) => {
@@ -318,6 +607,8 @@ pub async fn delete_file_route(
                 Status::NotFound
             } else if error_msg.contains("inválido") {
                 Status::BadRequest
+            } else if error_msg.contains("Permiso insuficiente") {
+                Status::Forbidden
             } else {
                 Status::InternalServerError
             };
@@ -333,3 +624,229 @@ pub async fn delete_file_route(
         }
     }
 }
+
+/// Ruta para consultar el espacio usado y la cuota del usuario
+///
+/// Endpoint: GET /api/files/usage
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// Response: JSON con `used_bytes`, `quota_bytes` y `available_bytes`
+/// (`null` si el usuario no tiene límite)
+#[get("/api/files/usage")]
+pub async fn usage_route(
+    user: AuthenticatedUser,
+) -> Result<Json<UsageResponse>, Custom<Json<UsageResponse>>> {
+    let span = span!(Level::INFO, "usage_route");
+    let _enter = span.enter();
+
+    match storage_usage(&user.user_id).await {
+        Ok(usage) => Ok(Json(UsageResponse {
+            success: true,
+            message: format!("{} bytes usados", usage.used_bytes),
+            usage,
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = if error_msg.contains("no encontrado") {
+                Status::NotFound
+            } else {
+                error!("Error al obtener el uso de almacenamiento: {}", e);
+                Status::InternalServerError
+            };
+
+            Err(Custom(
+                status,
+                Json(UsageResponse {
+                    success: false,
+                    message: error_msg,
+                    usage: StorageUsage::default(),
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta para mover un archivo a otra carpeta
+///
+/// Endpoint: PUT /api/files/<file_id>/move
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// Content-Type: application/json
+/// ```
+///
+/// Body: `{"folder_id": "<id>"}`, o `{"folder_id": null}` para la raíz
+///
+/// El archivo no cambia de espacio: la carpeta destino debe ser del mismo
+/// espacio (personal o de equipo) y `null` es la raíz de ese espacio.
+#[put("/api/files/<file_id>/move", data = "<request>")]
+pub async fn move_file_route(
+    user: AuthenticatedUser,
+    file_id: String,
+    request: Json<MoveRequest>,
+) -> Result<Json<FileResponse>, Custom<Json<FileResponse>>> {
+    let span = span!(Level::INFO, "move_file_route");
+    let _enter = span.enter();
+
+    match move_file(&user.user_id, &file_id, request.folder_id.as_deref()).await {
+        Ok(file) => Ok(Json(FileResponse {
+            success: true,
+            message: "Archivo movido".to_string(),
+            file: Some(file.into()),
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = if error_msg.contains("no encontrad") {
+                Status::NotFound
+            } else if error_msg.contains("inválido") {
+                Status::BadRequest
+            } else if error_msg.contains("Permiso insuficiente") {
+                Status::Forbidden
+            } else {
+                error!("Error al mover archivo {}: {}", file_id, e);
+                Status::InternalServerError
+            };
+
+            Err(Custom(
+                status,
+                Json(FileResponse {
+                    success: false,
+                    message: error_msg,
+                    file: None,
+                }),
+            ))
+        }
+    }
+}
+
+/// Respuesta de error común a renombrar, copiar y duplicar
+fn file_op_error(e: anyhow::Error, file_id: &str) -> Custom<Json<FileResponse>> {
+    let error_msg = e.to_string();
+    let status = if error_msg.contains("no encontrad") {
+        Status::NotFound
+    } else if error_msg.contains("inválid") {
+        Status::BadRequest
+    } else if error_msg.contains("cuota") {
+        Status::InsufficientStorage
+    } else if error_msg.contains("corrupto") {
+        Status::Conflict
+    } else if error_msg.contains("Permiso insuficiente") {
+        Status::Forbidden
+    } else {
+        error!("Error en operación sobre archivo {}: {}", file_id, e);
+        Status::InternalServerError
+    };
+
+    Custom(
+        status,
+        Json(FileResponse {
+            success: false,
+            message: error_msg,
+            file: None,
+        }),
+    )
+}
+
+/// Ruta para renombrar un archivo
+///
+/// Endpoint: PUT /api/files/<file_id>/rename
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// Content-Type: application/json
+/// ```
+///
+/// Body: `{"name": "informe-final.pdf"}`
+#[put("/api/files/<file_id>/rename", data = "<request>")]
+pub async fn rename_file_route(
+    user: AuthenticatedUser,
+    file_id: String,
+    request: Json<RenameRequest>,
+) -> Result<Json<FileResponse>, Custom<Json<FileResponse>>> {
+    let span = span!(Level::INFO, "rename_file_route");
+    let _enter = span.enter();
+
+    let file = rename_file(&user.user_id, &file_id, &request.name)
+        .await
+        .map_err(|e| file_op_error(e, &file_id))?;
+
+    Ok(Json(FileResponse {
+        success: true,
+        message: format!("Archivo renombrado a '{}'", file.filename),
+        file: Some(file.into()),
+    }))
+}
+
+/// Ruta para copiar un archivo a otra carpeta sin volver a subirlo
+///
+/// Endpoint: POST /api/files/<file_id>/copy
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// Content-Type: application/json
+/// ```
+///
+/// Body: `{"folder_id": "<id o null>", "name": "<opcional>"}`
+///
+/// Response: la copia, con un ID nuevo. Comparte el contenido con el
+/// original, pero su tamaño cuenta para la cuota.
+#[post("/api/files/<file_id>/copy", data = "<request>")]
+pub async fn copy_file_route(
+    user: AuthenticatedUser,
+    file_id: String,
+    request: Json<CopyRequest>,
+) -> Result<Json<FileResponse>, Custom<Json<FileResponse>>> {
+    let span = span!(Level::INFO, "copy_file_route");
+    let _enter = span.enter();
+
+    let file = copy_file(
+        &user.user_id,
+        &file_id,
+        request.folder_id.as_deref(),
+        request.name.as_deref(),
+    )
+    .await
+    .map_err(|e| file_op_error(e, &file_id))?;
+
+    Ok(Json(FileResponse {
+        success: true,
+        message: "Archivo copiado".to_string(),
+        file: Some(file.into()),
+    }))
+}
+
+/// Ruta para duplicar un archivo en su misma carpeta
+///
+/// Endpoint: POST /api/files/<file_id>/duplicate
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// Response: el duplicado, llamado `<nombre> (copia)` o `<nombre> (copia N)`
+#[post("/api/files/<file_id>/duplicate")]
+pub async fn duplicate_file_route(
+    user: AuthenticatedUser,
+    file_id: String,
+) -> Result<Json<FileResponse>, Custom<Json<FileResponse>>> {
+    let span = span!(Level::INFO, "duplicate_file_route");
+    let _enter = span.enter();
+
+    let file = duplicate_file(&user.user_id, &file_id)
+        .await
+        .map_err(|e| file_op_error(e, &file_id))?;
+
+    Ok(Json(FileResponse {
+        success: true,
+        message: format!("Archivo duplicado como '{}'", file.filename),
+        file: Some(file.into()),
+    }))
+}
@@ -0,0 +1,242 @@
+use rocket::serde::json::Json;
+use rocket::{delete, get, http::Status, post, put, response::status::Custom};
+use tracing::{Level, error, info, span};
+
+use super::files::AuthenticatedUser;
+use crate::core::procedures::{
+    create_folder, delete_folder, list_folder, move_folder, rename_folder,
+};
+use crate::core::structs::{
+    CreateFolderRequest, DeleteResponse, FileInfo, Folder, FolderContentsResponse, FolderInfo,
+    FolderResponse, MoveRequest, RenameRequest,
+};
+
+impl From<Folder> for FolderInfo {
+    fn from(folder: Folder) -> Self {
+        FolderInfo {
+            id: folder.id,
+            parent_id: folder.parent_id,
+            name: folder.name,
+            created_at: folder.created_at,
+            updated_at: folder.updated_at,
+        }
+    }
+}
+
+/// Traduce el mensaje de error de un procedure de carpetas a un status HTTP
+fn folder_error_status(error_msg: &str) -> Status {
+    if error_msg.contains("no encontrad") {
+        Status::NotFound
+    } else if error_msg.contains("inválid") {
+        Status::BadRequest
+    } else if error_msg.contains("Ya existe") || error_msg.contains("no está vacía") {
+        Status::Conflict
+    } else if error_msg.contains("Permiso insuficiente") {
+        Status::Forbidden
+    } else {
+        Status::InternalServerError
+    }
+}
+
+/// Respuesta de error común a las rutas que devuelven una carpeta
+fn folder_error(e: anyhow::Error) -> Custom<Json<FolderResponse>> {
+    let error_msg = e.to_string();
+    let status = folder_error_status(&error_msg);
+    if status == Status::InternalServerError {
+        error!("Error en operación de carpeta: {}", e);
+    }
+
+    Custom(
+        status,
+        Json(FolderResponse {
+            success: false,
+            message: error_msg,
+            folder: None,
+        }),
+    )
+}
+
+// ============================================================================
+// Routes
+// ============================================================================
+
+/// Ruta para crear una carpeta
+///
+/// Endpoint: POST /api/folders
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// Content-Type: application/json
+/// ```
+///
+/// Body: `{"name": "Facturas", "parent_id": "<id o null para la raíz>"}`.
+/// Con `"team_id"` y sin `parent_id` se crea en la raíz del espacio del
+/// equipo.
+#[post("/api/folders", data = "<request>")]
+pub async fn create_folder_route(
+    user: AuthenticatedUser,
+    request: Json<CreateFolderRequest>,
+) -> Result<Json<FolderResponse>, Custom<Json<FolderResponse>>> {
+    let span = span!(Level::INFO, "create_folder_route");
+    let _enter = span.enter();
+
+    let folder = create_folder(
+        &user.user_id,
+        &request.name,
+        request.parent_id.as_deref(),
+        request.team_id.as_deref(),
+    )
+    .await
+    .map_err(folder_error)?;
+
+    Ok(Json(FolderResponse {
+        success: true,
+        message: format!("Carpeta '{}' creada", folder.name),
+        folder: Some(folder.into()),
+    }))
+}
+
+/// Ruta para listar el contenido de una carpeta
+///
+/// Endpoint: GET /api/folders/contents?folder_id=<optional>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// Sin `folder_id` se lista la raíz del usuario. La respuesta incluye la
+/// ruta desde la raíz para mostrar la navegación.
+#[get("/api/folders/contents?<folder_id>")]
+pub async fn folder_contents_route(
+    user: AuthenticatedUser,
+    folder_id: Option<String>,
+) -> Result<Json<FolderContentsResponse>, Custom<Json<FolderContentsResponse>>> {
+    let span = span!(Level::INFO, "folder_contents_route");
+    let _enter = span.enter();
+
+    match list_folder(&user.user_id, folder_id.as_deref()).await {
+        Ok(listing) => Ok(Json(FolderContentsResponse {
+            success: true,
+            message: format!(
+                "{} carpeta(s) y {} archivo(s)",
+                listing.folders.len(),
+                listing.files.len()
+            ),
+            folder: listing.folder.map(FolderInfo::from),
+            path: listing.path.into_iter().map(FolderInfo::from).collect(),
+            folders: listing.folders.into_iter().map(FolderInfo::from).collect(),
+            files: listing.files.into_iter().map(FileInfo::from).collect(),
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = folder_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al listar carpeta: {}", e);
+            }
+
+            Err(Custom(
+                status,
+                Json(FolderContentsResponse {
+                    success: false,
+                    message: error_msg,
+                    folder: None,
+                    path: vec![],
+                    folders: vec![],
+                    files: vec![],
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta para renombrar una carpeta
+///
+/// Endpoint: PUT /api/folders/<folder_id>/rename
+///
+/// Body: `{"name": "Nuevo nombre"}`
+#[put("/api/folders/<folder_id>/rename", data = "<request>")]
+pub async fn rename_folder_route(
+    user: AuthenticatedUser,
+    folder_id: String,
+    request: Json<RenameRequest>,
+) -> Result<Json<FolderResponse>, Custom<Json<FolderResponse>>> {
+    let span = span!(Level::INFO, "rename_folder_route");
+    let _enter = span.enter();
+
+    let folder = rename_folder(&user.user_id, &folder_id, &request.name)
+        .await
+        .map_err(folder_error)?;
+
+    Ok(Json(FolderResponse {
+        success: true,
+        message: format!("Carpeta renombrada a '{}'", folder.name),
+        folder: Some(folder.into()),
+    }))
+}
+
+/// Ruta para mover una carpeta (con su contenido) a otra carpeta
+///
+/// Endpoint: PUT /api/folders/<folder_id>/move
+///
+/// Body: `{"folder_id": "<destino>"}`, o `{"folder_id": null}` para la raíz
+#[put("/api/folders/<folder_id>/move", data = "<request>")]
+pub async fn move_folder_route(
+    user: AuthenticatedUser,
+    folder_id: String,
+    request: Json<MoveRequest>,
+) -> Result<Json<FolderResponse>, Custom<Json<FolderResponse>>> {
+    let span = span!(Level::INFO, "move_folder_route");
+    let _enter = span.enter();
+
+    let folder = move_folder(&user.user_id, &folder_id, request.folder_id.as_deref())
+        .await
+        .map_err(folder_error)?;
+
+    Ok(Json(FolderResponse {
+        success: true,
+        message: "Carpeta movida".to_string(),
+        folder: Some(folder.into()),
+    }))
+}
+
+/// Ruta para eliminar una carpeta
+///
+/// Endpoint: DELETE /api/folders/<folder_id>?recursive=<optional>
+///
+/// Sin `recursive=true` solo se eliminan carpetas vacías (409 si no lo está)
+#[delete("/api/folders/<folder_id>?<recursive>")]
+pub async fn delete_folder_route(
+    user: AuthenticatedUser,
+    folder_id: String,
+    recursive: Option<bool>,
+) -> Result<Json<DeleteResponse>, Custom<Json<DeleteResponse>>> {
+    let span = span!(Level::INFO, "delete_folder_route");
+    let _enter = span.enter();
+
+    match delete_folder(&user.user_id, &folder_id, recursive.unwrap_or(false)).await {
+        Ok(archivos) => {
+            info!("Carpeta {} eliminada", folder_id);
+            Ok(Json(DeleteResponse {
+                success: true,
+                message: format!("Carpeta {} eliminada ({} archivo(s))", folder_id, archivos),
+            }))
+        }
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = folder_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al eliminar carpeta {}: {}", folder_id, e);
+            }
+
+            Err(Custom(
+                status,
+                Json(DeleteResponse {
+                    success: false,
+                    message: error_msg,
+                }),
+            ))
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use rocket::serde::json::Json;
+use rocket::{delete, get, http::Status, post, response::status::Custom};
+use tracing::{Level, error, span};
+
+use super::files::AuthenticatedUser;
+use crate::core::procedures::{create_grant, list_grants, revoke_grant, shared_with_me};
+use crate::core::structs::{
+    DeleteResponse, FileInfo, FolderInfo, Grant, GrantInfo, GrantListResponse, GrantRequest,
+    GrantResponse, SharedItem, SharedWithMeResponse,
+};
+
+impl From<Grant> for GrantInfo {
+    fn from(grant: Grant) -> Self {
+        GrantInfo {
+            id: grant.id,
+            owner_id: grant.owner_id,
+            grantee_id: grant.grantee_id,
+            file_id: grant.file_id,
+            folder_id: grant.folder_id,
+            role: grant.role,
+            created_at: grant.created_at,
+        }
+    }
+}
+
+/// Traduce el mensaje de error de un procedure de permisos a un status HTTP
+fn grant_error_status(error_msg: &str) -> Status {
+    if error_msg.contains("no encontrad") {
+        Status::NotFound
+    } else if error_msg.contains("inválid") {
+        Status::BadRequest
+    } else if error_msg.contains("Permiso insuficiente") {
+        Status::Forbidden
+    } else {
+        Status::InternalServerError
+    }
+}
+
+// ============================================================================
+// Routes
+// ============================================================================
+
+/// Ruta para compartir un archivo o una carpeta con otro usuario
+///
+/// Endpoint: POST /api/grants
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// Content-Type: application/json
+/// ```
+///
+/// Body: `{"user_id": "...", "role": "viewer", "file_id": "..."}` o con
+/// `folder_id` en lugar de `file_id`. `role` es `viewer` o `editor`; si el
+/// usuario ya tenía acceso solo se cambia el rol.
+#[post("/api/grants", data = "<request>")]
+pub async fn create_grant_route(
+    user: AuthenticatedUser,
+    request: Json<GrantRequest>,
+) -> Result<Json<GrantResponse>, Custom<Json<GrantResponse>>> {
+    let span = span!(Level::INFO, "create_grant_route");
+    let _enter = span.enter();
+
+    let request = request.into_inner();
+    match create_grant(
+        &user.user_id,
+        &request.user_id,
+        &request.role,
+        request.file_id.as_deref(),
+        request.folder_id.as_deref(),
+    )
+    .await
+    {
+        Ok(grant) => Ok(Json(GrantResponse {
+            success: true,
+            message: "Permiso otorgado".to_string(),
+            grant: Some(grant.into()),
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = grant_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al otorgar permiso: {}", e);
+            }
+
+            Err(Custom(
+                status,
+                Json(GrantResponse {
+                    success: false,
+                    message: error_msg,
+                    grant: None,
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta para listar los permisos dados por el usuario
+///
+/// Endpoint: GET /api/grants?file_id=<optional>&folder_id=<optional>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+#[get("/api/grants?<file_id>&<folder_id>")]
+pub async fn list_grants_route(
+    user: AuthenticatedUser,
+    file_id: Option<String>,
+    folder_id: Option<String>,
+) -> Result<Json<GrantListResponse>, Custom<Json<GrantListResponse>>> {
+    let span = span!(Level::INFO, "list_grants_route");
+    let _enter = span.enter();
+
+    match list_grants(&user.user_id, file_id.as_deref(), folder_id.as_deref()).await {
+        Ok(grants) => Ok(Json(GrantListResponse {
+            success: true,
+            message: format!("{} permiso(s)", grants.len()),
+            grants: grants.into_iter().map(GrantInfo::from).collect(),
+        })),
+        Err(e) => {
+            error!("Error al listar permisos: {}", e);
+            Err(Custom(
+                Status::InternalServerError,
+                Json(GrantListResponse {
+                    success: false,
+                    message: e.to_string(),
+                    grants: vec![],
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta para revocar un permiso
+///
+/// Endpoint: DELETE /api/grants/<grant_id>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// La puede usar el dueño o el destinatario del permiso; el acceso se
+/// corta de inmediato.
+#[delete("/api/grants/<grant_id>")]
+pub async fn revoke_grant_route(
+    user: AuthenticatedUser,
+    grant_id: String,
+) -> Result<Json<DeleteResponse>, Custom<Json<DeleteResponse>>> {
+    let span = span!(Level::INFO, "revoke_grant_route");
+    let _enter = span.enter();
+
+    match revoke_grant(&user.user_id, &grant_id).await {
+        Ok(_) => Ok(Json(DeleteResponse {
+            success: true,
+            message: "Permiso revocado".to_string(),
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = grant_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al revocar permiso {}: {}", grant_id, e);
+            }
+
+            Err(Custom(
+                status,
+                Json(DeleteResponse {
+                    success: false,
+                    message: error_msg,
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta para listar los archivos y carpetas compartidos con el usuario
+///
+/// Endpoint: GET /api/shared-with-me
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// Response: cada elemento trae el permiso y el archivo o la carpeta. El
+/// contenido de una carpeta se lista con
+/// `/api/folders/contents?folder_id=<folder_id>`.
+#[get("/api/shared-with-me")]
+pub async fn shared_with_me_route(
+    user: AuthenticatedUser,
+) -> Result<Json<SharedWithMeResponse>, Custom<Json<SharedWithMeResponse>>> {
+    let span = span!(Level::INFO, "shared_with_me_route");
+    let _enter = span.enter();
+
+    match shared_with_me(&user.user_id).await {
+        Ok(entradas) => Ok(Json(SharedWithMeResponse {
+            success: true,
+            message: format!("{} elemento(s) compartido(s)", entradas.len()),
+            items: entradas
+                .into_iter()
+                .map(|entrada| SharedItem {
+                    grant: entrada.grant.into(),
+                    file: entrada.file.map(FileInfo::from),
+                    folder: entrada.folder.map(FolderInfo::from),
+                })
+                .collect(),
+        })),
+        Err(e) => {
+            error!("Error al listar lo compartido con {}: {}", user.user_id, e);
+            Err(Custom(
+                Status::InternalServerError,
+                Json(SharedWithMeResponse {
+                    success: false,
+                    message: e.to_string(),
+                    items: vec![],
+                }),
+            ))
+        }
+    }
+}
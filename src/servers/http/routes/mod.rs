@@ -1,4 +1,48 @@
+mod admin;
 mod auth;
 mod files;
-pub use auth::{login, register};
-pub use files::{delete_file_route, download_file_route, list_files_route, upload_file_route};
+mod folders;
+mod grants;
+mod shares;
+mod signed;
+mod teams;
+mod trash;
+mod tus;
+mod upload_links;
+mod versions;
+pub use admin::{
+    delete_user_route, disable_user_route, enable_user_route, get_user_route,
+    integrity_report_route, list_users_route, reconcile_route, reset_password_route,
+    server_storage_route, set_quota_route, set_team_quota_route, set_user_role_route,
+    start_scrub_route,
+};
+pub use auth::{change_password_route, login, register};
+pub use files::{
+    batch_route, copy_file_route, delete_file_route, download_archive_route, download_file_route,
+    duplicate_file_route, head_file_route, list_files_route, move_file_route, rename_file_route,
+    upload_file_route, usage_route,
+};
+pub use folders::{
+    create_folder_route, delete_folder_route, folder_contents_route, move_folder_route,
+    rename_folder_route,
+};
+pub use grants::{create_grant_route, list_grants_route, revoke_grant_route, shared_with_me_route};
+pub use shares::{
+    create_share_route, list_shares_route, public_download_route, public_head_route,
+    revoke_share_route,
+};
+pub use signed::{create_signed_url_route, signed_download_route, signed_head_route};
+pub use teams::{
+    create_team_route, delete_team_route, get_team_route, list_team_members_route,
+    list_teams_route, remove_team_member_route, set_team_member_route, team_drive_route,
+};
+pub use trash::{empty_trash_route, list_trash_route, purge_file_route, restore_file_route};
+pub use tus::{tus_create, tus_delete, tus_head, tus_options, tus_patch};
+pub use upload_links::{
+    create_upload_link_route, list_upload_links_route, public_upload_link_route,
+    public_upload_route, revoke_upload_link_route, upload_link_files_route,
+};
+pub use versions::{
+    download_version_route, list_versions_route, promote_version_route, prune_versions_route,
+    upload_version_route,
+};
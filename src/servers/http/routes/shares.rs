@@ -0,0 +1,274 @@
+use rocket::request::{self, FromRequest, Request};
+use rocket::serde::json::Json;
+use rocket::{delete, get, head, http::Status, post, response::status::Custom};
+use tracing::{Level, error, info, span};
+
+use super::files::AuthenticatedUser;
+use crate::core::procedures::{
+    count_share_download, create_share_link, list_share_links, open_share_link, revoke_share_link,
+};
+use crate::core::structs::{
+    CreateShareRequest, DeleteResponse, ShareLink, ShareLinkInfo, ShareLinkListResponse,
+    ShareLinkResponse,
+};
+use crate::servers::http::download::{
+    DownloadHeaders, RangedDownload, serve_download, serve_head, starts_download,
+};
+
+impl From<ShareLink> for ShareLinkInfo {
+    fn from(link: ShareLink) -> Self {
+        ShareLinkInfo {
+            url: format!("/api/public/shares/{}", link.token),
+            token: link.token,
+            file_id: link.file_id,
+            has_password: link.password_hash.is_some(),
+            expires_at: link.expires_at,
+            max_downloads: link.max_downloads,
+            download_count: link.download_count,
+            created_at: link.created_at,
+        }
+    }
+}
+
+/// Contraseña de un enlace público (de descarga o de subida) enviada en
+/// `Privafile-Share-Password` o, para enlaces abiertos desde un navegador
+/// sin poder mandar headers, en el parámetro `password` de la query
+pub struct SharePassword(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SharePassword {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let header = request.headers().get_one("Privafile-Share-Password");
+        let query = || {
+            request
+                .query_value::<String>("password")
+                .and_then(Result::ok)
+        };
+        request::Outcome::Success(SharePassword(header.map(str::to_string).or_else(query)))
+    }
+}
+
+/// Traduce el mensaje de error de un procedure de enlaces a un status HTTP
+fn share_error_status(error_msg: &str) -> Status {
+    if error_msg.contains("no encontrad") {
+        Status::NotFound
+    } else if error_msg.contains("expirado") || error_msg.contains("límite de descargas") {
+        Status::Gone
+    } else if error_msg.contains("requiere contraseña") || error_msg.contains("incorrecta") {
+        Status::Unauthorized
+    } else if error_msg.contains("Demasiados intentos") {
+        Status::TooManyRequests
+    } else if error_msg.contains("inválid") {
+        Status::BadRequest
+    } else if error_msg.contains("Permiso insuficiente") {
+        Status::Forbidden
+    } else {
+        Status::InternalServerError
+    }
+}
+
+/// Respuesta de error de las descargas por enlace
+fn public_download_error(e: anyhow::Error) -> Custom<String> {
+    let error_msg = e.to_string();
+    let status = share_error_status(&error_msg);
+    if status == Status::InternalServerError {
+        error!("Error al descargar por enlace público: {}", e);
+    }
+    Custom(status, error_msg)
+}
+
+// ============================================================================
+// Routes
+// ============================================================================
+
+/// Ruta para crear un enlace público de un archivo
+///
+/// Endpoint: POST /api/files/<file_id>/shares
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// Content-Type: application/json
+/// ```
+///
+/// Body: `{"expires_in_hours": 48, "max_downloads": 5, "password": "..."}`,
+/// todos opcionales
+#[post("/api/files/<file_id>/shares", data = "<request>")]
+pub async fn create_share_route(
+    user: AuthenticatedUser,
+    file_id: String,
+    request: Json<CreateShareRequest>,
+) -> Result<Json<ShareLinkResponse>, Custom<Json<ShareLinkResponse>>> {
+    let span = span!(Level::INFO, "create_share_route");
+    let _enter = span.enter();
+
+    let request = request.into_inner();
+    match create_share_link(
+        &user.user_id,
+        &file_id,
+        request.expires_in_hours,
+        request.max_downloads,
+        request.password.as_deref(),
+    )
+    .await
+    {
+        Ok(link) => Ok(Json(ShareLinkResponse {
+            success: true,
+            message: "Enlace creado".to_string(),
+            link: Some(link.into()),
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = share_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al crear enlace para {}: {}", file_id, e);
+            }
+
+            Err(Custom(
+                status,
+                Json(ShareLinkResponse {
+                    success: false,
+                    message: error_msg,
+                    link: None,
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta para listar los enlaces públicos del usuario
+///
+/// Endpoint: GET /api/shares?file_id=<optional>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// Con `file_id` solo se listan los enlaces de ese archivo
+#[get("/api/shares?<file_id>")]
+pub async fn list_shares_route(
+    user: AuthenticatedUser,
+    file_id: Option<String>,
+) -> Result<Json<ShareLinkListResponse>, Custom<Json<ShareLinkListResponse>>> {
+    let span = span!(Level::INFO, "list_shares_route");
+    let _enter = span.enter();
+
+    match list_share_links(&user.user_id, file_id.as_deref()).await {
+        Ok(links) => Ok(Json(ShareLinkListResponse {
+            success: true,
+            message: format!("{} enlace(s)", links.len()),
+            links: links.into_iter().map(ShareLinkInfo::from).collect(),
+        })),
+        Err(e) => {
+            error!("Error al listar enlaces: {}", e);
+            Err(Custom(
+                Status::InternalServerError,
+                Json(ShareLinkListResponse {
+                    success: false,
+                    message: e.to_string(),
+                    links: vec![],
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta para revocar un enlace público
+///
+/// Endpoint: DELETE /api/shares/<token>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+#[delete("/api/shares/<token>")]
+pub async fn revoke_share_route(
+    user: AuthenticatedUser,
+    token: String,
+) -> Result<Json<DeleteResponse>, Custom<Json<DeleteResponse>>> {
+    let span = span!(Level::INFO, "revoke_share_route");
+    let _enter = span.enter();
+
+    match revoke_share_link(&user.user_id, &token).await {
+        Ok(_) => Ok(Json(DeleteResponse {
+            success: true,
+            message: "Enlace revocado".to_string(),
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = share_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al revocar enlace: {}", e);
+            }
+
+            Err(Custom(
+                status,
+                Json(DeleteResponse {
+                    success: false,
+                    message: error_msg,
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta pública para descargar el archivo de un enlace, sin cuenta
+///
+/// Endpoint: GET /api/public/shares/<token>?inline=<optional>&password=<optional>
+///
+/// Headers:
+/// ```text
+/// Privafile-Share-Password: <contraseña>   (si el enlace la pide)
+/// Range: bytes=0-1023                      (opcional)
+/// ```
+///
+/// La contraseña también se acepta en `password` para abrir el enlace desde
+/// un navegador, aunque así puede quedar en el historial y en los logs de
+/// proxies; el header tiene prioridad.
+///
+/// Consume una descarga del enlace solo la respuesta que entrega el
+/// comienzo del archivo (completo o un rango desde el byte 0); los 304 y los
+/// rangos que reanudan una descarga no cuentan. Responde 401 si falta la
+/// contraseña o no coincide, 429 tras demasiadas contraseñas incorrectas y
+/// 410 si el enlace expiró o alcanzó su límite.
+#[get("/api/public/shares/<token>?<inline>")]
+pub async fn public_download_route(
+    token: String,
+    inline: Option<bool>,
+    password: SharePassword,
+    headers: DownloadHeaders,
+) -> Result<RangedDownload, Custom<String>> {
+    let span = span!(Level::INFO, "public_download_route");
+    let _enter = span.enter();
+
+    let file = open_share_link(&token, password.0.as_deref())
+        .await
+        .map_err(public_download_error)?;
+    if starts_download(&file, &headers) {
+        count_share_download(&token, &file.id)
+            .await
+            .map_err(public_download_error)?;
+    }
+    info!("Archivo {} servido por enlace público", file.id);
+    serve_download(&file, &headers, inline.unwrap_or(false))
+        .await
+        .map_err(public_download_error)
+}
+
+/// Metadatos del archivo de un enlace sin descargarlo ni consumir descargas
+///
+/// Endpoint: HEAD /api/public/shares/<token>?inline=<optional>
+#[head("/api/public/shares/<token>?<inline>")]
+pub async fn public_head_route(
+    token: String,
+    inline: Option<bool>,
+    password: SharePassword,
+) -> Result<RangedDownload, Custom<String>> {
+    let file = open_share_link(&token, password.0.as_deref())
+        .await
+        .map_err(public_download_error)?;
+    Ok(serve_head(&file, inline.unwrap_or(false)))
+}
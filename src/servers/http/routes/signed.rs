@@ -0,0 +1,238 @@
+use std::net::{IpAddr, SocketAddr};
+
+use rocket::serde::json::Json;
+use rocket::{State, get, head, http::Status, post, response::status::Custom};
+use tracing::{Level, error, info, span, warn};
+
+use super::files::AuthenticatedUser;
+use crate::core::cryptography::authentication::{DownloadClaims, PasetoManager};
+use crate::core::procedures::{download_file, session_user, stat_file};
+use crate::core::structs::{SignedUrlRequest, SignedUrlResponse};
+use crate::servers::http::download::{DownloadHeaders, RangedDownload, serve_download, serve_head};
+
+/// Validez por defecto de una URL firmada (5 minutos)
+const SIGNED_URL_DEFAULT_SECONDS: i64 = 300;
+/// Validez máxima de una URL firmada (24 horas)
+const SIGNED_URL_MAX_SECONDS: i64 = 24 * 3600;
+
+/// IP de la conexión TCP. No se usa `client_ip()` porque Rocket le da
+/// prioridad a `X-Real-IP`, que el cliente puede falsificar.
+fn peer_ip(remote: Option<SocketAddr>) -> Option<IpAddr> {
+    remote.map(|remote| remote.ip())
+}
+
+/// Traduce el error de una descarga firmada a un status HTTP con el mensaje
+fn signed_error(e: anyhow::Error) -> Custom<String> {
+    let error_msg = e.to_string();
+    let status = if error_msg.contains("no encontrado") {
+        Status::NotFound
+    } else if error_msg.contains("inválido") {
+        Status::BadRequest
+    } else if error_msg.contains("corrupto") {
+        Status::Conflict
+    } else {
+        error!("Error en descarga firmada: {}", e);
+        Status::InternalServerError
+    };
+
+    Custom(status, error_msg)
+}
+
+/// Valida la firma de la URL: que no haya expirado, que sea de este archivo,
+/// que quien la firmó siga con una sesión válida y, si está atada a una IP,
+/// que la petición venga de esa IP
+async fn verify_signature(
+    paseto: &PasetoManager,
+    sig: &str,
+    file_id: &str,
+    ip: Option<IpAddr>,
+) -> Result<DownloadClaims, Custom<String>> {
+    let claims = paseto.verify_download_token(sig).map_err(|e| {
+        warn!("Firma de descarga rechazada para {}: {}", file_id, e);
+        Custom(Status::Forbidden, "Firma inválida o expirada".to_string())
+    })?;
+
+    if claims.file_id != file_id {
+        warn!(
+            "Firma del archivo {} usada para el archivo {}",
+            claims.file_id, file_id
+        );
+        return Err(Custom(
+            Status::Forbidden,
+            "La firma no corresponde a este archivo".to_string(),
+        ));
+    }
+    if let Some(ip_firmada) = &claims.ip
+        && ip.map(|ip| ip.to_string()).as_ref() != Some(ip_firmada)
+    {
+        warn!(
+            "URL firmada para {} usada desde {:?} (atada a {})",
+            file_id, ip, ip_firmada
+        );
+        return Err(Custom(
+            Status::Forbidden,
+            "La URL firmada no es válida desde esta dirección IP".to_string(),
+        ));
+    }
+    // Deshabilitar la cuenta o cortar sus sesiones invalida también sus URLs
+    if let Err(e) = session_user(&claims.sub, claims.iat).await {
+        warn!("URL firmada de {} rechazada: {}", claims.sub, e);
+        return Err(Custom(
+            Status::Forbidden,
+            "Firma inválida o expirada".to_string(),
+        ));
+    }
+    Ok(claims)
+}
+
+// ============================================================================
+// Routes
+// ============================================================================
+
+/// Ruta para obtener una URL de descarga firmada de un archivo
+///
+/// Endpoint: POST /api/files/<file_id>/signed-url
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// Content-Type: application/json
+/// ```
+///
+/// Body: `{"expires_in_seconds": 300, "bind_ip": false, "inline": true}`,
+/// todos opcionales
+///
+/// La URL sirve para `<video src>`, `<img src>` o enlaces, que no pueden
+/// mandar el header `Authorization`. Vale solo para este archivo y hasta
+/// `expires_at` (como mucho 24 horas); con `bind_ip` solo desde la IP que
+/// la pidió, tomada de la conexión y no de headers como `X-Real-IP`.
+#[post("/api/files/<file_id>/signed-url", data = "<request>")]
+pub async fn create_signed_url_route(
+    user: AuthenticatedUser,
+    paseto: &State<PasetoManager>,
+    remote: Option<SocketAddr>,
+    file_id: String,
+    request: Json<SignedUrlRequest>,
+) -> Result<Json<SignedUrlResponse>, Custom<Json<SignedUrlResponse>>> {
+    let span = span!(Level::INFO, "create_signed_url_route");
+    let _enter = span.enter();
+
+    let fallo = |status: Status, message: String| {
+        Custom(
+            status,
+            Json(SignedUrlResponse {
+                success: false,
+                message,
+                url: None,
+                expires_at: None,
+            }),
+        )
+    };
+
+    let segundos = request
+        .expires_in_seconds
+        .unwrap_or(SIGNED_URL_DEFAULT_SECONDS);
+    if !(1..=SIGNED_URL_MAX_SECONDS).contains(&segundos) {
+        return Err(fallo(
+            Status::BadRequest,
+            format!(
+                "Expiración inválida: debe estar entre 1 y {} segundos",
+                SIGNED_URL_MAX_SECONDS
+            ),
+        ));
+    }
+    let ip = match (request.bind_ip, peer_ip(remote)) {
+        (false, _) => None,
+        (true, Some(ip)) => Some(ip.to_string()),
+        (true, None) => {
+            return Err(fallo(
+                Status::BadRequest,
+                "No se pudo determinar la IP del cliente".to_string(),
+            ));
+        }
+    };
+
+    // La URL solo se firma si el usuario puede descargar el archivo ahora
+    if let Err(e) = stat_file(&user.user_id, &file_id).await {
+        let Custom(status, message) = signed_error(e);
+        return Err(fallo(status, message));
+    }
+
+    let (token, expires_at) = paseto
+        .create_download_token(&user.user_id, &file_id, ip.as_deref(), segundos)
+        .map_err(|e| {
+            error!("Error al firmar la URL de {}: {}", file_id, e);
+            fallo(
+                Status::InternalServerError,
+                "Error al firmar la URL".to_string(),
+            )
+        })?;
+
+    info!(
+        "URL firmada para el archivo {} ({} s, ip: {:?})",
+        file_id, segundos, ip
+    );
+    let inline = if request.inline { "&inline=true" } else { "" };
+    Ok(Json(SignedUrlResponse {
+        success: true,
+        message: "URL firmada".to_string(),
+        url: Some(format!(
+            "/api/files/signed/{}?sig={}{}",
+            file_id, token, inline
+        )),
+        expires_at: Some(expires_at),
+    }))
+}
+
+/// Ruta para descargar un archivo con una URL firmada, sin `Authorization`
+///
+/// Endpoint: GET /api/files/signed/<file_id>?sig=<firma>&inline=<optional>
+///
+/// Headers:
+/// ```text
+/// Range: bytes=0-1023          (opcional)
+/// ```
+///
+/// Admite los mismos headers condicionales y de rango que
+/// `/api/files/download/<file_id>`. Responde 403 si la firma no es válida,
+/// expiró, es de otro archivo o de otra IP. El acceso del firmante se vuelve
+/// a comprobar en cada petición, así que dejar de compartir el archivo
+/// invalida también sus URLs firmadas.
+#[get("/api/files/signed/<file_id>?<sig>&<inline>")]
+pub async fn signed_download_route(
+    paseto: &State<PasetoManager>,
+    remote: Option<SocketAddr>,
+    file_id: String,
+    sig: String,
+    inline: Option<bool>,
+    headers: DownloadHeaders,
+) -> Result<RangedDownload, Custom<String>> {
+    let span = span!(Level::INFO, "signed_download_route");
+    let _enter = span.enter();
+
+    let claims = verify_signature(paseto, &sig, &file_id, peer_ip(remote)).await?;
+    let file = download_file(&claims.sub, &file_id)
+        .await
+        .map_err(signed_error)?;
+    serve_download(&file, &headers, inline.unwrap_or(false))
+        .await
+        .map_err(signed_error)
+}
+
+/// Metadatos de un archivo con una URL firmada, sin registrar un acceso
+///
+/// Endpoint: HEAD /api/files/signed/<file_id>?sig=<firma>&inline=<optional>
+#[head("/api/files/signed/<file_id>?<sig>&<inline>")]
+pub async fn signed_head_route(
+    paseto: &State<PasetoManager>,
+    remote: Option<SocketAddr>,
+    file_id: String,
+    sig: String,
+    inline: Option<bool>,
+) -> Result<RangedDownload, Custom<String>> {
+    let claims = verify_signature(paseto, &sig, &file_id, peer_ip(remote)).await?;
+    stat_file(&claims.sub, &file_id)
+        .await
+        .map(|file| serve_head(&file, inline.unwrap_or(false)))
+        .map_err(signed_error)
+}
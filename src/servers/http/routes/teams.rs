@@ -0,0 +1,365 @@
+use rocket::serde::json::Json;
+use rocket::{delete, get, http::Status, post, put, response::status::Custom};
+use tracing::{Level, error, info, span};
+
+use super::files::AuthenticatedUser;
+use crate::core::procedures::{
+    create_team, delete_team, get_team, list_team_drive, list_team_members, list_teams,
+    remove_team_member, set_team_member,
+};
+use crate::core::structs::{
+    CreateTeamRequest, DeleteResponse, FileInfo, FolderContentsResponse, FolderInfo, TeamInfo,
+    TeamListResponse, TeamMember, TeamMemberInfo, TeamMemberListResponse, TeamMemberRequest,
+    TeamMembership, TeamResponse,
+};
+
+impl From<TeamMembership> for TeamInfo {
+    fn from(membership: TeamMembership) -> Self {
+        TeamInfo {
+            id: membership.team.id,
+            name: membership.team.name,
+            role: membership.role,
+            usage: membership.usage,
+            created_at: membership.team.created_at,
+        }
+    }
+}
+
+impl From<TeamMember> for TeamMemberInfo {
+    fn from(member: TeamMember) -> Self {
+        TeamMemberInfo {
+            user_id: member.user_id,
+            role: member.role,
+            created_at: member.created_at,
+        }
+    }
+}
+
+/// Traduce el mensaje de error de un procedure de equipos a un status HTTP
+fn team_error_status(error_msg: &str) -> Status {
+    if error_msg.contains("no encontrad") {
+        Status::NotFound
+    } else if error_msg.contains("inválid") {
+        Status::BadRequest
+    } else if error_msg.contains("Permiso insuficiente") {
+        Status::Forbidden
+    } else if error_msg.contains("no está vacío") || error_msg.contains("al menos un dueño") {
+        Status::Conflict
+    } else {
+        Status::InternalServerError
+    }
+}
+
+/// Respuesta de error común a las rutas que devuelven un equipo
+fn team_error(e: anyhow::Error) -> Custom<Json<TeamResponse>> {
+    let error_msg = e.to_string();
+    let status = team_error_status(&error_msg);
+    if status == Status::InternalServerError {
+        error!("Error en operación de equipo: {}", e);
+    }
+
+    Custom(
+        status,
+        Json(TeamResponse {
+            success: false,
+            message: error_msg,
+            team: None,
+        }),
+    )
+}
+
+/// Respuesta de error común a las rutas que devuelven miembros
+fn members_error(e: anyhow::Error) -> Custom<Json<TeamMemberListResponse>> {
+    let error_msg = e.to_string();
+    let status = team_error_status(&error_msg);
+    if status == Status::InternalServerError {
+        error!("Error en operación de miembros: {}", e);
+    }
+
+    Custom(
+        status,
+        Json(TeamMemberListResponse {
+            success: false,
+            message: error_msg,
+            members: vec![],
+        }),
+    )
+}
+
+/// Respuesta de error de las rutas que solo confirman la operación
+fn delete_error(e: anyhow::Error) -> Custom<Json<DeleteResponse>> {
+    let error_msg = e.to_string();
+    let status = team_error_status(&error_msg);
+    if status == Status::InternalServerError {
+        error!("Error en operación de equipo: {}", e);
+    }
+
+    Custom(
+        status,
+        Json(DeleteResponse {
+            success: false,
+            message: error_msg,
+        }),
+    )
+}
+
+// ============================================================================
+// Routes
+// ============================================================================
+
+/// Ruta para crear un equipo; quien lo crea queda como su dueño
+///
+/// Endpoint: POST /api/teams
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// Content-Type: application/json
+/// ```
+///
+/// Body: `{"name": "Diseño"}`
+#[post("/api/teams", data = "<request>")]
+pub async fn create_team_route(
+    user: AuthenticatedUser,
+    request: Json<CreateTeamRequest>,
+) -> Result<Json<TeamResponse>, Custom<Json<TeamResponse>>> {
+    let span = span!(Level::INFO, "create_team_route");
+    let _enter = span.enter();
+
+    let team = create_team(&user.user_id, &request.name)
+        .await
+        .map_err(team_error)?;
+
+    Ok(Json(TeamResponse {
+        success: true,
+        message: format!("Equipo '{}' creado", team.team.name),
+        team: Some(team.into()),
+    }))
+}
+
+/// Ruta para listar los equipos del usuario con su rol en cada uno
+///
+/// Endpoint: GET /api/teams
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+#[get("/api/teams")]
+pub async fn list_teams_route(
+    user: AuthenticatedUser,
+) -> Result<Json<TeamListResponse>, Custom<Json<TeamListResponse>>> {
+    let span = span!(Level::INFO, "list_teams_route");
+    let _enter = span.enter();
+
+    match list_teams(&user.user_id).await {
+        Ok(teams) => Ok(Json(TeamListResponse {
+            success: true,
+            message: format!("{} equipo(s)", teams.len()),
+            teams: teams.into_iter().map(TeamInfo::from).collect(),
+        })),
+        Err(e) => {
+            error!("Error al listar equipos: {}", e);
+            Err(Custom(
+                Status::InternalServerError,
+                Json(TeamListResponse {
+                    success: false,
+                    message: e.to_string(),
+                    teams: vec![],
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta para consultar un equipo y el uso de su espacio
+///
+/// Endpoint: GET /api/teams/<team_id>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+#[get("/api/teams/<team_id>")]
+pub async fn get_team_route(
+    user: AuthenticatedUser,
+    team_id: String,
+) -> Result<Json<TeamResponse>, Custom<Json<TeamResponse>>> {
+    let team = get_team(&user.user_id, &team_id)
+        .await
+        .map_err(team_error)?;
+
+    Ok(Json(TeamResponse {
+        success: true,
+        message: format!("Equipo '{}'", team.team.name),
+        team: Some(team.into()),
+    }))
+}
+
+/// Ruta para eliminar un equipo (solo dueños y con el espacio vacío)
+///
+/// Endpoint: DELETE /api/teams/<team_id>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// Responde 409 si el espacio todavía tiene carpetas o archivos; los de la
+/// papelera se purgan.
+#[delete("/api/teams/<team_id>")]
+pub async fn delete_team_route(
+    user: AuthenticatedUser,
+    team_id: String,
+) -> Result<Json<DeleteResponse>, Custom<Json<DeleteResponse>>> {
+    let span = span!(Level::INFO, "delete_team_route");
+    let _enter = span.enter();
+
+    delete_team(&user.user_id, &team_id)
+        .await
+        .map_err(delete_error)?;
+
+    info!("Equipo {} eliminado", team_id);
+    Ok(Json(DeleteResponse {
+        success: true,
+        message: "Equipo eliminado".to_string(),
+    }))
+}
+
+/// Ruta para listar los miembros de un equipo
+///
+/// Endpoint: GET /api/teams/<team_id>/members
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+#[get("/api/teams/<team_id>/members")]
+pub async fn list_team_members_route(
+    user: AuthenticatedUser,
+    team_id: String,
+) -> Result<Json<TeamMemberListResponse>, Custom<Json<TeamMemberListResponse>>> {
+    let members = list_team_members(&user.user_id, &team_id)
+        .await
+        .map_err(members_error)?;
+
+    Ok(Json(TeamMemberListResponse {
+        success: true,
+        message: format!("{} miembro(s)", members.len()),
+        members: members.into_iter().map(TeamMemberInfo::from).collect(),
+    }))
+}
+
+/// Ruta para agregar un miembro a un equipo o cambiar su rol
+///
+/// Endpoint: PUT /api/teams/<team_id>/members
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// Content-Type: application/json
+/// ```
+///
+/// Body: `{"user_id": "...", "role": "member"}`. Requiere rol `admin`, o
+/// `owner` para dar o quitar los roles `owner` y `admin`.
+#[put("/api/teams/<team_id>/members", data = "<request>")]
+pub async fn set_team_member_route(
+    user: AuthenticatedUser,
+    team_id: String,
+    request: Json<TeamMemberRequest>,
+) -> Result<Json<TeamMemberListResponse>, Custom<Json<TeamMemberListResponse>>> {
+    let span = span!(Level::INFO, "set_team_member_route");
+    let _enter = span.enter();
+
+    let member = set_team_member(&user.user_id, &team_id, &request.user_id, &request.role)
+        .await
+        .map_err(members_error)?;
+
+    Ok(Json(TeamMemberListResponse {
+        success: true,
+        message: format!("Rol '{}' asignado", member.role),
+        members: vec![member.into()],
+    }))
+}
+
+/// Ruta para quitar a un miembro de un equipo, o para irse de él
+///
+/// Endpoint: DELETE /api/teams/<team_id>/members/<member_id>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+#[delete("/api/teams/<team_id>/members/<member_id>")]
+pub async fn remove_team_member_route(
+    user: AuthenticatedUser,
+    team_id: String,
+    member_id: String,
+) -> Result<Json<DeleteResponse>, Custom<Json<DeleteResponse>>> {
+    let span = span!(Level::INFO, "remove_team_member_route");
+    let _enter = span.enter();
+
+    remove_team_member(&user.user_id, &team_id, &member_id)
+        .await
+        .map_err(delete_error)?;
+
+    Ok(Json(DeleteResponse {
+        success: true,
+        message: "Miembro quitado del equipo".to_string(),
+    }))
+}
+
+/// Ruta para listar la raíz del espacio de un equipo
+///
+/// Endpoint: GET /api/teams/<team_id>/drive
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// Las subcarpetas se listan con `/api/folders/contents?folder_id=<id>`.
+/// Para subir o crear carpetas en la raíz del equipo se usa `team_id` en
+/// `/api/files/upload` y `/api/folders`.
+#[get("/api/teams/<team_id>/drive")]
+pub async fn team_drive_route(
+    user: AuthenticatedUser,
+    team_id: String,
+) -> Result<Json<FolderContentsResponse>, Custom<Json<FolderContentsResponse>>> {
+    let span = span!(Level::INFO, "team_drive_route");
+    let _enter = span.enter();
+
+    match list_team_drive(&user.user_id, &team_id).await {
+        Ok(listing) => Ok(Json(FolderContentsResponse {
+            success: true,
+            message: format!(
+                "{} carpeta(s) y {} archivo(s)",
+                listing.folders.len(),
+                listing.files.len()
+            ),
+            folder: None,
+            path: vec![],
+            folders: listing.folders.into_iter().map(FolderInfo::from).collect(),
+            files: listing.files.into_iter().map(FileInfo::from).collect(),
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = team_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al listar el espacio del equipo {}: {}", team_id, e);
+            }
+
+            Err(Custom(
+                status,
+                Json(FolderContentsResponse {
+                    success: false,
+                    message: error_msg,
+                    folder: None,
+                    path: vec![],
+                    folders: vec![],
+                    files: vec![],
+                }),
+            ))
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use rocket::serde::json::Json;
+use rocket::{delete, get, http::Status, post, response::status::Custom};
+use tracing::{Level, error, info, span};
+
+use super::files::AuthenticatedUser;
+use crate::core::procedures::{empty_trash, list_trash, purge_file, restore_file};
+use crate::core::structs::{DeleteResponse, FileInfo, FileListResponse, FileResponse};
+
+/// Traduce el mensaje de error de un procedure de la papelera a un status HTTP
+fn trash_error_status(error_msg: &str) -> Status {
+    if error_msg.contains("no encontrad") {
+        Status::NotFound
+    } else if error_msg.contains("inválid") {
+        Status::BadRequest
+    } else if error_msg.contains("Permiso insuficiente") {
+        Status::Forbidden
+    } else {
+        Status::InternalServerError
+    }
+}
+
+// ============================================================================
+// Routes
+// ============================================================================
+
+/// Ruta para listar la papelera del usuario
+///
+/// Endpoint: GET /api/trash?team_id=<optional>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// Con `team_id` se lista la papelera del espacio de ese equipo; hace falta
+/// ser al menos `member`.
+///
+/// Response: archivos en la papelera con su `deleted_at`, del más reciente
+/// al más antiguo
+#[get("/api/trash?<team_id>")]
+pub async fn list_trash_route(
+    user: AuthenticatedUser,
+    team_id: Option<String>,
+) -> Result<Json<FileListResponse>, Custom<Json<FileListResponse>>> {
+    let span = span!(Level::INFO, "list_trash_route");
+    let _enter = span.enter();
+
+    match list_trash(&user.user_id, team_id.as_deref()).await {
+        Ok(files) => Ok(Json(FileListResponse {
+            success: true,
+            message: format!("{} archivo(s) en la papelera", files.len()),
+            files: files.into_iter().map(FileInfo::from).collect(),
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = trash_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al listar la papelera: {}", e);
+            }
+
+            Err(Custom(
+                status,
+                Json(FileListResponse {
+                    success: false,
+                    message: error_msg,
+                    files: vec![],
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta para restaurar un archivo de la papelera
+///
+/// Endpoint: POST /api/trash/<file_id>/restore
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// El archivo vuelve a su carpeta original, o a la raíz de su espacio si ya
+/// no existe. Los archivos de la papelera de un equipo los puede restaurar o
+/// purgar cualquier miembro con rol de escritura.
+#[post("/api/trash/<file_id>/restore")]
+pub async fn restore_file_route(
+    user: AuthenticatedUser,
+    file_id: String,
+) -> Result<Json<FileResponse>, Custom<Json<FileResponse>>> {
+    let span = span!(Level::INFO, "restore_file_route");
+    let _enter = span.enter();
+
+    match restore_file(&user.user_id, &file_id).await {
+        Ok(file) => Ok(Json(FileResponse {
+            success: true,
+            message: "Archivo restaurado".to_string(),
+            file: Some(file.into()),
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = trash_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al restaurar archivo {}: {}", file_id, e);
+            }
+
+            Err(Custom(
+                status,
+                Json(FileResponse {
+                    success: false,
+                    message: error_msg,
+                    file: None,
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta para eliminar definitivamente un archivo de la papelera
+///
+/// Endpoint: DELETE /api/trash/<file_id>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+#[delete("/api/trash/<file_id>")]
+pub async fn purge_file_route(
+    user: AuthenticatedUser,
+    file_id: String,
+) -> Result<Json<DeleteResponse>, Custom<Json<DeleteResponse>>> {
+    let span = span!(Level::INFO, "purge_file_route");
+    let _enter = span.enter();
+
+    match purge_file(&user.user_id, &file_id).await {
+        Ok(_) => Ok(Json(DeleteResponse {
+            success: true,
+            message: format!("Archivo {} eliminado definitivamente", file_id),
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = trash_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al purgar archivo {}: {}", file_id, e);
+            }
+
+            Err(Custom(
+                status,
+                Json(DeleteResponse {
+                    success: false,
+                    message: error_msg,
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta para vaciar la papelera
+///
+/// Endpoint: DELETE /api/trash?team_id=<optional>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// Con `team_id` se vacía la papelera del espacio de ese equipo
+#[delete("/api/trash?<team_id>")]
+pub async fn empty_trash_route(
+    user: AuthenticatedUser,
+    team_id: Option<String>,
+) -> Result<Json<DeleteResponse>, Custom<Json<DeleteResponse>>> {
+    let span = span!(Level::INFO, "empty_trash_route");
+    let _enter = span.enter();
+
+    match empty_trash(&user.user_id, team_id.as_deref()).await {
+        Ok(eliminados) => {
+            info!(
+                "Papelera de {} vaciada",
+                team_id.as_deref().unwrap_or(&user.user_id)
+            );
+            Ok(Json(DeleteResponse {
+                success: true,
+                message: format!("{} archivo(s) eliminados definitivamente", eliminados),
+            }))
+        }
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = trash_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al vaciar la papelera: {}", e);
+            }
+
+            Err(Custom(
+                status,
+                Json(DeleteResponse {
+                    success: false,
+                    message: error_msg,
+                }),
+            ))
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::DateTime;
+use rocket::data::ToByteUnit;
+use rocket::http::{Header, Status};
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::{self, Responder, Response};
+use rocket::{Data, delete, head, options, patch, post};
+use std::io::Cursor;
+use tracing::{Level, error, info, span};
+
+use super::files::AuthenticatedUser;
+use crate::core::procedures::{
+    append_resumable_upload, create_resumable_upload, resumable_upload_status,
+    terminate_resumable_upload,
+};
+use crate::core::structs::TusUpload;
+use crate::core::{max_upload_size, tus_expiration_hours};
+
+/// Versión del protocolo tus implementada
+const TUS_VERSION: &str = "1.0.0";
+const TUS_EXTENSIONS: &str = "creation,termination,expiration";
+
+// ============================================================================
+// Headers y respuestas tus
+// ============================================================================
+
+/// Headers del protocolo tus presentes en la petición
+pub struct TusHeaders {
+    resumable: Option<String>,
+    upload_length: Option<String>,
+    upload_offset: Option<String>,
+    upload_metadata: Option<String>,
+    content_type: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for TusHeaders {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let headers = request.headers();
+        let get = |name: &str| headers.get_one(name).map(str::to_string);
+        request::Outcome::Success(TusHeaders {
+            resumable: get("Tus-Resumable"),
+            upload_length: get("Upload-Length"),
+            upload_offset: get("Upload-Offset"),
+            upload_metadata: get("Upload-Metadata"),
+            content_type: get("Content-Type"),
+        })
+    }
+}
+
+/// Respuesta tus: siempre incluye `Tus-Resumable` y no se cachea
+pub struct TusResponse {
+    status: Status,
+    headers: Vec<Header<'static>>,
+    body: Option<String>,
+}
+
+impl TusResponse {
+    fn new(status: Status) -> Self {
+        TusResponse {
+            status,
+            headers: vec![],
+            body: None,
+        }
+    }
+
+    fn header(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.headers.push(Header::new(name, value.to_string()));
+        self
+    }
+
+    fn error(status: Status, message: impl ToString) -> Self {
+        TusResponse {
+            status,
+            headers: vec![],
+            body: Some(message.to_string()),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for TusResponse {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .status(self.status)
+            .raw_header("Tus-Resumable", TUS_VERSION)
+            .raw_header("Cache-Control", "no-store");
+        for header in self.headers {
+            response.header(header);
+        }
+        if let Some(body) = self.body {
+            response.sized_body(body.len(), Cursor::new(body));
+        }
+        response.ok()
+    }
+}
+
+/// Traduce un error de los procedures a una respuesta tus
+fn tus_error(e: anyhow::Error) -> TusResponse {
+    let error_msg = e.to_string();
+
+    let status = if error_msg.contains("no encontrad") {
+        Status::NotFound
+    } else if error_msg.contains("no coincide") || error_msg.contains("en uso") {
+        Status::Conflict
+    } else if error_msg.contains("cuota") {
+        Status::InsufficientStorage
+    } else if error_msg.contains("excede") {
+        Status::PayloadTooLarge
+    } else if error_msg.contains("inválid") || error_msg.contains("vacío") {
+        Status::BadRequest
+    } else {
+        Status::InternalServerError
+    };
+
+    if status == Status::InternalServerError {
+        error!("Error en subida reanudable: {}", e);
+    }
+    TusResponse::error(status, error_msg)
+}
+
+/// Rechaza peticiones que no declaran la versión soportada del protocolo
+fn check_version(headers: &TusHeaders) -> Result<(), TusResponse> {
+    if headers.resumable.as_deref() == Some(TUS_VERSION) {
+        Ok(())
+    } else {
+        Err(
+            TusResponse::error(Status::PreconditionFailed, "Versión de tus no soportada")
+                .header("Tus-Version", TUS_VERSION),
+        )
+    }
+}
+
+/// Valor decodificado de la primera clave de `claves` presente en `Upload-Metadata`
+fn metadata_value(metadata: Option<&str>, claves: &[&str]) -> Option<String> {
+    metadata
+        .into_iter()
+        .flat_map(|m| m.split(','))
+        .filter_map(|par| par.trim().split_once(' '))
+        .find(|(clave, _)| claves.contains(clave))
+        .and_then(|(_, valor)| STANDARD.decode(valor.trim()).ok())
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+}
+
+/// Extrae el tipo MIME de `Upload-Metadata` (claves `filetype` o `mime`)
+fn mime_from_metadata(metadata: Option<&str>) -> String {
+    match metadata_value(metadata, &["filetype", "mime"]) {
+        Some(mime) if !mime.is_empty() && mime.contains('/') && mime.len() <= 100 => mime,
+        _ => "application/octet-stream".to_string(),
+    }
+}
+
+/// Fecha (formato HTTP) en la que expira la subida si no recibe más datos
+fn upload_expires(upload: &TusUpload) -> String {
+    let expira = upload.updated_at + tus_expiration_hours() * 3600;
+    DateTime::from_timestamp(expira, 0)
+        .unwrap_or_default()
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+/// Headers comunes con el estado de una subida
+fn with_upload_state(mut response: TusResponse, upload: &TusUpload) -> TusResponse {
+    response = response.header("Upload-Offset", upload.upload_offset);
+    match &upload.file_id {
+        Some(file_id) => response.header("Privafile-File-Id", file_id),
+        None => response.header("Upload-Expires", upload_expires(upload)),
+    }
+}
+
+// ============================================================================
+// Routes
+// ============================================================================
+
+/// Descubrimiento de capacidades tus
+///
+/// Endpoint: OPTIONS /api/files/tus
+#[options("/api/files/tus")]
+pub async fn tus_options() -> TusResponse {
+    TusResponse::new(Status::NoContent)
+        .header("Tus-Version", TUS_VERSION)
+        .header("Tus-Extension", TUS_EXTENSIONS)
+        .header("Tus-Max-Size", max_upload_size())
+}
+
+/// Crea una subida reanudable
+///
+/// Endpoint: POST /api/files/tus
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// Tus-Resumable: 1.0.0
+/// Upload-Length: <bytes totales>
+/// Upload-Metadata: filetype <mime en base64>,filename <nombre en base64>   (opcional)
+/// ```
+///
+/// Response: 201 con `Location` apuntando a la subida
+#[post("/api/files/tus")]
+pub async fn tus_create(user: AuthenticatedUser, headers: TusHeaders) -> TusResponse {
+    let span = span!(Level::INFO, "tus_create");
+    let _enter = span.enter();
+
+    if let Err(response) = check_version(&headers) {
+        return response;
+    }
+
+    let Some(length) = headers
+        .upload_length
+        .as_deref()
+        .and_then(|l| l.trim().parse::<u64>().ok())
+    else {
+        return TusResponse::error(Status::BadRequest, "Upload-Length inválido o ausente");
+    };
+
+    let mime = mime_from_metadata(headers.upload_metadata.as_deref());
+    let filename = metadata_value(headers.upload_metadata.as_deref(), &["filename", "name"]);
+    match create_resumable_upload(&user.user_id, &mime, filename.as_deref(), length).await {
+        Ok(upload) => {
+            info!("Subida reanudable creada: {}", upload.id);
+            TusResponse::new(Status::Created)
+                .header("Location", format!("/api/files/tus/{}", upload.id))
+                .header("Upload-Expires", upload_expires(&upload))
+        }
+        Err(e) => tus_error(e),
+    }
+}
+
+/// Consulta el offset actual de una subida
+///
+/// Endpoint: HEAD /api/files/tus/<upload_id>
+#[head("/api/files/tus/<upload_id>")]
+pub async fn tus_head(
+    user: AuthenticatedUser,
+    upload_id: String,
+    headers: TusHeaders,
+) -> TusResponse {
+    if let Err(response) = check_version(&headers) {
+        return response;
+    }
+
+    match resumable_upload_status(&user.user_id, &upload_id).await {
+        Ok(upload) => with_upload_state(TusResponse::new(Status::Ok), &upload)
+            .header("Upload-Length", upload.upload_length),
+        Err(e) => tus_error(e),
+    }
+}
+
+/// Envía un tramo de la subida a partir de `Upload-Offset`
+///
+/// Endpoint: PATCH /api/files/tus/<upload_id>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// Tus-Resumable: 1.0.0
+/// Content-Type: application/offset+octet-stream
+/// Upload-Offset: <offset actual>
+/// ```
+///
+/// Response: 204 con el nuevo `Upload-Offset`. Al completarse incluye
+/// `Privafile-File-Id` con el ID del archivo creado.
+#[patch("/api/files/tus/<upload_id>", data = "<data>")]
+pub async fn tus_patch(
+    user: AuthenticatedUser,
+    upload_id: String,
+    headers: TusHeaders,
+    data: Data<'_>,
+) -> TusResponse {
+    let span = span!(Level::INFO, "tus_patch");
+    let _enter = span.enter();
+
+    if let Err(response) = check_version(&headers) {
+        return response;
+    }
+
+    if headers.content_type.as_deref() != Some("application/offset+octet-stream") {
+        return TusResponse::error(
+            Status::UnsupportedMediaType,
+            "Content-Type debe ser application/offset+octet-stream",
+        );
+    }
+
+    let Some(offset) = headers
+        .upload_offset
+        .as_deref()
+        .and_then(|o| o.trim().parse::<u64>().ok())
+    else {
+        return TusResponse::error(Status::BadRequest, "Upload-Offset inválido o ausente");
+    };
+
+    // Un byte extra permite detectar cuerpos más largos que lo declarado
+    let upload = match resumable_upload_status(&user.user_id, &upload_id).await {
+        Ok(upload) => upload,
+        Err(e) => return tus_error(e),
+    };
+    let restante = (upload.upload_length - upload.upload_offset).max(0) as u64;
+    let mut stream = data.open(ToByteUnit::bytes(restante + 1));
+
+    match append_resumable_upload(&user.user_id, &upload_id, offset, &mut stream).await {
+        Ok(upload) => with_upload_state(TusResponse::new(Status::NoContent), &upload),
+        Err(e) => tus_error(e),
+    }
+}
+
+/// Cancela una subida y descarta los datos recibidos
+///
+/// Endpoint: DELETE /api/files/tus/<upload_id>
+#[delete("/api/files/tus/<upload_id>")]
+pub async fn tus_delete(
+    user: AuthenticatedUser,
+    upload_id: String,
+    headers: TusHeaders,
+) -> TusResponse {
+    if let Err(response) = check_version(&headers) {
+        return response;
+    }
+
+    match terminate_resumable_upload(&user.user_id, &upload_id).await {
+        Ok(_) => TusResponse::new(Status::NoContent),
+        Err(e) => tus_error(e),
+    }
+}
@@ -0,0 +1,385 @@
+use rocket::data::ToByteUnit;
+use rocket::serde::json::Json;
+use rocket::{Data, delete, get, http::Status, post, response::status::Custom};
+use tracing::{Level, error, info, span};
+
+use super::files::AuthenticatedUser;
+use super::shares::SharePassword;
+use crate::core::max_upload_size;
+use crate::core::procedures::{
+    create_upload_link, list_upload_link_files, list_upload_links, open_upload_link,
+    revoke_upload_link, upload_link_max_size, upload_link_mime_types, upload_via_link,
+};
+use crate::core::structs::{
+    CreateUploadLinkRequest, DeleteResponse, FileInfo, PublicUploadLinkInfo,
+    PublicUploadLinkResponse, UploadLink, UploadLinkFileInfo, UploadLinkFilesResponse,
+    UploadLinkInfo, UploadLinkListResponse, UploadLinkResponse, UploadResponse,
+};
+
+impl From<UploadLink> for UploadLinkInfo {
+    fn from(link: UploadLink) -> Self {
+        UploadLinkInfo {
+            url: format!("/api/public/uploads/{}", link.token),
+            allowed_mime_types: upload_link_mime_types(&link),
+            token: link.token,
+            folder_id: link.folder_id,
+            has_password: link.password_hash.is_some(),
+            expires_at: link.expires_at,
+            max_files: link.max_files,
+            max_file_size: link.max_file_size,
+            upload_count: link.upload_count,
+            created_at: link.created_at,
+        }
+    }
+}
+
+/// Traduce el mensaje de error de un procedure de enlaces de subida a un
+/// status HTTP
+fn upload_link_error_status(error_msg: &str) -> Status {
+    if error_msg.contains("no encontrad") {
+        Status::NotFound
+    } else if error_msg.contains("expirado") || error_msg.contains("límite de archivos") {
+        Status::Gone
+    } else if error_msg.contains("requiere contraseña") || error_msg.contains("incorrecta") {
+        Status::Unauthorized
+    } else if error_msg.contains("Demasiados intentos") {
+        Status::TooManyRequests
+    } else if error_msg.contains("no permitido") {
+        Status::UnsupportedMediaType
+    } else if error_msg.contains("cuota") {
+        Status::InsufficientStorage
+    } else if error_msg.contains("excede") {
+        Status::PayloadTooLarge
+    } else if error_msg.contains("Permiso insuficiente") {
+        Status::Forbidden
+    } else if error_msg.contains("inválid")
+        || error_msg.contains("vacío")
+        || error_msg.contains("leer datos")
+    {
+        Status::BadRequest
+    } else {
+        Status::InternalServerError
+    }
+}
+
+/// Respuesta de error de las rutas públicas; quien sube no debe conocer el
+/// uso ni la cuota del dueño
+fn public_upload_error(e: anyhow::Error) -> Custom<Json<UploadResponse>> {
+    let error_msg = e.to_string();
+    let status = upload_link_error_status(&error_msg);
+    let message = if status == Status::InsufficientStorage {
+        "El destino no tiene espacio disponible".to_string()
+    } else if status == Status::InternalServerError {
+        error!("Error al subir por enlace público: {}", e);
+        "Error al subir el archivo".to_string()
+    } else {
+        error_msg
+    };
+
+    Custom(
+        status,
+        Json(UploadResponse {
+            success: false,
+            message,
+            file_id: None,
+        }),
+    )
+}
+
+// ============================================================================
+// Routes
+// ============================================================================
+
+/// Ruta para crear un enlace de subida a una carpeta
+///
+/// Endpoint: POST /api/folders/<folder_id>/upload-links
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// Content-Type: application/json
+/// ```
+///
+/// Body: `{"expires_in_hours": 72, "max_files": 10, "max_file_size": 10485760,
+/// "allowed_mime_types": ["image/*", "application/pdf"], "password": "..."}`,
+/// todos opcionales
+#[post("/api/folders/<folder_id>/upload-links", data = "<request>")]
+pub async fn create_upload_link_route(
+    user: AuthenticatedUser,
+    folder_id: String,
+    request: Json<CreateUploadLinkRequest>,
+) -> Result<Json<UploadLinkResponse>, Custom<Json<UploadLinkResponse>>> {
+    let span = span!(Level::INFO, "create_upload_link_route");
+    let _enter = span.enter();
+
+    let request = request.into_inner();
+    match create_upload_link(
+        &user.user_id,
+        &folder_id,
+        request.expires_in_hours,
+        request.max_files,
+        request.max_file_size,
+        &request.allowed_mime_types,
+        request.password.as_deref(),
+    )
+    .await
+    {
+        Ok(link) => Ok(Json(UploadLinkResponse {
+            success: true,
+            message: "Enlace de subida creado".to_string(),
+            link: Some(link.into()),
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = upload_link_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al crear enlace de subida para {}: {}", folder_id, e);
+            }
+
+            Err(Custom(
+                status,
+                Json(UploadLinkResponse {
+                    success: false,
+                    message: error_msg,
+                    link: None,
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta para listar los enlaces de subida del usuario
+///
+/// Endpoint: GET /api/upload-links?folder_id=<optional>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// Con `folder_id` solo se listan los enlaces de esa carpeta
+#[get("/api/upload-links?<folder_id>")]
+pub async fn list_upload_links_route(
+    user: AuthenticatedUser,
+    folder_id: Option<String>,
+) -> Result<Json<UploadLinkListResponse>, Custom<Json<UploadLinkListResponse>>> {
+    let span = span!(Level::INFO, "list_upload_links_route");
+    let _enter = span.enter();
+
+    match list_upload_links(&user.user_id, folder_id.as_deref()).await {
+        Ok(links) => Ok(Json(UploadLinkListResponse {
+            success: true,
+            message: format!("{} enlace(s)", links.len()),
+            links: links.into_iter().map(UploadLinkInfo::from).collect(),
+        })),
+        Err(e) => {
+            error!("Error al listar enlaces de subida: {}", e);
+            Err(Custom(
+                Status::InternalServerError,
+                Json(UploadLinkListResponse {
+                    success: false,
+                    message: e.to_string(),
+                    links: vec![],
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta para revocar un enlace de subida
+///
+/// Endpoint: DELETE /api/upload-links/<token>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// Los archivos ya recibidos se quedan en la carpeta, pero se pierde el
+/// registro de quién subió cada uno.
+#[delete("/api/upload-links/<token>")]
+pub async fn revoke_upload_link_route(
+    user: AuthenticatedUser,
+    token: String,
+) -> Result<Json<DeleteResponse>, Custom<Json<DeleteResponse>>> {
+    let span = span!(Level::INFO, "revoke_upload_link_route");
+    let _enter = span.enter();
+
+    match revoke_upload_link(&user.user_id, &token).await {
+        Ok(_) => Ok(Json(DeleteResponse {
+            success: true,
+            message: "Enlace de subida revocado".to_string(),
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = upload_link_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al revocar enlace de subida: {}", e);
+            }
+
+            Err(Custom(
+                status,
+                Json(DeleteResponse {
+                    success: false,
+                    message: error_msg,
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta para ver qué se subió por un enlace y quién lo subió
+///
+/// Endpoint: GET /api/upload-links/<token>/files
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// `uploader_name` es el nombre que dio quien subió el archivo, sin
+/// verificar; `null` si no dio ninguno.
+#[get("/api/upload-links/<token>/files")]
+pub async fn upload_link_files_route(
+    user: AuthenticatedUser,
+    token: String,
+) -> Result<Json<UploadLinkFilesResponse>, Custom<Json<UploadLinkFilesResponse>>> {
+    let span = span!(Level::INFO, "upload_link_files_route");
+    let _enter = span.enter();
+
+    match list_upload_link_files(&user.user_id, &token).await {
+        Ok(entradas) => Ok(Json(UploadLinkFilesResponse {
+            success: true,
+            message: format!("{} archivo(s) recibido(s)", entradas.len()),
+            uploads: entradas
+                .into_iter()
+                .map(|entrada| UploadLinkFileInfo {
+                    file: FileInfo::from(entrada.file),
+                    uploader_name: entrada.upload.uploader_name,
+                    uploaded_at: entrada.upload.created_at,
+                })
+                .collect(),
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = upload_link_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al listar archivos del enlace de subida: {}", e);
+            }
+
+            Err(Custom(
+                status,
+                Json(UploadLinkFilesResponse {
+                    success: false,
+                    message: error_msg,
+                    uploads: vec![],
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta pública para consultar las condiciones de un enlace de subida
+///
+/// Endpoint: GET /api/public/uploads/<token>?password=<optional>
+///
+/// Headers:
+/// ```text
+/// Privafile-Share-Password: <contraseña>   (si el enlace la pide)
+/// ```
+///
+/// Solo devuelve lo necesario para subir (tipos aceptados, tamaño máximo,
+/// archivos restantes); nunca el nombre ni el contenido de la carpeta.
+#[get("/api/public/uploads/<token>")]
+pub async fn public_upload_link_route(
+    token: String,
+    password: SharePassword,
+) -> Result<Json<PublicUploadLinkResponse>, Custom<Json<PublicUploadLinkResponse>>> {
+    match open_upload_link(&token, password.0.as_deref()).await {
+        Ok(link) => Ok(Json(PublicUploadLinkResponse {
+            success: true,
+            message: "Enlace de subida vigente".to_string(),
+            link: Some(PublicUploadLinkInfo {
+                has_password: link.password_hash.is_some(),
+                expires_at: link.expires_at,
+                remaining_files: link.max_files.map(|max| max - link.upload_count),
+                max_file_size: upload_link_max_size(&link),
+                allowed_mime_types: upload_link_mime_types(&link),
+            }),
+        })),
+        Err(e) => {
+            let Custom(status, Json(respuesta)) = public_upload_error(e);
+            Err(Custom(
+                status,
+                Json(PublicUploadLinkResponse {
+                    success: false,
+                    message: respuesta.message,
+                    link: None,
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta pública para subir un archivo por un enlace, sin cuenta
+///
+/// Endpoint: POST /api/public/uploads/<token>?mime=<mime>&filename=<optional>&name=<optional>&password=<optional>
+///
+/// Headers:
+/// ```text
+/// Content-Type: application/octet-stream
+/// Privafile-Share-Password: <contraseña>   (si el enlace la pide)
+/// ```
+///
+/// Body: archivo binario raw. `name` es el nombre de quien sube, que verá
+/// el dueño del enlace. La respuesta no incluye el ID del archivo: quien
+/// sube no puede descargarlo ni listar la carpeta. Responde 401 si falta la
+/// contraseña o no coincide, 429 tras demasiadas contraseñas incorrectas,
+/// 410 si el enlace expiró o alcanzó su límite, 413 si el archivo supera el
+/// tamaño máximo y 415 si su tipo no se acepta.
+#[post(
+    "/api/public/uploads/<token>?<mime>&<filename>&<name>",
+    data = "<data>"
+)]
+pub async fn public_upload_route(
+    token: String,
+    mime: String,
+    filename: Option<String>,
+    name: Option<String>,
+    password: SharePassword,
+    data: Data<'_>,
+) -> Result<Json<UploadResponse>, Custom<Json<UploadResponse>>> {
+    let span = span!(Level::INFO, "public_upload_route");
+    let _enter = span.enter();
+
+    if mime.is_empty() || !mime.contains('/') || mime.len() > 100 {
+        return Err(Custom(
+            Status::BadRequest,
+            Json(UploadResponse {
+                success: false,
+                message: "El mime type es inválido".to_string(),
+                file_id: None,
+            }),
+        ));
+    }
+
+    // El límite propio del enlace lo aplica el procedure
+    let mut stream = data.open(ToByteUnit::bytes(max_upload_size() + 1));
+    let file_id = upload_via_link(
+        &token,
+        password.0.as_deref(),
+        &mime,
+        filename.as_deref(),
+        name.as_deref(),
+        &mut stream,
+    )
+    .await
+    .map_err(public_upload_error)?;
+
+    info!("Archivo {} recibido por enlace de subida", file_id);
+    Ok(Json(UploadResponse {
+        success: true,
+        message: "Archivo recibido".to_string(),
+        file_id: None,
+    }))
+}
@@ -0,0 +1,293 @@
+use rocket::data::ToByteUnit;
+use rocket::serde::json::Json;
+use rocket::{Data, delete, get, http::Status, post, response::status::Custom};
+use tracing::{Level, error, info, span};
+
+use super::files::AuthenticatedUser;
+use crate::core::max_upload_size;
+use crate::core::procedures::{
+    download_file_version, list_file_versions, promote_file_version, prune_file_versions,
+    upload_file_version,
+};
+use crate::core::structs::{
+    DeleteResponse, FileResponse, FileVersion, VersionInfo, VersionListResponse,
+};
+use crate::servers::http::download::{DownloadHeaders, RangedDownload, serve_download};
+
+impl From<FileVersion> for VersionInfo {
+    fn from(version: FileVersion) -> Self {
+        VersionInfo {
+            version: version.version,
+            hash: version.hash,
+            mime: version.mime,
+            size: version.size,
+            created_at: version.created_at,
+            current: false,
+        }
+    }
+}
+
+/// Traduce el mensaje de error de un procedure de versiones a un status HTTP
+fn version_error_status(error_msg: &str) -> Status {
+    if error_msg.contains("no encontrad") {
+        Status::NotFound
+    } else if error_msg.contains("cuota") {
+        Status::InsufficientStorage
+    } else if error_msg.contains("excede") {
+        Status::PayloadTooLarge
+    } else if error_msg.contains("ya es la versión actual") {
+        Status::Conflict
+    } else if error_msg.contains("inválid")
+        || error_msg.contains("vacío")
+        || error_msg.contains("leer datos")
+    {
+        Status::BadRequest
+    } else if error_msg.contains("Permiso insuficiente") {
+        Status::Forbidden
+    } else {
+        Status::InternalServerError
+    }
+}
+
+/// Respuesta de error común a las rutas que devuelven un archivo
+fn file_error(e: anyhow::Error) -> Custom<Json<FileResponse>> {
+    let error_msg = e.to_string();
+    let status = version_error_status(&error_msg);
+    if status == Status::InternalServerError {
+        error!("Error en operación de versiones: {}", e);
+    }
+
+    Custom(
+        status,
+        Json(FileResponse {
+            success: false,
+            message: error_msg,
+            file: None,
+        }),
+    )
+}
+
+// ============================================================================
+// Routes
+// ============================================================================
+
+/// Ruta para subir una nueva versión de un archivo existente
+///
+/// Endpoint: POST /api/files/<file_id>/versions?mime=<optional>
+///
+/// Headers:
+/// ```text
+/// Content-Type: application/octet-stream
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// Body: contenido binario raw de la nueva versión. Sin `mime` se conserva
+/// el tipo del archivo.
+#[post("/api/files/<file_id>/versions?<mime>", data = "<data>")]
+pub async fn upload_version_route(
+    user: AuthenticatedUser,
+    file_id: String,
+    mime: Option<String>,
+    data: Data<'_>,
+) -> Result<Json<FileResponse>, Custom<Json<FileResponse>>> {
+    let span = span!(Level::INFO, "upload_version_route");
+    let _enter = span.enter();
+
+    if let Some(mime) = &mime
+        && (mime.is_empty() || !mime.contains('/') || mime.len() > 100)
+    {
+        return Err(file_error(anyhow::anyhow!("El mime type es inválido")));
+    }
+
+    // Un byte extra permite detectar si se excede el límite
+    let mut stream = data.open(ToByteUnit::bytes(max_upload_size() + 1));
+
+    let file = upload_file_version(&user.user_id, &file_id, mime.as_deref(), &mut stream)
+        .await
+        .map_err(file_error)?;
+
+    info!("Versión {} subida para archivo {}", file.version, file_id);
+    Ok(Json(FileResponse {
+        success: true,
+        message: format!("Versión {} guardada", file.version),
+        file: Some(file.into()),
+    }))
+}
+
+/// Ruta para listar las versiones de un archivo
+///
+/// Endpoint: GET /api/files/<file_id>/versions
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// Response: versiones de la actual (`current: true`) a la más antigua
+#[get("/api/files/<file_id>/versions")]
+pub async fn list_versions_route(
+    user: AuthenticatedUser,
+    file_id: String,
+) -> Result<Json<VersionListResponse>, Custom<Json<VersionListResponse>>> {
+    let span = span!(Level::INFO, "list_versions_route");
+    let _enter = span.enter();
+
+    match list_file_versions(&user.user_id, &file_id).await {
+        Ok((file, anteriores)) => {
+            let mut versions = vec![VersionInfo {
+                version: file.version,
+                hash: file.hash,
+                mime: file.mime,
+                size: file.size,
+                created_at: file.version_created_at,
+                current: true,
+            }];
+            versions.extend(anteriores.into_iter().map(VersionInfo::from));
+
+            Ok(Json(VersionListResponse {
+                success: true,
+                message: format!("{} versión(es)", versions.len()),
+                versions,
+            }))
+        }
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = version_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al listar versiones de {}: {}", file_id, e);
+            }
+
+            Err(Custom(
+                status,
+                Json(VersionListResponse {
+                    success: false,
+                    message: error_msg,
+                    versions: vec![],
+                }),
+            ))
+        }
+    }
+}
+
+/// Ruta para descargar una versión anterior de un archivo
+///
+/// Endpoint: GET /api/files/<file_id>/versions/<version>/download?inline=<optional>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// Range: bytes=0-1023          (opcional, admite varios rangos)
+/// If-Range: "<etag>" o fecha   (opcional)
+/// If-None-Match: "<etag>"      (opcional)
+/// If-Modified-Since: <fecha>   (opcional)
+/// ```
+///
+/// Admite rangos y peticiones condicionales igual que la descarga normal. La versión actual se
+/// descarga con `/api/files/download/<file_id>`.
+#[get("/api/files/<file_id>/versions/<version>/download?<inline>")]
+pub async fn download_version_route(
+    user: AuthenticatedUser,
+    file_id: String,
+    version: i32,
+    inline: Option<bool>,
+    headers: DownloadHeaders,
+) -> Result<RangedDownload, Custom<String>> {
+    let span = span!(Level::INFO, "download_version_route");
+    let _enter = span.enter();
+
+    let resultado = match download_file_version(&user.user_id, &file_id, version).await {
+        Ok(file) => serve_download(&file, &headers, inline.unwrap_or(false)).await,
+        Err(e) => Err(e),
+    };
+
+    resultado.map_err(|e| {
+        let error_msg = e.to_string();
+        let status = version_error_status(&error_msg);
+        if status == Status::InternalServerError {
+            error!(
+                "Error al descargar la versión {} de {}: {}",
+                version, file_id, e
+            );
+        }
+        Custom(status, error_msg)
+    })
+}
+
+/// Ruta para convertir una versión anterior en la actual
+///
+/// Endpoint: POST /api/files/<file_id>/versions/<version>/promote
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// El contenido actual pasa al historial y la versión promovida se guarda
+/// como una versión nueva.
+#[post("/api/files/<file_id>/versions/<version>/promote")]
+pub async fn promote_version_route(
+    user: AuthenticatedUser,
+    file_id: String,
+    version: i32,
+) -> Result<Json<FileResponse>, Custom<Json<FileResponse>>> {
+    let span = span!(Level::INFO, "promote_version_route");
+    let _enter = span.enter();
+
+    let file = promote_file_version(&user.user_id, &file_id, version)
+        .await
+        .map_err(file_error)?;
+
+    Ok(Json(FileResponse {
+        success: true,
+        message: format!(
+            "Versión {} restaurada como versión {}",
+            version, file.version
+        ),
+        file: Some(file.into()),
+    }))
+}
+
+/// Ruta para eliminar versiones anteriores de un archivo
+///
+/// Endpoint: DELETE /api/files/<file_id>/versions?keep=<optional>&older_than_days=<optional>
+///
+/// Headers:
+/// ```text
+/// Authorization: Bearer <paseto-token>
+/// ```
+///
+/// Query params (al menos uno):
+/// - keep: cuántas versiones anteriores conservar (las más recientes)
+/// - older_than_days: eliminar las versiones subidas hace más de N días
+#[delete("/api/files/<file_id>/versions?<keep>&<older_than_days>")]
+pub async fn prune_versions_route(
+    user: AuthenticatedUser,
+    file_id: String,
+    keep: Option<usize>,
+    older_than_days: Option<i64>,
+) -> Result<Json<DeleteResponse>, Custom<Json<DeleteResponse>>> {
+    let span = span!(Level::INFO, "prune_versions_route");
+    let _enter = span.enter();
+
+    match prune_file_versions(&user.user_id, &file_id, keep, older_than_days).await {
+        Ok(eliminadas) => Ok(Json(DeleteResponse {
+            success: true,
+            message: format!("{} versión(es) eliminadas", eliminadas),
+        })),
+        Err(e) => {
+            let error_msg = e.to_string();
+            let status = version_error_status(&error_msg);
+            if status == Status::InternalServerError {
+                error!("Error al eliminar versiones de {}: {}", file_id, e);
+            }
+
+            Err(Custom(
+                status,
+                Json(DeleteResponse {
+                    success: false,
+                    message: error_msg,
+                }),
+            ))
+        }
+    }
+}